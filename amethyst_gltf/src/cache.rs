@@ -0,0 +1,97 @@
+//! Caching of the files glTF assets reference.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use assets::{Result, Source};
+
+/// An asset source that remembers the bytes of every file loaded through it, so buffers and
+/// images referenced by several glTF files are only read once.  A file is read again if the
+/// wrapped source reports it has been modified since.
+///
+/// Every scene import caches the files it reads this way, wrap the source given to the
+/// `Loader` in this to share the cache between imports.
+pub struct CachedSource {
+    source: Arc<Source>,
+    files: Mutex<HashMap<String, Arc<Mutex<Option<(u64, Vec<u8>)>>>>>,
+}
+
+impl CachedSource {
+    /// Creates a cache in front of the given source.
+    pub fn new(source: Arc<Source>) -> Self {
+        CachedSource {
+            source,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Source for CachedSource {
+    fn modified(&self, path: &str) -> Result<u64> {
+        self.source.modified(path)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>> {
+        // Every file has its own lock, so different files can be loaded at the same time while
+        // concurrent loads of the same file wait for the first one.
+        let file = self.files
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_insert_with(Default::default)
+            .clone();
+        let mut file = file.lock().unwrap();
+        let modified = self.source.modified(path).unwrap_or(0);
+        if let Some((cached, ref data)) = *file {
+            if cached == modified {
+                return Ok(data.clone());
+            }
+        }
+        let data = self.source.load(path)?;
+        *file = Some((modified, data.clone()));
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A source of files in memory, counting how many times each is loaded.
+    #[derive(Default)]
+    pub struct CountingSource {
+        pub files: HashMap<String, Vec<u8>>,
+        pub loads: Mutex<HashMap<String, usize>>,
+    }
+
+    impl CountingSource {
+        pub fn loads(&self, path: &str) -> usize {
+            self.loads.lock().unwrap().get(path).cloned().unwrap_or(0)
+        }
+    }
+
+    impl Source for CountingSource {
+        fn modified(&self, _: &str) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>> {
+            *self.loads.lock().unwrap().entry(path.to_string()).or_insert(0) += 1;
+            match self.files.get(path) {
+                Some(data) => Ok(data.clone()),
+                None => Err(format!("{} not found", path).into()),
+            }
+        }
+    }
+
+    #[test]
+    fn files_are_loaded_once() {
+        let mut source = CountingSource::default();
+        source.files.insert("a.bin".to_string(), vec![1, 2, 3]);
+        let counting = Arc::new(source);
+        let cached = CachedSource::new(counting.clone());
+        assert_eq!(cached.load("a.bin").unwrap(), vec![1, 2, 3]);
+        assert_eq!(cached.load("a.bin").unwrap(), vec![1, 2, 3]);
+        assert_eq!(counting.loads("a.bin"), 1);
+    }
+}
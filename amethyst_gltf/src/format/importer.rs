@@ -1,7 +1,10 @@
 use std;
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt;
-use std::path::Path;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::ptr;
 use std::sync::Arc;
 
 use assets::{Error as AssetError, Result as AssetResult, Source as AssetSource};
@@ -11,23 +14,109 @@ use gltf::Gltf;
 use gltf::json;
 use gltf::json::validation;
 use gltf_utils::Source;
+use rayon::prelude::*;
 
-#[derive(Debug)]
+use {GltfLight, GltfLightKind, ImportProgress, TextureTransform, ValidationLevel};
+
+/// The encoding of an image
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ImageFormat {
     Png,
     Jpeg,
 }
 
 impl ImageFormat {
-    fn from_mime_type(mime: &str) -> Self {
+    fn from_mime_type(mime: &str) -> Result<Self, Error> {
         match mime {
-            "image/jpeg" => ImageFormat::Jpeg,
-            "image/png" => ImageFormat::Png,
-            _ => unreachable!(),
+            "image/jpeg" => Ok(ImageFormat::Jpeg),
+            "image/png" => Ok(ImageFormat::Png),
+            _ => Err(Error::UnsupportedImageFormat(mime.to_string())),
+        }
+    }
+
+    fn from_extension(ext: &str) -> Result<Self, Error> {
+        match ext {
+            "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+            "png" => Ok(ImageFormat::Png),
+            _ => Err(Error::UnsupportedImageFormat(ext.to_string())),
         }
     }
 }
 
+/// The glTF extensions the importer understands.
+const SUPPORTED_EXTENSIONS: &[&str] = &[LIGHTS_PUNCTUAL, TEXTURE_TRANSFORM, UNLIT];
+
+/// The name of the extension adding directional, point and spot lights to nodes.
+const LIGHTS_PUNCTUAL: &str = "KHR_lights_punctual";
+
+/// The name of the extension transforming the texture coordinates of a texture reference.
+const TEXTURE_TRANSFORM: &str = "KHR_texture_transform";
+
+/// The name of the extension marking materials as unlit.
+const UNLIT: &str = "KHR_materials_unlit";
+
+/// The name of the extension compressing primitives with Draco, which has no decoder in Rust yet.
+/// Assets that only use it still carry uncompressed accessors, which are loaded instead.
+const DRACO_MESH_COMPRESSION: &str = "KHR_draco_mesh_compression";
+
+/// Extension data returned from `import` that the `gltf` crate doesn't parse.
+#[derive(Clone, Debug, Default)]
+pub struct Extensions {
+    materials: Vec<MaterialExtensions>,
+    target_names: Vec<Vec<String>>,
+    lights: Vec<GltfLight>,
+    node_lights: Vec<Option<usize>>,
+    node_extras: Vec<Option<json::Value>>,
+    mesh_extras: Vec<Option<json::Value>>,
+}
+
+impl Extensions {
+    /// The extensions of the material at the given index.
+    pub fn material(&self, material: usize) -> MaterialExtensions {
+        self.materials.get(material).cloned().unwrap_or_default()
+    }
+
+    /// The names of the morph targets of the mesh at the given index, exporters store them in
+    /// the `targetNames` extras of the mesh.
+    pub fn target_names(&self, mesh: usize) -> Vec<String> {
+        self.target_names.get(mesh).cloned().unwrap_or_default()
+    }
+
+    /// The lights of the asset, from `KHR_lights_punctual`.
+    pub fn lights(&self) -> Vec<GltfLight> {
+        self.lights.clone()
+    }
+
+    /// The index of the light attached to the node at the given index.
+    pub fn node_light(&self, node: usize) -> Option<usize> {
+        self.node_lights.get(node).cloned().unwrap_or(None)
+    }
+
+    /// The `extras` of the node at the given index.
+    pub fn node_extras(&self, node: usize) -> Option<json::Value> {
+        self.node_extras.get(node).cloned().unwrap_or(None)
+    }
+
+    /// The `extras` of every mesh.
+    pub fn mesh_extras(&self) -> Vec<Option<json::Value>> {
+        self.mesh_extras.clone()
+    }
+}
+
+/// The extensions used by a single material.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialExtensions {
+    /// Whether the material uses `KHR_materials_unlit`.
+    pub unlit: bool,
+    pub base_color: TextureTransform,
+    pub metallic_roughness: TextureTransform,
+    pub normal: TextureTransform,
+    pub occlusion: TextureTransform,
+    pub emissive: TextureTransform,
+    /// The `extras` of the material.
+    pub extras: Option<json::Value>,
+}
+
 /// Buffer data returned from `import`.
 #[derive(Clone, Debug)]
 pub struct Buffers(Vec<Vec<u8>>);
@@ -61,16 +150,68 @@ impl Buffers {
 }
 
 /// Imports glTF 2.0
-pub fn import<P>(source: Arc<AssetSource>, path: P) -> Result<(Gltf, Buffers), Error>
+pub fn import<P>(
+    source: Arc<AssetSource>,
+    path: P,
+    validation: ValidationLevel,
+    buffer_padding: usize,
+) -> Result<(Gltf, Buffers, Extensions), Error>
 where
     P: AsRef<Path>,
+{
+    let no_progress = |_: ImportProgress| {};
+    import_with_progress(source, path, validation, buffer_padding, &[], &no_progress)
+}
+
+/// Imports glTF 2.0, reporting the parsing of the JSON and each loaded buffer to `progress`.
+/// The supported extensions named in `disabled_extensions` are ignored, and assets requiring
+/// them fail to import.
+pub fn import_with_progress<P, F>(
+    source: Arc<AssetSource>,
+    path: P,
+    validation: ValidationLevel,
+    buffer_padding: usize,
+    disabled_extensions: &[String],
+    progress: &F,
+) -> Result<(Gltf, Buffers, Extensions), Error>
+where
+    P: AsRef<Path>,
+    F: Fn(ImportProgress) + ?Sized,
 {
     let path = path.as_ref();
     let data = read_to_end(source.clone(), path)?;
+    check_version(&data)?;
+    let disabled = disabled_extensions;
     if data.starts_with(b"glTF") {
-        import_binary(&data, source, path)
+        import_binary(&data, source, path, validation, buffer_padding, disabled, progress)
     } else {
-        import_standard(&data, source, path)
+        import_standard(&data, source, path, validation, buffer_padding, disabled, progress)
+    }
+}
+
+/// Catches glTF 1.0 assets before parsing, as they would fail with confusing parsing and
+/// validation errors.
+fn check_version(data: &[u8]) -> Result<(), Error> {
+    if data.starts_with(b"glTF") {
+        // The binary header has the container version after the magic.
+        if data.get(4..8) == Some(&[1, 0, 0, 0][..]) {
+            return Err(Error::IncompatibleVersion("1.0".to_string()));
+        }
+        return Ok(());
+    }
+    // Files that aren't valid JSON are left for the parser to report.
+    let root = match json::from_slice::<json::Value>(data) {
+        Ok(root) => root,
+        Err(_) => return Ok(()),
+    };
+    let version = root.get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(|version| version.as_str());
+    match version {
+        Some(version) if !version.starts_with("2.") => {
+            Err(Error::IncompatibleVersion(version.to_string()))
+        }
+        _ => Ok(()),
     }
 }
 
@@ -79,47 +220,392 @@ fn read_to_end<P: AsRef<Path>>(source: Arc<AssetSource>, path: P) -> AssetResult
     Ok(source.load(path.to_str().unwrap())?)
 }
 
+/// Parses a `data:[<media type>][;base64],<data>` URI.  Data without the `;base64` parameter is
+/// percent-encoded.
 fn parse_data_uri(uri: &str) -> Result<Vec<u8>, Error> {
-    let encoded = uri.split(",").nth(1).unwrap();
-    let decoded = base64::decode(&encoded)?;
-    Ok(decoded)
+    let comma = match uri.find(',') {
+        Some(comma) if uri.starts_with("data:") => comma,
+        _ => return Err(Error::InvalidDataUri),
+    };
+    let (header, payload) = (&uri["data:".len()..comma], &uri[comma + 1..]);
+    if header.split(';').any(|param| param.trim() == "base64") {
+        Ok(base64::decode(payload)?)
+    } else {
+        Ok(percent_decode(payload))
+    }
+}
+
+/// Decodes percent-encoded bytes in a URI, such as `%20` for a space.  If the decoded bytes
+/// aren't valid UTF-8 the URI is returned unchanged.
+fn decode_uri(uri: &str) -> String {
+    String::from_utf8(percent_decode(uri)).unwrap_or_else(|_| uri.to_string())
 }
 
-fn load_external_buffers(
+/// Decodes percent-encoded bytes, escapes that aren't followed by two hex digits are kept as is.
+fn percent_decode(uri: &str) -> Vec<u8> {
+    fn hex(byte: u8) -> Option<u8> {
+        match byte {
+            b'0'...b'9' => Some(byte - b'0'),
+            b'a'...b'f' => Some(byte - b'a' + 10),
+            b'A'...b'F' => Some(byte - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push(high * 16 + low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded
+}
+
+/// The path of a file referenced by an external URI, relative to the asset at `base_path`.
+/// `.` and `..` components are resolved, paths that would leave the root of the asset source are
+/// rejected.
+fn uri_path(base_path: &Path, uri: &str) -> Result<PathBuf, Error> {
+    use std::path::Component;
+    let joined = base_path
+        .parent()
+        .unwrap_or(Path::new("./"))
+        .join(decode_uri(uri));
+    let mut path = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(name) => path.push(name),
+            Component::ParentDir => if !path.pop() {
+                return Err(Error::UriOutsideSource(uri.to_string()));
+            },
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(Error::UriOutsideSource(uri.to_string()))
+            }
+        }
+    }
+    Ok(path)
+}
+
+fn load_external_buffers<F>(
     source: Arc<AssetSource>,
     base_path: &Path,
     gltf: &Gltf,
     mut bin: Option<Vec<u8>>,
-) -> Result<Vec<Vec<u8>>, Error> {
+    validation: ValidationLevel,
+    buffer_padding: usize,
+    progress: &F,
+) -> Result<Vec<Vec<u8>>, Error>
+where
+    F: Fn(ImportProgress) + ?Sized,
+{
+    // External files are loaded in parallel, the results stay in buffer order so the first error
+    // is returned like when loading them one by one.
+    let uris = gltf.buffers()
+        .map(|buffer| buffer.uri().to_string())
+        .collect::<Vec<_>>();
+    let mut files = uris.par_iter()
+        .map(|uri| {
+            if uri == "#bin" || uri.starts_with("data:") {
+                None
+            } else {
+                Some(
+                    uri_path(base_path, uri)
+                        .and_then(|path| Ok(read_to_end(source.clone(), path)?)),
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    let mut data_uris = HashMap::new();
     let mut buffers = vec![];
     for (index, buffer) in gltf.buffers().enumerate() {
         let uri = buffer.uri();
-        let data_res: Result<Vec<u8>, Error> = if uri == "#bin" {
-            Ok(bin.take().unwrap())
+        let file = files.next().unwrap();
+        let mut data = if uri == "#bin" {
+            match bin.take() {
+                Some(bin) => bin,
+                None => {
+                    use gltf::json::validation::Error as Reason;
+                    let path = json::Path::new().field("buffers").index(index).field("uri");
+                    return Err(Error::Validation(vec![(path, Reason::Missing)]));
+                }
+            }
         } else if uri.starts_with("data:") {
-            Ok(parse_data_uri(uri)?)
+            // Identical embedded buffers are only decoded once.
+            if !data_uris.contains_key(uri) {
+                data_uris.insert(uri.to_string(), parse_data_uri(uri)?);
+            }
+            data_uris[uri].clone()
         } else {
-            let path = base_path.parent().unwrap_or(Path::new("./")).join(uri);
-            Ok(read_to_end(source.clone(), &path)?)
+            file.unwrap()?
         };
-        let data = data_res?;
 
+        // Buffers longer than needed are fine, some exporters leave trailing bytes.
         if data.len() < buffer.length() {
-            let path = json::Path::new().field("buffers").index(index);
-            return Err(Error::BufferLength(path));
+            let shortfall = buffer.length() - data.len();
+            if shortfall <= buffer_padding {
+                warn!(
+                    "Buffer {} is {} bytes short, padding it with zeros",
+                    index, shortfall
+                );
+                data.resize(buffer.length(), 0);
+            } else if validation != ValidationLevel::None {
+                let path = json::Path::new().field("buffers").index(index);
+                return Err(Error::BufferLength(path, buffer.length(), data.len()));
+            }
         }
         buffers.push(data);
+        progress(ImportProgress::BufferLoaded {
+            index,
+            count: uris.len(),
+        });
     }
     Ok(buffers)
 }
 
-fn validate_standard(unvalidated: gltf::Unvalidated) -> Result<Gltf, Error> {
-    Ok(unvalidated.validate_completely()?)
+/// Fails on the first extension the asset requires that is unsupported or disabled, and warns
+/// about the extensions the asset uses that will be ignored.
+fn check_extensions(unvalidated: &gltf::Unvalidated, disabled: &[String]) -> Result<(), Error> {
+    let root = unvalidated.as_json();
+    for extension in &root.extensions_required {
+        if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+            return Err(Error::ExtensionUnsupported(extension.clone()));
+        }
+        if disabled.contains(extension) {
+            return Err(Error::ExtensionDisabled(extension.clone()));
+        }
+    }
+    for extension in &root.extensions_used {
+        if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+            warn!("The unsupported extension {} is ignored", extension);
+        } else if disabled.contains(extension) {
+            warn!("The disabled extension {} is ignored", extension);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the extensions of the JSON chunk that the `gltf` crate skips over, apart from the
+/// `disabled` ones.
+fn load_extensions(json: &[u8], disabled: &[String]) -> Result<Extensions, Error> {
+    let root: json::Value = json::from_slice(json)?;
+    let materials = match root.get("materials").and_then(|m| m.as_array()) {
+        Some(materials) => materials
+            .iter()
+            .map(|material| load_material_extensions(material, disabled))
+            .collect(),
+        None => Vec::new(),
+    };
+    let target_names = match root.get("meshes").and_then(|m| m.as_array()) {
+        Some(meshes) => meshes.iter().map(load_target_names).collect(),
+        None => Vec::new(),
+    };
+    let extras = |name: &str| match root.get(name).and_then(|values| values.as_array()) {
+        Some(values) => values
+            .iter()
+            .map(|value| value.get("extras").cloned())
+            .collect(),
+        None => Vec::new(),
+    };
+    let node_extras = extras("nodes");
+    let mesh_extras = extras("meshes");
+    let (lights, node_lights) = if disabled.iter().any(|extension| extension == LIGHTS_PUNCTUAL) {
+        (Vec::new(), Vec::new())
+    } else {
+        load_lights(&root)
+    };
+    Ok(Extensions {
+        materials,
+        target_names,
+        lights,
+        node_lights,
+        node_extras,
+        mesh_extras,
+    })
+}
+
+/// Reads the lights of `KHR_lights_punctual` and the light index of every node.
+fn load_lights(root: &json::Value) -> (Vec<GltfLight>, Vec<Option<usize>>) {
+    let lights = root.get("extensions")
+        .and_then(|extensions| extensions.get(LIGHTS_PUNCTUAL))
+        .and_then(|extension| extension.get("lights"))
+        .and_then(|lights| lights.as_array())
+        .map_or(Vec::new(), |lights| lights.iter().map(load_light).collect());
+    let node_lights = root.get("nodes")
+        .and_then(|nodes| nodes.as_array())
+        .map_or(Vec::new(), |nodes| {
+            nodes
+                .iter()
+                .enumerate()
+                .map(|(index, node)| {
+                    let light = node.get("extensions")
+                        .and_then(|extensions| extensions.get(LIGHTS_PUNCTUAL))
+                        .and_then(|extension| extension.get("light"))
+                        .and_then(|light| light.as_u64())
+                        .map(|light| light as usize);
+                    match light {
+                        Some(light) if light >= lights.len() => {
+                            warn!("Node {} uses the missing light {}", index, light);
+                            None
+                        }
+                        light => light,
+                    }
+                })
+                .collect()
+        });
+    (lights, node_lights)
 }
 
-fn validate_binary(unvalidated: gltf::Unvalidated, has_bin: bool) -> Result<Gltf, Error> {
+/// Reads a single light, properties left out take their default values from the extension.
+fn load_light(light: &json::Value) -> GltfLight {
+    let number = |value: &json::Value, name: &str| {
+        value
+            .get(name)
+            .and_then(|number| number.as_f64())
+            .map(|number| number as f32)
+    };
+    let color = light.get("color").and_then(|color| color.as_array());
+    let color = match color.map(|color| (color.get(0), color.get(1), color.get(2))) {
+        Some((Some(r), Some(g), Some(b))) => [
+            r.as_f64().unwrap_or(1.) as f32,
+            g.as_f64().unwrap_or(1.) as f32,
+            b.as_f64().unwrap_or(1.) as f32,
+        ],
+        _ => [1., 1., 1.],
+    };
+    let range = number(light, "range");
+    let kind = match light.get("type").and_then(|kind| kind.as_str()) {
+        Some("directional") => GltfLightKind::Directional,
+        Some("spot") => {
+            let angle = |name: &str, default: f32| {
+                light
+                    .get("spot")
+                    .and_then(|spot| number(spot, name))
+                    .unwrap_or(default)
+            };
+            GltfLightKind::Spot {
+                range,
+                inner_cone_angle: angle("innerConeAngle", 0.),
+                outer_cone_angle: angle("outerConeAngle", std::f32::consts::FRAC_PI_4),
+            }
+        }
+        kind => {
+            if kind != Some("point") {
+                warn!("Light of type {:?} is loaded as a point light", kind);
+            }
+            GltfLightKind::Point { range }
+        }
+    };
+    GltfLight {
+        name: light
+            .get("name")
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_string()),
+        color,
+        intensity: number(light, "intensity").unwrap_or(1.),
+        kind,
+    }
+}
+
+fn load_target_names(mesh: &json::Value) -> Vec<String> {
+    let names = mesh.get("extras")
+        .and_then(|extras| extras.get("targetNames"))
+        .and_then(|names| names.as_array());
+    match names {
+        Some(names) => names
+            .iter()
+            .map(|name| name.as_str().unwrap_or("").to_string())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn load_material_extensions(material: &json::Value, disabled: &[String]) -> MaterialExtensions {
+    let extension = |value: Option<&json::Value>, name: &str| {
+        if disabled.iter().any(|extension| extension == name) {
+            return None;
+        }
+        value
+            .and_then(|value| value.get("extensions"))
+            .and_then(|extensions| extensions.get(name))
+            .cloned()
+    };
+    let transform = |value: Option<&json::Value>| {
+        extension(value, TEXTURE_TRANSFORM)
+            .map(|transform| load_texture_transform(&transform))
+            .unwrap_or_default()
+    };
+    let pbr = material.get("pbrMetallicRoughness");
+    MaterialExtensions {
+        unlit: extension(Some(material), UNLIT).is_some(),
+        base_color: transform(pbr.and_then(|pbr| pbr.get("baseColorTexture"))),
+        metallic_roughness: transform(pbr.and_then(|pbr| pbr.get("metallicRoughnessTexture"))),
+        normal: transform(material.get("normalTexture")),
+        occlusion: transform(material.get("occlusionTexture")),
+        emissive: transform(material.get("emissiveTexture")),
+        extras: material.get("extras").cloned(),
+    }
+}
+
+/// Reads a `KHR_texture_transform` object, missing properties keep their identity values.
+fn load_texture_transform(transform: &json::Value) -> TextureTransform {
+    let number = |value: &json::Value| value.as_f64().map(|value| value as f32);
+    let pair = |name: &str, default: [f32; 2]| {
+        let values = transform.get(name).and_then(|values| values.as_array());
+        match values.map(|values| (values.get(0), values.get(1))) {
+            Some((Some(x), Some(y))) => [
+                number(x).unwrap_or(default[0]),
+                number(y).unwrap_or(default[1]),
+            ],
+            _ => default,
+        }
+    };
+    let identity = TextureTransform::default();
+    TextureTransform {
+        offset: pair("offset", identity.offset),
+        rotation: transform
+            .get("rotation")
+            .and_then(number)
+            .unwrap_or(identity.rotation),
+        scale: pair("scale", identity.scale),
+        tex_coord: transform
+            .get("texCoord")
+            .and_then(|tex_coord| tex_coord.as_u64())
+            .map(|tex_coord| tex_coord as u32),
+    }
+}
+
+fn validate_standard(
+    unvalidated: gltf::Unvalidated,
+    validation: ValidationLevel,
+) -> Result<Gltf, Error> {
+    match validation {
+        ValidationLevel::Complete => Ok(unvalidated.validate_completely()?),
+        ValidationLevel::Minimal => Ok(unvalidated.validate_minimally()?),
+        ValidationLevel::None => Ok(unvalidated.skip_validation()),
+    }
+}
+
+fn validate_binary(
+    unvalidated: gltf::Unvalidated,
+    has_bin: bool,
+    validation: ValidationLevel,
+) -> Result<Gltf, Error> {
     use gltf::json::validation::Error as Reason;
 
+    if validation == ValidationLevel::None {
+        return Ok(unvalidated.skip_validation());
+    }
+
     let mut errs = vec![];
     {
         let json = unvalidated.as_json();
@@ -138,78 +624,319 @@ fn validate_binary(unvalidated: gltf::Unvalidated, has_bin: bool) -> Result<Gltf
     }
 
     if errs.is_empty() {
-        Ok(unvalidated.validate_completely()?)
+        validate_standard(unvalidated, validation)
     } else {
         Err(Error::Validation(errs))
     }
 }
 
-fn import_standard(
+fn import_standard<F>(
     data: &[u8],
     source: Arc<AssetSource>,
     base_path: &Path,
-) -> Result<(Gltf, Buffers), Error> {
-    let gltf = validate_standard(Gltf::from_slice(data)?)?;
-    let buffers = Buffers(load_external_buffers(source, base_path, &gltf, None)?);
-    Ok((gltf, buffers))
+    validation: ValidationLevel,
+    buffer_padding: usize,
+    disabled_extensions: &[String],
+    progress: &F,
+) -> Result<(Gltf, Buffers, Extensions), Error>
+where
+    F: Fn(ImportProgress) + ?Sized,
+{
+    let unvalidated = Gltf::from_slice(data)?;
+    progress(ImportProgress::Parsed);
+    check_extensions(&unvalidated, disabled_extensions)?;
+    let gltf = validate_standard(unvalidated, validation)?;
+    let buffers = Buffers(load_external_buffers(
+        source,
+        base_path,
+        &gltf,
+        None,
+        validation,
+        buffer_padding,
+        progress,
+    )?);
+    validate_accessors(&gltf, &buffers, validation)?;
+    Ok((gltf, buffers, load_extensions(data, disabled_extensions)?))
 }
 
-fn import_binary(
+fn import_binary<F>(
     data: &[u8],
     source: Arc<AssetSource>,
     base_path: &Path,
-) -> Result<(Gltf, Buffers), Error> {
+    validation: ValidationLevel,
+    buffer_padding: usize,
+    disabled_extensions: &[String],
+    progress: &F,
+) -> Result<(Gltf, Buffers, Extensions), Error>
+where
+    F: Fn(ImportProgress) + ?Sized,
+{
     let gltf::Glb {
         header: _,
         json,
         bin,
     } = gltf::Glb::from_slice(data)?;
     let unvalidated = Gltf::from_slice(json)?;
+    progress(ImportProgress::Parsed);
+    check_extensions(&unvalidated, disabled_extensions)?;
     let bin = bin.map(|x| x.to_vec());
-    let gltf = validate_binary(unvalidated, bin.is_some())?;
-    let buffers = Buffers(load_external_buffers(source, base_path, &gltf, bin)?);
-    Ok((gltf, buffers))
+    let gltf = validate_binary(unvalidated, bin.is_some(), validation)?;
+    let buffers = Buffers(load_external_buffers(
+        source,
+        base_path,
+        &gltf,
+        bin,
+        validation,
+        buffer_padding,
+        progress,
+    )?);
+    validate_accessors(&gltf, &buffers, validation)?;
+    Ok((gltf, buffers, load_extensions(json, disabled_extensions)?))
+}
+
+/// Checks that buffer views fit in their loaded buffers, and that the elements of accessors fit
+/// in their buffer views, so reading them can't go out of bounds.
+fn validate_accessors(
+    gltf: &Gltf,
+    buffers: &Buffers,
+    validation: ValidationLevel,
+) -> Result<(), Error> {
+    use gltf::accessor::sparse::IndexType;
+    use gltf::json::validation::Error as Reason;
+
+    if validation == ValidationLevel::None {
+        return Ok(());
+    }
+
+    let mut errs = vec![];
+    for view in gltf.views() {
+        let length = buffers.buffer(&view.buffer()).map_or(0, |data| data.len());
+        if view.offset() + view.length() > length {
+            let path = json::Path::new().field("bufferViews").index(view.index());
+            errs.push((path.field("byteLength"), Reason::Invalid));
+        }
+    }
+    // The last element only needs its own size, not a whole stride.
+    let fits = |view: &gltf::buffer::View, offset: usize, count: usize, stride, size: usize| {
+        count == 0 || offset + stride * (count - 1) + size <= view.length()
+    };
+    for accessor in gltf.accessors() {
+        let path = || json::Path::new().field("accessors").index(accessor.index());
+        let size = accessor.size();
+        let view = accessor.view();
+        let stride = view.stride().unwrap_or(size);
+        if !fits(&view, accessor.offset(), accessor.count(), stride, size) {
+            errs.push((path().field("count"), Reason::Invalid));
+        }
+        if let Some(sparse) = accessor.sparse() {
+            let indices = sparse.indices();
+            let index_size = match indices.index_type() {
+                IndexType::U8 => 1,
+                IndexType::U16 => 2,
+                IndexType::U32 => 4,
+            };
+            let count = sparse.count() as usize;
+            if !fits(&indices.view(), indices.offset() as usize, count, index_size, index_size) {
+                let path = path().field("sparse").field("indices");
+                errs.push((path.field("bufferView"), Reason::Invalid));
+            }
+            let values = sparse.values();
+            if !fits(&values.view(), values.offset() as usize, count, size, size) {
+                let path = path().field("sparse").field("values");
+                errs.push((path.field("bufferView"), Reason::Invalid));
+            }
+        }
+    }
+
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation(errs))
+    }
+}
+
+/// Reads the elements of an accessor, substituting the values of a sparse accessor into them.
+pub fn read_accessor<T: Copy>(
+    accessor: &gltf::Accessor,
+    buffers: &Buffers,
+) -> Result<Vec<T>, Error> {
+    let size = accessor.size();
+    debug_assert_eq!(size, mem::size_of::<T>());
+    Ok(read_accessor_bytes(accessor, buffers)?
+        .chunks(size)
+        .map(|element| unsafe { ptr::read_unaligned(element.as_ptr() as *const T) })
+        .collect())
+}
+
+/// Reads the elements of an accessor packed one after another, substituting the values of a
+/// sparse accessor into them.
+pub fn read_accessor_bytes(accessor: &gltf::Accessor, buffers: &Buffers) -> Result<Vec<u8>, Error> {
+    use gltf::accessor::sparse::IndexType;
+    let size = accessor.size();
+    let view = accessor.view();
+    let base = buffers
+        .view(&view)
+        .and_then(|data| data.get(accessor.offset()..));
+    let stride = view.stride().unwrap_or(size);
+    let mut elements = read_elements(base, stride, accessor.count(), size)?;
+
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse.indices();
+        let index_size = match indices.index_type() {
+            IndexType::U8 => 1,
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        };
+        let index_data = buffers
+            .view(&indices.view())
+            .and_then(|data| data.get(indices.offset()..))
+            .ok_or(Error::AccessorBounds)?;
+        let indices = read_indices(index_data, index_size, sparse.count())?;
+        let values = sparse.values();
+        let value_data = buffers
+            .view(&values.view())
+            .and_then(|data| data.get(values.offset()..))
+            .ok_or(Error::AccessorBounds)?;
+        apply_sparse(&mut elements, size, &indices, value_data)?;
+    }
+    Ok(elements)
+}
+
+/// Copies `count` elements of `size` bytes that are `stride` bytes apart into a packed buffer,
+/// elements of accessors without a buffer view are all zeros.
+fn read_elements(
+    data: Option<&[u8]>,
+    stride: usize,
+    count: usize,
+    size: usize,
+) -> Result<Vec<u8>, Error> {
+    let data = match data {
+        Some(data) => data,
+        None => return Ok(vec![0; count * size]),
+    };
+    let mut elements = Vec::with_capacity(count * size);
+    for index in 0..count {
+        let start = index * stride;
+        let element = data.get(start..start + size).ok_or(Error::AccessorBounds)?;
+        elements.extend_from_slice(element);
+    }
+    Ok(elements)
+}
+
+/// Reads `count` little endian indices of `index_size` bytes.
+pub fn read_indices(data: &[u8], index_size: usize, count: usize) -> Result<Vec<usize>, Error> {
+    let data = data.get(..count * index_size).ok_or(Error::AccessorBounds)?;
+    Ok(data.chunks(index_size)
+        .map(|index| {
+            index
+                .iter()
+                .rev()
+                .fold(0, |value, &byte| value << 8 | byte as usize)
+        })
+        .collect())
 }
 
-pub fn get_image_data(
+/// Overwrites the elements at the given indices with the packed sparse values.
+fn apply_sparse(
+    elements: &mut [u8],
+    size: usize,
+    indices: &[usize],
+    values: &[u8],
+) -> Result<(), Error> {
+    for (i, &index) in indices.iter().enumerate() {
+        let value = values
+            .get(i * size..(i + 1) * size)
+            .ok_or(Error::AccessorBounds)?;
+        let element = elements
+            .get_mut(index * size..(index + 1) * size)
+            .ok_or(Error::AccessorBounds)?;
+        element.copy_from_slice(value);
+    }
+    Ok(())
+}
+
+/// Where the encoded data of an image is, so it can be read when it's needed.
+#[derive(Clone, Debug)]
+pub enum ImageReference {
+    /// A file of the asset source.
+    File(PathBuf),
+    /// A `data:` URI, decoded when the image is read.
+    DataUri(String),
+    /// The bytes of a buffer view, copied out of the buffer so it isn't kept alive.
+    View(Vec<u8>),
+}
+
+impl ImageReference {
+    /// Reads the encoded image.
+    pub fn read(&self, source: Arc<AssetSource>) -> Result<Vec<u8>, Error> {
+        match *self {
+            ImageReference::File(ref path) => Ok(source.load(path.to_str().unwrap())?),
+            ImageReference::DataUri(ref uri) => parse_data_uri(uri),
+            ImageReference::View(ref data) => Ok(data.clone()),
+        }
+    }
+}
+
+impl fmt::Display for ImageReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageReference::File(ref path) => write!(f, "{}", path.display()),
+            ImageReference::DataUri(_) => write!(f, "a data URI"),
+            ImageReference::View(_) => write!(f, "a buffer view"),
+        }
+    }
+}
+
+/// Finds the data of an image without reading it.
+pub fn get_image_reference(
     image: &gltf::Image,
     buffers: &Buffers,
-    source: Arc<AssetSource>,
     base_path: &Path,
-) -> Result<(Vec<u8>, ImageFormat), Error> {
+) -> Result<(ImageReference, ImageFormat), Error> {
     match image.data() {
         gltf::image::Data::View { view, mime_type } => {
             let data = buffers.view(&view).unwrap();
-            Ok((data.to_vec(), ImageFormat::from_mime_type(mime_type)))
+            Ok((
+                ImageReference::View(data.to_vec()),
+                ImageFormat::from_mime_type(mime_type)?,
+            ))
+        }
+
+        gltf::image::Data::Uri { uri, mime_type } if uri.starts_with("data:") => {
+            let mime_type = mime_type.unwrap_or(data_uri_media_type(uri));
+            let format = ImageFormat::from_mime_type(mime_type)?;
+            Ok((ImageReference::DataUri(uri.to_string()), format))
         }
 
         gltf::image::Data::Uri { uri, mime_type } => {
-            let path = base_path.parent().unwrap_or(Path::new("./")).join(uri);
-            let data = source.load(path.to_str().unwrap())?;
-            if let Some(ty) = mime_type {
-                Ok((data, ImageFormat::from_mime_type(ty)))
+            let path = uri_path(base_path, uri)?;
+            let format = if let Some(ty) = mime_type {
+                ImageFormat::from_mime_type(ty)?
             } else {
                 use std::ascii::AsciiExt;
                 let ext = path.extension()
                     .and_then(|s| s.to_str())
                     .map_or("".to_string(), |s| s.to_ascii_lowercase());
-                let format = match &ext[..] {
-                    "jpg" | "jpeg" => ImageFormat::Jpeg,
-                    "png" => ImageFormat::Png,
-                    _ => unreachable!(),
-                };
-                Ok((data, format))
-            }
+                ImageFormat::from_extension(&ext)?
+            };
+            Ok((ImageReference::File(path), format))
         }
     }
 }
 
+/// The media type of a `data:` URI, empty if it has none.
+fn data_uri_media_type(uri: &str) -> &str {
+    let header = uri["data:".len()..].split(',').next().unwrap_or("");
+    header.split(';').next().unwrap_or("").trim()
+}
+
 /// Error encountered when importing a glTF 2.0 asset.
 #[allow(unused)]
 #[derive(Debug)]
 pub enum Error {
-    /// A loaded glTF buffer is not of the required length.
-    BufferLength(json::Path),
+    /// A loaded glTF buffer is shorter than its required length, given as the path of the buffer,
+    /// the required length and the loaded length.
+    BufferLength(json::Path, usize, usize),
 
     /// Base 64 decoding error.
     Base64Decoding(base64::DecodeError),
@@ -223,6 +950,9 @@ pub enum Error {
     /// The glTF version of the asset is incompatible with the importer.
     IncompatibleVersion(String),
 
+    /// An external URI of the asset points outside of the asset source.
+    UriOutsideSource(String),
+
     /// Standard I/O error.
     Io(std::io::Error),
 
@@ -237,6 +967,15 @@ pub enum Error {
 
     /// Asset error
     Asset(AssetError),
+
+    /// An image uses a MIME type or file extension that can't be decoded.
+    UnsupportedImageFormat(String),
+
+    /// A `data:` URI has no `,` separating its header from its data.
+    InvalidDataUri,
+
+    /// An accessor reads outside of its buffer views, or a sparse index is out of range.
+    AccessorBounds,
 }
 
 impl From<AssetError> for Error {
@@ -281,7 +1020,48 @@ impl From<base64::DecodeError> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use std::error::Error;
-        write!(f, "{}", self.description())
+        match *self {
+            self::Error::UnsupportedImageFormat(ref format) => {
+                write!(f, "{}: {}", self.description(), format)
+            }
+            self::Error::BufferLength(ref path, expected, actual) => write!(
+                f,
+                "{} at {}: expected {} bytes, found {}",
+                self.description(),
+                path,
+                expected,
+                actual
+            ),
+            self::Error::Validation(ref errors) => {
+                write!(f, "{}:", self.description())?;
+                for &(ref path, ref reason) in errors {
+                    write!(f, "\n    {}: {}", path, reason)?;
+                }
+                Ok(())
+            }
+            self::Error::ExtensionUnsupported(ref extension)
+                if extension == DRACO_MESH_COMPRESSION =>
+            {
+                write!(
+                    f,
+                    "{}: {}, decompress the meshes of the asset to load it",
+                    self.description(),
+                    extension
+                )
+            }
+            self::Error::ExtensionDisabled(ref extension)
+            | self::Error::ExtensionUnsupported(ref extension) => {
+                write!(f, "{}: {}", self.description(), extension)
+            }
+            self::Error::UriOutsideSource(ref uri) => write!(f, "{}: {}", self.description(), uri),
+            self::Error::IncompatibleVersion(ref version) => write!(
+                f,
+                "{}: the asset is version {}, convert it to glTF 2.0 to load it",
+                self.description(),
+                version
+            ),
+            _ => write!(f, "{}", self.description()),
+        }
     }
 }
 
@@ -290,15 +1070,19 @@ impl StdError for Error {
         use self::Error::*;
         match *self {
             Base64Decoding(_) => "Base 64 decoding failed",
-            BufferLength(_) => "Loaded buffer does not match required length",
+            BufferLength(..) => "Loaded buffer is shorter than its required length",
             ExtensionDisabled(_) => "Asset requires a disabled extension",
             ExtensionUnsupported(_) => "Assets requires an unsupported extension",
             IncompatibleVersion(_) => "Asset is not glTF version 2.0",
+            UriOutsideSource(_) => "Asset references a file outside of its source",
             Io(_) => "I/O error",
             Gltf(_) => "Error from gltf crate",
             MalformedJson(_) => "Malformed .gltf / .glb JSON",
             Validation(_) => "Asset failed validation tests",
             Asset(_) => "Failed loading file from source",
+            UnsupportedImageFormat(_) => "Image format is not supported",
+            InvalidDataUri => "Malformed data URI",
+            AccessorBounds => "Accessor reads outside of its data",
         }
     }
 
@@ -311,3 +1095,432 @@ impl StdError for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_buffer_uris_resolve_to_the_file() {
+        let path = uri_path(Path::new("models/scene.gltf"), "my%20model.bin").unwrap();
+        assert_eq!(path, Path::new("models/my model.bin"));
+        let path = uri_path(Path::new("scene.gltf"), "caf%C3%A9.bin").unwrap();
+        assert_eq!(path, Path::new("café.bin"));
+    }
+
+    #[test]
+    fn parent_directories_in_uris_are_resolved_within_the_source() {
+        let base = Path::new("models/props/scene.gltf");
+        let path = uri_path(base, "../textures/./wood.png").unwrap();
+        assert_eq!(path, Path::new("models/textures/wood.png"));
+        let path = uri_path(base, "../../shared.bin").unwrap();
+        assert_eq!(path, Path::new("shared.bin"));
+        for uri in &["../../../secret.bin", "/etc/passwd", "a/../../../../b.bin"] {
+            match uri_path(base, uri) {
+                Err(Error::UriOutsideSource(ref outside)) if outside == uri => {}
+                other => panic!("expected {} to be rejected, got {:?}", uri, other),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_escapes_are_kept() {
+        assert_eq!(decode_uri("100%.bin"), "100%.bin");
+        assert_eq!(decode_uri("a%zzb%2"), "a%zzb%2");
+        assert_eq!(decode_uri("%FF.bin"), "%FF.bin");
+    }
+
+    #[test]
+    fn data_uris_are_decoded_by_encoding() {
+        let base64 = "data:application/octet-stream;base64,AAEC";
+        assert_eq!(parse_data_uri(base64).unwrap(), vec![0, 1, 2]);
+        let encoded = "data:text/plain;charset=utf-8,a%20b,c";
+        assert_eq!(parse_data_uri(encoded).unwrap(), b"a b,c".to_vec());
+        assert_eq!(parse_data_uri("data:,").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn unlit_materials_are_read_from_extensions() {
+        let json = br#"{
+            "materials": [
+                {},
+                { "extensions": { "KHR_materials_unlit": {} } }
+            ]
+        }"#;
+        let extensions = load_extensions(json, &[]).unwrap();
+        assert!(!extensions.material(0).unlit);
+        assert!(extensions.material(1).unlit);
+        assert!(!extensions.material(2).unlit);
+    }
+
+    #[test]
+    fn disabled_extensions_are_not_read() {
+        let json = br#"{
+            "materials": [{ "extensions": { "KHR_materials_unlit": {} } }]
+        }"#;
+        let disabled = vec![UNLIT.to_string()];
+        assert!(!load_extensions(json, &disabled).unwrap().material(0).unlit);
+    }
+
+    #[test]
+    fn required_extensions_are_checked_by_name() {
+        let json = |required: &str| {
+            format!(
+                r#"{{ "asset": {{ "version": "2.0" }}, "extensionsRequired": [{}] }}"#,
+                required
+            )
+        };
+        let check = |required: &str, disabled: &[String]| {
+            let unvalidated = Gltf::from_slice(json(required).as_bytes()).unwrap();
+            check_extensions(&unvalidated, disabled)
+        };
+        assert!(check(r#""KHR_materials_unlit""#, &[]).is_ok());
+        match check(r#""KHR_materials_unlit", "KHR_draco_mesh_compression""#, &[]) {
+            Err(Error::ExtensionUnsupported(ref name)) if name == "KHR_draco_mesh_compression" => {}
+            other => panic!("expected an unsupported extension, got {:?}", other),
+        }
+        match check(r#""KHR_materials_unlit""#, &[UNLIT.to_string()]) {
+            Err(Error::ExtensionDisabled(ref name)) if name == UNLIT => {}
+            other => panic!("expected a disabled extension, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn draco_compression_asks_for_decompressed_meshes() {
+        let error = Error::ExtensionUnsupported(DRACO_MESH_COMPRESSION.to_string());
+        assert!(format!("{}", error).ends_with("decompress the meshes of the asset to load it"));
+    }
+
+    #[test]
+    fn punctual_lights_are_read_with_their_nodes() {
+        let json = br#"{
+            "extensions": {
+                "KHR_lights_punctual": {
+                    "lights": [
+                        { "type": "directional", "color": [1, 0.5, 0], "intensity": 3 },
+                        { "type": "spot", "range": 10, "spot": { "innerConeAngle": 0.25 } },
+                        { "name": "bulb", "type": "point" }
+                    ]
+                }
+            },
+            "nodes": [
+                { "extensions": { "KHR_lights_punctual": { "light": 2 } } },
+                {},
+                { "extensions": { "KHR_lights_punctual": { "light": 7 } } }
+            ]
+        }"#;
+        let extensions = load_extensions(json, &[]).unwrap();
+        assert_eq!(
+            extensions.lights(),
+            vec![
+                GltfLight {
+                    name: None,
+                    color: [1., 0.5, 0.],
+                    intensity: 3.,
+                    kind: GltfLightKind::Directional,
+                },
+                GltfLight {
+                    name: None,
+                    color: [1., 1., 1.],
+                    intensity: 1.,
+                    kind: GltfLightKind::Spot {
+                        range: Some(10.),
+                        inner_cone_angle: 0.25,
+                        outer_cone_angle: std::f32::consts::FRAC_PI_4,
+                    },
+                },
+                GltfLight {
+                    name: Some("bulb".to_string()),
+                    color: [1., 1., 1.],
+                    intensity: 1.,
+                    kind: GltfLightKind::Point { range: None },
+                },
+            ]
+        );
+        assert_eq!(extensions.node_light(0), Some(2));
+        assert_eq!(extensions.node_light(1), None);
+        assert_eq!(extensions.node_light(2), None);
+    }
+
+    #[test]
+    fn extras_are_kept_as_json() {
+        let json = br#"{
+            "materials": [{ "extras": { "footsteps": "metal" } }, {}],
+            "meshes": [{ "primitives": [], "extras": { "collision": false } }],
+            "nodes": [{}, { "extras": { "spawn": ["player", 1] } }]
+        }"#;
+        let extensions = load_extensions(json, &[]).unwrap();
+        let extras = |text: &str| Some(json::from_slice::<json::Value>(text.as_bytes()).unwrap());
+        assert_eq!(extensions.material(0).extras, extras(r#"{ "footsteps": "metal" }"#));
+        assert_eq!(extensions.material(1).extras, None);
+        assert_eq!(extensions.mesh_extras(), vec![extras(r#"{ "collision": false }"#)]);
+        assert_eq!(extensions.node_extras(0), None);
+        assert_eq!(extensions.node_extras(1), extras(r#"{ "spawn": ["player", 1] }"#));
+        assert_eq!(extensions.node_extras(2), None);
+    }
+
+    #[test]
+    fn target_names_are_read_from_mesh_extras() {
+        let json = br#"{
+            "meshes": [
+                { "extras": { "targetNames": ["smile", "blink"] } },
+                { "primitives": [] }
+            ]
+        }"#;
+        let extensions = load_extensions(json, &[]).unwrap();
+        assert_eq!(extensions.target_names(0), vec!["smile", "blink"]);
+        assert!(extensions.target_names(1).is_empty());
+    }
+
+    #[test]
+    fn texture_transforms_are_read_per_texture_reference() {
+        let json = br#"{
+            "materials": [{
+                "pbrMetallicRoughness": {
+                    "baseColorTexture": {
+                        "index": 0,
+                        "extensions": {
+                            "KHR_texture_transform": {
+                                "offset": [0.5, 0.25],
+                                "rotation": 1.5,
+                                "texCoord": 1
+                            }
+                        }
+                    }
+                },
+                "normalTexture": {
+                    "index": 0,
+                    "extensions": { "KHR_texture_transform": { "scale": [2, 4] } }
+                },
+                "emissiveTexture": { "index": 0 }
+            }]
+        }"#;
+        let material = load_extensions(json, &[]).unwrap().material(0);
+        assert_eq!(
+            material.base_color,
+            TextureTransform {
+                offset: [0.5, 0.25],
+                rotation: 1.5,
+                scale: [1., 1.],
+                tex_coord: Some(1),
+            }
+        );
+        assert_eq!(
+            material.normal,
+            TextureTransform {
+                scale: [2., 4.],
+                ..TextureTransform::default()
+            }
+        );
+        assert_eq!(material.emissive, TextureTransform::default());
+        assert_eq!(material.occlusion, TextureTransform::default());
+    }
+
+    #[test]
+    fn sparse_values_replace_the_base_elements() {
+        // Four u16 elements, 4 bytes apart in the buffer view.
+        let base = [1, 0, 0xff, 0xff, 2, 0, 0xff, 0xff, 3, 0, 0xff, 0xff, 4, 0];
+        let mut elements = read_elements(Some(&base), 4, 4, 2).unwrap();
+        assert_eq!(elements, vec![1, 0, 2, 0, 3, 0, 4, 0]);
+        let indices = read_indices(&[3, 0, 1, 0], 2, 2).unwrap();
+        assert_eq!(indices, vec![3, 1]);
+        apply_sparse(&mut elements, 2, &indices, &[40, 0, 0, 1]).unwrap();
+        assert_eq!(elements, vec![1, 0, 0, 1, 3, 0, 40, 0]);
+    }
+
+    #[test]
+    fn sparse_values_without_a_base_start_from_zero() {
+        let mut elements = read_elements(None, 4, 3, 4).unwrap();
+        apply_sparse(&mut elements, 4, &[1], &[1, 2, 3, 4]).unwrap();
+        assert_eq!(elements, vec![0, 0, 0, 0, 1, 2, 3, 4, 0, 0, 0, 0]);
+        match apply_sparse(&mut elements, 4, &[3], &[1, 2, 3, 4]) {
+            Err(Error::AccessorBounds) => {}
+            other => panic!("expected an out of bounds index, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn buffers_sharing_a_file_load_it_once() {
+        use cache::tests::CountingSource;
+        use cache::CachedSource;
+
+        let mut source = CountingSource::default();
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [
+                { "uri": "shared%20data.bin", "byteLength": 4 },
+                { "uri": "shared data.bin", "byteLength": 4 },
+                { "uri": "data:application/octet-stream;base64,AAECAw==", "byteLength": 4 }
+            ]
+        }"#;
+        source
+            .files
+            .insert("models/scene.gltf".to_string(), scene.to_vec());
+        source
+            .files
+            .insert("models/shared data.bin".to_string(), vec![4, 5, 6, 7]);
+        let counting = Arc::new(source);
+        let (_, buffers, _) =
+            import(
+                Arc::new(CachedSource::new(counting.clone())),
+                "models/scene.gltf",
+                ValidationLevel::Complete,
+                0,
+            ).unwrap();
+        assert_eq!(
+            buffers.take(),
+            vec![vec![4, 5, 6, 7], vec![4, 5, 6, 7], vec![0, 1, 2, 3]]
+        );
+        assert_eq!(counting.loads("models/shared data.bin"), 1);
+    }
+
+    #[test]
+    fn only_unvalidated_imports_accept_short_buffers() {
+        use cache::tests::CountingSource;
+
+        let mut source = CountingSource::default();
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "short.bin", "byteLength": 8 }]
+        }"#;
+        source.files.insert("scene.gltf".to_string(), scene.to_vec());
+        source.files.insert("short.bin".to_string(), vec![0; 4]);
+        let source = Arc::new(source);
+        for &validation in &[ValidationLevel::Complete, ValidationLevel::Minimal] {
+            match import(source.clone(), "scene.gltf", validation, 0) {
+                Err(Error::BufferLength(_, 8, 4)) => {}
+                other => panic!("expected a buffer length error, got {:?}", other.err()),
+            }
+        }
+        assert!(import(source, "scene.gltf", ValidationLevel::None, 0).is_ok());
+    }
+
+    #[test]
+    fn short_buffers_are_padded_within_the_tolerance() {
+        use cache::tests::CountingSource;
+
+        let mut source = CountingSource::default();
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [
+                { "uri": "short.bin", "byteLength": 4 },
+                { "uri": "long.bin", "byteLength": 2 }
+            ]
+        }"#;
+        source.files.insert("scene.gltf".to_string(), scene.to_vec());
+        source.files.insert("short.bin".to_string(), vec![1, 2]);
+        source.files.insert("long.bin".to_string(), vec![3, 4, 5]);
+        let source = Arc::new(source);
+        match import(source.clone(), "scene.gltf", ValidationLevel::Complete, 1) {
+            Err(Error::BufferLength(_, 4, 2)) => {}
+            other => panic!("expected a buffer length error, got {:?}", other.err()),
+        }
+        let (_, buffers, _) =
+            import(source, "scene.gltf", ValidationLevel::Complete, 2).unwrap();
+        assert_eq!(buffers.take(), vec![vec![1, 2, 0, 0], vec![3, 4, 5]]);
+    }
+
+    #[test]
+    fn accessors_past_the_end_of_their_views_fail_validation() {
+        use cache::tests::CountingSource;
+
+        let mut source = CountingSource::default();
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "data.bin", "byteLength": 24 }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 24 },
+                { "buffer": 0, "byteOffset": 12, "byteLength": 24 }
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" },
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }
+            ]
+        }"#;
+        source.files.insert("scene.gltf".to_string(), scene.to_vec());
+        source.files.insert("data.bin".to_string(), vec![0; 24]);
+        let source = Arc::new(source);
+        match import(source.clone(), "scene.gltf", ValidationLevel::Complete, 0) {
+            Err(Error::Validation(errs)) => {
+                let paths = errs.iter().map(|e| e.0.to_string()).collect::<Vec<_>>();
+                assert_eq!(paths, vec!["bufferViews[1].byteLength", "accessors[1].count"]);
+            }
+            other => panic!("expected a validation error, got {:?}", other.err()),
+        }
+        assert!(import(source, "scene.gltf", ValidationLevel::None, 0).is_ok());
+    }
+
+    #[test]
+    fn progress_is_reported_for_each_buffer() {
+        use cache::tests::CountingSource;
+        use std::cell::RefCell;
+
+        let mut source = CountingSource::default();
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [
+                { "uri": "a.bin", "byteLength": 1 },
+                { "uri": "data:application/octet-stream;base64,AA==", "byteLength": 1 }
+            ]
+        }"#;
+        source.files.insert("scene.gltf".to_string(), scene.to_vec());
+        source.files.insert("a.bin".to_string(), vec![0]);
+        let stages = RefCell::new(Vec::new());
+        import_with_progress(
+            Arc::new(source),
+            "scene.gltf",
+            ValidationLevel::Complete,
+            0,
+            &[],
+            &|stage| stages.borrow_mut().push(stage),
+        ).unwrap();
+        assert_eq!(
+            stages.into_inner(),
+            vec![
+                ImportProgress::Parsed,
+                ImportProgress::BufferLoaded { index: 0, count: 2 },
+                ImportProgress::BufferLoaded { index: 1, count: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn old_versions_are_reported() {
+        let json = br#"{ "asset": { "version": "1.0" } }"#;
+        match check_version(json) {
+            Err(Error::IncompatibleVersion(ref version)) if version == "1.0" => {}
+            other => panic!("expected an incompatible version, got {:?}", other),
+        }
+        let binary = b"glTF\x01\x00\x00\x00";
+        match check_version(binary) {
+            Err(Error::IncompatibleVersion(_)) => {}
+            other => panic!("expected an incompatible version, got {:?}", other),
+        }
+        assert!(check_version(br#"{ "asset": { "version": "2.0" } }"#).is_ok());
+        assert!(check_version(b"not json").is_ok());
+    }
+
+    #[test]
+    fn validation_errors_display_their_paths() {
+        let path = json::Path::new().field("meshes").index(3).field("primitives");
+        let error = Error::Validation(vec![(path, validation::Error::Missing)]);
+        let display = format!("{}", error);
+        assert!(display.starts_with("Asset failed validation tests:"));
+        assert!(display.contains("meshes[3].primitives: "));
+    }
+
+    #[test]
+    fn data_uri_media_types_are_read_from_the_header() {
+        assert_eq!(data_uri_media_type("data:image/png;base64,AAAA"), "image/png");
+        assert_eq!(data_uri_media_type("data:;base64,AAAA"), "");
+        assert_eq!(data_uri_media_type("data:image/jpeg,%FF"), "image/jpeg");
+    }
+
+    #[test]
+    fn data_uris_without_a_separator_are_rejected() {
+        match parse_data_uri("data:application/octet-stream;base64") {
+            Err(Error::InvalidDataUri) => {}
+            other => panic!("expected an invalid data URI, got {:?}", other),
+        }
+    }
+}
@@ -1,19 +1,25 @@
 //! GLTF format
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt;
 use std::sync::Arc;
 
-use self::importer::{get_image_data, import, Buffers, ImageFormat};
+pub use self::importer::{ImageFormat, ImageReference};
+
+use self::importer::{get_image_reference, import_with_progress, read_accessor, read_accessor_bytes,
+                     read_indices, Buffers, Extensions, MaterialExtensions};
 use animation::{AnimationOutput, InterpolationType, Sampler};
 use assets::{Error as AssetError, Format, FormatValue, Result as AssetResult, ResultExt, Source};
 use core::transform::LocalTransform;
+use fnv::FnvHashMap;
 use gfx::Primitive;
 use gfx::texture::SamplerInfo;
 use gltf;
 use gltf::Gltf;
 use itertools::Itertools;
+use rayon::prelude::*;
 use renderer::{Color, JpgFormat, Normal, PngFormat, Position, Separate, Tangent, TexCoord,
                TextureMetadata};
 
@@ -37,12 +43,46 @@ pub enum GltfError {
     /// GLTF have no default scene and the number of scenes is not 1
     InvalidSceneGltf(usize),
 
-    /// GLTF primitive use a primitive type not support by gfx
-    PrimitiveMissingInGfx(String),
+    /// GLTF has no scene at the index chosen in the options, given the index and the number of
+    /// scenes
+    MissingScene(usize, usize),
+
+    /// GLTF has no node with the name chosen in the options
+    MissingNode(String),
+
+    /// GLTF has several nodes with the name chosen in the options, given the name and the number
+    /// of nodes
+    AmbiguousNodeName(String, usize),
 
     /// GLTF primitive missing positions
     MissingPositions,
 
+    /// GLTF nodes don't form trees, the node has several parents or is part of a cycle
+    InvalidHierarchy(usize),
+
+    /// GLTF animation sampler has the wrong number of outputs for its inputs and interpolation,
+    /// given the expected and actual number of outputs
+    InvalidSamplerOutput(usize, usize),
+
+    /// GLTF vertex colors use a component type or dimensions the specification doesn't allow
+    UnsupportedColorFormat(String),
+
+    /// GLTF texture coordinates use a component type the specification doesn't allow
+    UnsupportedTexCoordFormat(String),
+
+    /// GLTF joint weights use a component type the specification doesn't allow
+    UnsupportedWeightFormat(String),
+
+    /// GLTF indices use a component type other than unsigned bytes, shorts or ints
+    UnsupportedIndexFormat(String),
+
+    /// GLTF primitive isn't made of triangles, so normals can't be generated for it
+    NormalGenerationTopology(String),
+
+    /// GLTF morph target doesn't have an offset for every vertex of its primitive, given the
+    /// number of vertices and offsets
+    MorphTargetLength(usize, usize),
+
     /// External file failed loading
     Asset(AssetError),
 
@@ -56,8 +96,18 @@ impl StdError for GltfError {
         match *self {
             GltfImporterError(_) => "Gltf import error",
             InvalidSceneGltf(_) => "Gltf has no default scene, and the number of scenes is not 1",
-            PrimitiveMissingInGfx(_) => "Primitive missing in gfx",
+            MissingScene(..) => "Gltf has no scene at the chosen index",
+            MissingNode(_) => "Gltf has no node with the chosen name",
+            AmbiguousNodeName(..) => "Gltf has several nodes with the chosen name",
             MissingPositions => "Primitive missing positions",
+            InvalidHierarchy(_) => "Node has several parents or is its own ancestor",
+            InvalidSamplerOutput(..) => "Animation sampler outputs don't match its inputs",
+            UnsupportedColorFormat(_) => "Vertex colors have an unsupported format",
+            UnsupportedTexCoordFormat(_) => "Texture coordinates have an unsupported format",
+            UnsupportedWeightFormat(_) => "Joint weights have an unsupported format",
+            UnsupportedIndexFormat(_) => "Indices have an unsupported format",
+            NormalGenerationTopology(_) => "Normals can only be generated for triangle lists",
+            MorphTargetLength(..) => "Morph target doesn't match the vertices of its primitive",
             Asset(_) => "File loading error",
             NotImplemented => "Not implemented",
         }
@@ -77,12 +127,42 @@ impl fmt::Display for GltfError {
         use self::GltfError::*;
         use std::error::Error;
         match *self {
-            GltfImporterError(ref err) => {
-                write!(f, "{}: {}", self.description(), err.description())
+            GltfImporterError(ref err) => write!(f, "{}: {}", self.description(), err),
+            UnsupportedColorFormat(ref format)
+            | UnsupportedTexCoordFormat(ref format)
+            | UnsupportedWeightFormat(ref format)
+            | UnsupportedIndexFormat(ref format) => {
+                write!(f, "{}: {}", self.description(), format)
+            }
+            NormalGenerationTopology(ref mode) => write!(f, "{}: {}", self.description(), mode),
+            MissingNode(ref name) => write!(f, "{}: {}", self.description(), name),
+            AmbiguousNodeName(ref name, count) => {
+                write!(f, "{}: {} nodes are named {}", self.description(), count, name)
             }
-            PrimitiveMissingInGfx(ref err) => write!(f, "{}: {}", self.description(), err),
             Asset(ref err) => write!(f, "{}: {}", self.description(), err.description()),
             InvalidSceneGltf(size) => write!(f, "{}: {}", self.description(), size),
+            MissingScene(index, count) => write!(
+                f,
+                "{}: scene {} of {}",
+                self.description(),
+                index,
+                count
+            ),
+            InvalidHierarchy(node) => write!(f, "{}: {}", self.description(), node),
+            InvalidSamplerOutput(expected, found) => write!(
+                f,
+                "{}: expected {} outputs, found {}",
+                self.description(),
+                expected,
+                found
+            ),
+            MorphTargetLength(vertices, offsets) => write!(
+                f,
+                "{}: {} vertices, {} offsets",
+                self.description(),
+                vertices,
+                offsets
+            ),
             MissingPositions | NotImplemented => write!(f, "{}", self.description()),
         }
     }
@@ -113,7 +193,7 @@ impl Format<GltfSceneAsset> for GltfSceneFormat {
         _create_reload: bool,
     ) -> AssetResult<FormatValue<GltfSceneAsset>> {
         let gltf = load_gltf(source, &name, options).chain_err(|| "Failed to import gltf scene")?;
-        if gltf.default_scene.is_some() || gltf.scenes.len() == 1 {
+        if gltf.root_node.is_some() || gltf.default_scene.is_some() || gltf.scenes.len() == 1 {
             Ok(FormatValue::data(gltf)) // TODO: create `Reload` object
         } else {
             Err(GltfError::InvalidSceneGltf(gltf.scenes.len())).chain_err(|| "Invalid GLTF scene")
@@ -126,58 +206,244 @@ fn load_gltf(
     name: &str,
     options: GltfSceneOptions,
 ) -> Result<GltfSceneAsset, GltfError> {
-    import(source.clone(), name)
-        .map_err(GltfError::GltfImporterError)
-        .and_then(|(gltf, buffers)| {
-            load_data(&gltf, &buffers, &options, source, name)
+    // Buffers and images referenced several times are only loaded once.  Deferred images are read
+    // from the uncached source, as the cache would keep every loaded buffer alive with them.
+    let cached: Arc<Source> = Arc::new(CachedSource::new(source.clone()));
+    let images = if options.defer_images {
+        source
+    } else {
+        cached.clone()
+    };
+    let no_progress = |_: ImportProgress| {};
+    let progress: &(Fn(ImportProgress) + Sync) = match options.progress {
+        Some(ref callback) => &*callback.0,
+        None => &no_progress,
+    };
+    import_with_progress(
+        cached,
+        name,
+        options.validation,
+        options.buffer_padding,
+        &options.disabled_extensions,
+        progress,
+    ).map_err(GltfError::GltfImporterError)
+        .and_then(|(gltf, buffers, extensions)| {
+            load_data(&gltf, &buffers, &extensions, &options, images, name, progress)
         })
 }
 
 fn load_data(
     gltf: &Gltf,
     buffers: &Buffers,
+    extensions: &Extensions,
     options: &GltfSceneOptions,
     source: Arc<Source>,
     name: &str,
+    progress: &(Fn(ImportProgress) + Sync),
 ) -> Result<GltfSceneAsset, GltfError> {
-    // TODO: skins, animations, morph targets, cameras
     // TODO: KHR_materials_common extension
-    let nodes = load_nodes(gltf, buffers, options)?;
+    let root_node = match options.node {
+        Some(ref name) => Some(find_node(gltf, name)?),
+        None => None,
+    };
+    let scene_count = gltf.scenes().count();
+    let included = match (root_node, options.scene) {
+        (Some(index), _) => reachable_nodes(gltf, gltf.nodes().nth(index).into_iter().collect()),
+        (None, Some(index)) if index >= scene_count => {
+            return Err(GltfError::MissingScene(index, scene_count))
+        }
+        (None, Some(index)) => {
+            let roots = gltf.scenes().nth(index).unwrap().nodes().collect();
+            reachable_nodes(gltf, roots)
+        }
+        (None, None) => vec![true; gltf.nodes().count()],
+    };
+    let nodes = load_nodes(gltf, buffers, extensions, &included, options)?;
     let scenes = gltf.scenes()
         .map(|ref scene| load_scene(scene))
         .collect::<Result<Vec<GltfScene>, GltfError>>()?;
-    let default_scene = gltf.default_scene().map(|s| s.index());
-    let materials = gltf.materials()
-        .map(|ref m| load_material(m, buffers, source.clone(), name))
+    let default_scene = options
+        .scene
+        .or_else(|| gltf.default_scene().map(|s| s.index()));
+    // Materials no included node uses only find their images, without decoding them.
+    let all_used = options.scene.is_none() && root_node.is_none();
+    let mut used_materials = vec![all_used; gltf.materials().count()];
+    for material in nodes
+        .iter()
+        .flat_map(|node| node.primitives.iter())
+        .filter_map(|primitive| primitive.material)
+    {
+        used_materials[material] = true;
+    }
+    let deferred_options = GltfSceneOptions {
+        defer_images: true,
+        ..options.clone()
+    };
+    // Materials load their images in parallel.
+    let image_count = gltf.images().count();
+    let image_decoded = |index: usize| {
+        progress(ImportProgress::ImageDecoded {
+            index,
+            count: image_count,
+        })
+    };
+    let materials = (0..gltf.materials().count())
+        .into_par_iter()
+        .map(|index| {
+            let material = gltf.materials().nth(index).unwrap();
+            let extensions = extensions.material(index);
+            let source = source.clone();
+            let options = if used_materials[index] {
+                options
+            } else {
+                &deferred_options
+            };
+            load_material(&material, extensions, buffers, source, name, options, &image_decoded)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
         .collect::<Result<Vec<GltfMaterial>, GltfError>>()?;
     let animations = if options.load_animations {
         gltf.animations()
-            .map(|ref animation| load_animation(animation, buffers))
+            .map(|ref animation| load_animation(animation, buffers, options))
             .collect::<Result<Vec<GltfAnimation>, GltfError>>()?
     } else {
         Vec::default()
     };
+    let cameras = gltf.cameras()
+        .map(|ref camera| load_camera(camera, options.scale))
+        .collect();
+    let lights = extensions
+        .lights()
+        .into_iter()
+        .map(|light| scaled_light(light, options.scale))
+        .collect();
+    let skins = gltf.skins()
+        .map(|ref skin| load_skin(skin, buffers, options))
+        .collect::<Result<Vec<GltfSkin>, GltfError>>()?;
+    let names = load_names(gltf, &animations);
     Ok(GltfSceneAsset {
         nodes,
         scenes,
         materials,
         animations,
+        cameras,
+        lights,
+        skins,
         default_scene,
+        root_node,
+        names,
+        mesh_extras: extensions.mesh_extras(),
         options: options.clone(),
     })
 }
 
+/// Indexes the names of nodes, meshes and materials, and of the loaded animations.
+fn load_names(gltf: &Gltf, animations: &[GltfAnimation]) -> GltfNames {
+    fn index<I>(names: I) -> FnvHashMap<String, Vec<usize>>
+    where
+        I: Iterator<Item = Option<String>>,
+    {
+        let mut map = FnvHashMap::default();
+        for (index, name) in names.enumerate() {
+            if let Some(name) = name {
+                map.entry(name).or_insert_with(Vec::new).push(index);
+            }
+        }
+        map
+    }
+    let name = |name: Option<&str>| name.map(|name| name.to_string());
+    GltfNames {
+        nodes: index(gltf.nodes().map(|node| name(node.name()))),
+        meshes: index(gltf.meshes().map(|mesh| name(mesh.name()))),
+        materials: index(gltf.materials().map(|material| name(material.name()))),
+        animations: index(animations.iter().map(|animation| animation.name.clone())),
+    }
+}
+
+/// Scales the range of a light along with the positions of the asset.
+fn scaled_light(mut light: GltfLight, scale: f32) -> GltfLight {
+    match light.kind {
+        GltfLightKind::Point { ref mut range } | GltfLightKind::Spot { ref mut range, .. } => {
+            *range = range.map(|range| range * scale);
+        }
+        GltfLightKind::Directional => {}
+    }
+    light
+}
+
+/// Loads a camera, scaling its clipping planes and orthographic extents with the positions of
+/// the asset.  Angles and aspect ratios are unchanged by a uniform scale.
+fn load_camera(camera: &gltf::Camera, scale: f32) -> GltfCamera {
+    use gltf::camera::Projection;
+    match camera.projection() {
+        Projection::Perspective(perspective) => GltfCamera::Perspective {
+            aspect_ratio: perspective.aspect_ratio(),
+            yfov: perspective.yfov(),
+            znear: perspective.znear() * scale,
+            zfar: perspective.zfar().map(|zfar| zfar * scale),
+        },
+        Projection::Orthographic(orthographic) => GltfCamera::Orthographic {
+            xmag: orthographic.xmag() * scale,
+            ymag: orthographic.ymag() * scale,
+            znear: orthographic.znear() * scale,
+            zfar: orthographic.zfar() * scale,
+        },
+    }
+}
+
+fn load_skin(
+    skin: &gltf::Skin,
+    buffers: &Buffers,
+    options: &GltfSceneOptions,
+) -> Result<GltfSkin, GltfError> {
+    let joints = skin.joints().map(|joint| joint.index()).collect::<Vec<_>>();
+    let inverse_bind_matrices = match skin.inverse_bind_matrices() {
+        Some(matrices) => read_accessor::<[[f32; 4]; 4]>(&matrices, buffers)?
+            .into_iter()
+            .map(|matrix| {
+                let mut matrix = if options.z_up {
+                    z_up_matrix(matrix)
+                } else {
+                    matrix
+                };
+                // Only the translation changes, as a uniform scale commutes with the rest.
+                for component in &mut matrix[3][..3] {
+                    *component *= options.scale;
+                }
+                matrix
+            })
+            .collect(),
+        None => {
+            let identity = [
+                [1., 0., 0., 0.],
+                [0., 1., 0., 0.],
+                [0., 0., 1., 0.],
+                [0., 0., 0., 1.],
+            ];
+            vec![identity; joints.len()]
+        }
+    };
+    Ok(GltfSkin {
+        joints,
+        inverse_bind_matrices,
+        skeleton: skin.skeleton().map(|skeleton| skeleton.index()),
+    })
+}
+
 fn load_animation(
     animation: &gltf::Animation,
     buffers: &Buffers,
+    options: &GltfSceneOptions,
 ) -> Result<GltfAnimation, GltfError> {
     let (nodes, samplers) = animation
         .channels()
-        .map(|ref channel| load_channel(channel, buffers))
+        .map(|ref channel| load_channel(channel, buffers, options))
         .collect::<Result<Vec<(usize, Sampler)>, GltfError>>()?
         .into_iter()
         .unzip();
     Ok(GltfAnimation {
+        name: animation.name().map(|name| name.to_string()),
         nodes,
         samplers,
         handle: None,
@@ -187,18 +453,23 @@ fn load_animation(
 fn load_channel(
     channel: &gltf::animation::Channel,
     buffers: &Buffers,
+    options: &GltfSceneOptions,
 ) -> Result<(usize, Sampler), GltfError> {
     use gltf::animation::TrsProperty::*;
-    use gltf_utils::AccessorIter;
     let sampler = channel.sampler();
     let target = channel.target();
-    let input = gltf_utils::AccessorIter::new(sampler.input(), buffers).collect::<Vec<f32>>();
+    let input = read_accessor::<f32>(&sampler.input(), buffers)?;
     let node_index = target.node().index();
     let ty = map_interpolation_type(&sampler.interpolation());
 
     match target.path() {
         Translation => {
-            let output = AccessorIter::new(sampler.output(), buffers).collect::<Vec<[f32; 3]>>();
+            let mut output = vec3s(&read_floats(&sampler.output(), buffers)?);
+            check_sampler_output(&ty, input.len(), output.len())?;
+            if options.z_up {
+                output = output.into_iter().map(z_up).collect();
+            }
+            let output = scaled(output, options.scale);
             Ok((
                 node_index,
                 Sampler {
@@ -209,7 +480,11 @@ fn load_channel(
             ))
         }
         Scale => {
-            let output = AccessorIter::new(sampler.output(), buffers).collect::<Vec<[f32; 3]>>();
+            let mut output = vec3s(&read_floats(&sampler.output(), buffers)?);
+            check_sampler_output(&ty, input.len(), output.len())?;
+            if options.z_up {
+                output = output.into_iter().map(z_up_scale).collect();
+            }
             Ok((
                 node_index,
                 Sampler {
@@ -220,7 +495,11 @@ fn load_channel(
             ))
         }
         Rotation => {
-            let output = AccessorIter::new(sampler.output(), buffers).collect::<Vec<[f32; 4]>>();
+            let mut output = vec4s(&read_floats(&sampler.output(), buffers)?);
+            check_sampler_output(&ty, input.len(), output.len())?;
+            if options.z_up {
+                output = output.into_iter().map(z_up_rotation).collect();
+            }
             let ty = if ty == InterpolationType::Linear {
                 InterpolationType::SphericalLinear
             } else {
@@ -239,6 +518,26 @@ fn load_channel(
     }
 }
 
+/// Cubic splines store an in-tangent, the value and an out-tangent for every keyframe,
+/// Catmull-Rom splines have an extra control point at both ends, other interpolations only store
+/// the value.
+fn check_sampler_output(
+    ty: &InterpolationType,
+    inputs: usize,
+    outputs: usize,
+) -> Result<(), GltfError> {
+    let expected = match *ty {
+        InterpolationType::CubicSpline => inputs * 3,
+        InterpolationType::CatmullRomSpline => inputs + 2,
+        _ => inputs,
+    };
+    if outputs == expected {
+        Ok(())
+    } else {
+        Err(GltfError::InvalidSamplerOutput(expected, outputs))
+    }
+}
+
 fn map_interpolation_type(ty: &gltf::animation::InterpolationAlgorithm) -> InterpolationType {
     use gltf::animation::InterpolationAlgorithm::*;
 
@@ -253,33 +552,41 @@ fn map_interpolation_type(ty: &gltf::animation::InterpolationAlgorithm) -> Inter
 // Load a single material, and transform into a format usable by the engine
 fn load_material(
     material: &gltf::Material,
+    extensions: MaterialExtensions,
     buffers: &Buffers,
     source: Arc<Source>,
     name: &str,
+    options: &GltfSceneOptions,
+    image_decoded: &Fn(usize),
 ) -> Result<GltfMaterial, GltfError> {
-    let base_color = load_texture_with_factor(
+    let mut base_color = load_texture_with_factor(
         material.pbr_metallic_roughness().base_color_texture(),
         material.pbr_metallic_roughness().base_color_factor(),
         buffers,
         source.clone(),
         name,
-    ).map(|(texture, factor)| (GltfTexture::new(texture), factor))?;
+        options,
+        image_decoded,
+    )?;
 
-    let (metallic, roughness) = load_texture_with_factor(
+    let (mut metallic, mut roughness) = load_texture_with_factor(
         material
             .pbr_metallic_roughness()
             .metallic_roughness_texture(),
+        // Laid out like the texture, roughness in the green channel and metallic in the blue one.
         [
-            material.pbr_metallic_roughness().metallic_factor(),
-            material.pbr_metallic_roughness().roughness_factor(),
             1.0,
+            material.pbr_metallic_roughness().roughness_factor(),
+            material.pbr_metallic_roughness().metallic_factor(),
             1.0,
         ],
         buffers,
         source.clone(),
         name,
+        options,
+        image_decoded,
     ).map(|(texture, factors)| {
-        deconstruct_metallic_roughness(texture, factors[0], factors[1])
+        deconstruct_metallic_roughness(texture, factors[2], factors[1])
     })?;
 
     let double_sided = material.double_sided();
@@ -293,25 +600,27 @@ fn load_material(
     );
 
     let em_factor = material.emissive_factor();
-    let emissive = load_texture_with_factor(
+    let mut emissive = load_texture_with_factor(
         material.emissive_texture(),
         [em_factor[0], em_factor[1], em_factor[2], 1.0],
         buffers,
         source.clone(),
         name,
-    ).map(|(texture, factor)| {
-        (GltfTexture::new(texture), [factor[0], factor[1], factor[2]])
-    })?;
+        options,
+        image_decoded,
+    ).map(|(texture, factor)| (texture, [factor[0], factor[1], factor[2]]))?;
 
     // Can't use map/and_then because of Result returning from the load_texture function
-    let normal = match material.normal_texture() {
+    let mut normal = match material.normal_texture() {
         Some(normal_texture) => Some((
-            GltfTexture::new(load_texture(
+            load_texture(
                 &normal_texture.texture(),
                 buffers,
                 source.clone(),
                 name,
-            )?),
+                options,
+                image_decoded,
+            )?,
             normal_texture.scale(),
         )),
 
@@ -319,20 +628,64 @@ fn load_material(
     };
 
     // Can't use map/and_then because of Result returning from the load_texture function
-    let occlusion = match material.occlusion_texture() {
+    let mut occlusion = match material.occlusion_texture() {
         Some(occlusion_texture) => Some((
-            GltfTexture::new(load_texture(
+            load_texture(
                 &occlusion_texture.texture(),
                 buffers,
                 source.clone(),
                 name,
-            )?),
+                options,
+                image_decoded,
+            )?,
             occlusion_texture.strength(),
         )),
 
         None => None,
     };
 
+    let pbr = material.pbr_metallic_roughness();
+    let set = |texture: &mut GltfTexture, info: Option<gltf::texture::Info>| {
+        if let Some(info) = info {
+            texture.index = Some(info.texture().index());
+            texture.tex_coord = info.tex_coord();
+        }
+    };
+    let extensions = if options.flip_v {
+        MaterialExtensions {
+            base_color: flip_v_transform(extensions.base_color),
+            metallic_roughness: flip_v_transform(extensions.metallic_roughness),
+            normal: flip_v_transform(extensions.normal),
+            occlusion: flip_v_transform(extensions.occlusion),
+            emissive: flip_v_transform(extensions.emissive),
+            ..extensions
+        }
+    } else {
+        extensions
+    };
+    set(&mut base_color.0, pbr.base_color_texture());
+    base_color.0.transform = extensions.base_color;
+    set(&mut metallic.0, pbr.metallic_roughness_texture());
+    metallic.0.transform = extensions.metallic_roughness;
+    set(&mut roughness.0, pbr.metallic_roughness_texture());
+    roughness.0.transform = extensions.metallic_roughness;
+    set(&mut emissive.0, material.emissive_texture());
+    emissive.0.transform = extensions.emissive;
+    if let (Some(&mut (ref mut texture, _)), Some(info)) =
+        (normal.as_mut(), material.normal_texture())
+    {
+        texture.index = Some(info.texture().index());
+        texture.tex_coord = info.tex_coord();
+        texture.transform = extensions.normal;
+    }
+    if let (Some(&mut (ref mut texture, _)), Some(info)) =
+        (occlusion.as_mut(), material.occlusion_texture())
+    {
+        texture.index = Some(info.texture().index());
+        texture.tex_coord = info.tex_coord();
+        texture.transform = extensions.occlusion;
+    }
+
     Ok(GltfMaterial {
         base_color,
         metallic,
@@ -342,23 +695,27 @@ fn load_material(
         emissive,
         alpha,
         double_sided,
+        unlit: extensions.unlit,
+        extras: extensions.extras,
     })
 }
 
 fn deconstruct_metallic_roughness(
-    data: TextureData,
+    texture: GltfTexture,
     metallic_factor: f32,
     roughness_factor: f32,
 ) -> ((GltfTexture, f32), (GltfTexture, f32)) {
+    let channel = |offset| {
+        let mut channel = GltfTexture::new(deconstruct_image(&texture.data, offset, 4));
+        channel.deferred = texture.deferred.clone().map(|mut image| {
+            image.channel = Some(offset);
+            image
+        });
+        channel
+    };
     (
-        (
-            GltfTexture::new(deconstruct_image(&data, 2, 4)), // metallic from B channel
-            metallic_factor,
-        ),
-        (
-            GltfTexture::new(deconstruct_image(&data, 1, 4)), // roughness from G channel
-            roughness_factor,
-        ),
+        (channel(2), metallic_factor),  // metallic from B channel
+        (channel(1), roughness_factor), // roughness from G channel
     )
 }
 
@@ -393,13 +750,18 @@ fn load_texture_with_factor(
     buffers: &Buffers,
     source: Arc<Source>,
     name: &str,
-) -> Result<(TextureData, [f32; 4]), GltfError> {
+    options: &GltfSceneOptions,
+    image_decoded: &Fn(usize),
+) -> Result<(GltfTexture, [f32; 4]), GltfError> {
     match texture {
         Some(info) => Ok((
-            load_texture(&info.texture(), buffers, source, name)?,
+            load_texture(&info.texture(), buffers, source, name, options, image_decoded)?,
             factor,
         )),
-        None => Ok((TextureData::color(factor), [1.0, 1.0, 1.0, 1.0])),
+        None => Ok((
+            GltfTexture::new(TextureData::color(factor)),
+            [1.0, 1.0, 1.0, 1.0],
+        )),
     }
 }
 
@@ -408,35 +770,79 @@ fn load_texture(
     buffers: &Buffers,
     source: Arc<Source>,
     name: &str,
-) -> Result<TextureData, GltfError> {
-    let (data, format) = get_image_data(&texture.source(), buffers, source, name.as_ref())?;
+    options: &GltfSceneOptions,
+    image_decoded: &Fn(usize),
+) -> Result<GltfTexture, GltfError> {
+    let image = texture.source();
     let metadata = TextureMetadata::default().with_sampler(load_sampler_info(&texture.sampler()));
-    Ok(match format {
+    let (reference, format) = get_image_reference(&image, buffers, name.as_ref())?;
+    if options.defer_images {
+        let mut texture = GltfTexture::new(TextureData::color([1.0, 1.0, 1.0, 1.0]));
+        texture.deferred = Some(DeferredImage {
+            reference,
+            format,
+            metadata,
+            channel: None,
+            source,
+        });
+        return Ok(texture);
+    }
+    let failed = || format!("Failed to decode image {} from {}", image.index(), reference);
+    let data = reference.read(source).chain_err(&failed)?;
+    let texture = decode_image(data, format, metadata).chain_err(&failed)?;
+    image_decoded(image.index());
+    Ok(GltfTexture::new(texture))
+}
+
+fn decode_image(
+    data: Vec<u8>,
+    format: ImageFormat,
+    metadata: TextureMetadata,
+) -> AssetResult<TextureData> {
+    match format {
         ImageFormat::Png => PngFormat.from_data(data, metadata),
         ImageFormat::Jpeg => JpgFormat.from_data(data, metadata),
-    }?)
+    }
+}
+
+/// Reads and decodes a deferred image.
+pub fn resolve_image(image: &DeferredImage) -> AssetResult<TextureData> {
+    let data = image
+        .reference
+        .read(image.source.clone())
+        .chain_err(|| format!("Failed to read deferred glTF image from {}", image.reference))?;
+    let texture = decode_image(data, image.format, image.metadata.clone())
+        .chain_err(|| format!("Failed to decode deferred glTF image from {}", image.reference))?;
+    Ok(match image.channel {
+        Some(channel) => deconstruct_image(&texture, channel, 4),
+        None => texture,
+    })
 }
 
+/// Converts the filters and wrapping modes of a sampler.  gfx has a single filter for both
+/// magnification and minification, so the magnification filter picks between nearest and linear
+/// filtering, and the minification filter picks whether mipmaps are used.  Filters the asset
+/// leaves undefined are linear without mipmaps, and wrapping defaults to repeating.
 fn load_sampler_info(sampler: &gltf::texture::Sampler) -> SamplerInfo {
     use gfx::texture::{FilterMethod, WrapMode};
-    use gltf::texture::{MagFilter, WrappingMode};
-    // gfx only have support for a single filter, therefore we use mag filter, and ignore min filter
-    let filter = match sampler.mag_filter() {
-        None | Some(MagFilter::Nearest) => FilterMethod::Scale,
-        Some(MagFilter::Linear) => FilterMethod::Bilinear,
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+    let mipmaps = match sampler.min_filter() {
+        None | Some(MinFilter::Nearest) | Some(MinFilter::Linear) => false,
+        Some(_) => true,
     };
-    let wrap_s = match sampler.wrap_s() {
-        WrappingMode::ClampToEdge => WrapMode::Clamp,
-        WrappingMode::MirroredRepeat => WrapMode::Mirror,
-        WrappingMode::Repeat => WrapMode::Tile,
+    let filter = match (sampler.mag_filter(), mipmaps) {
+        (Some(MagFilter::Nearest), false) => FilterMethod::Scale,
+        (Some(MagFilter::Nearest), true) => FilterMethod::Mipmap,
+        (_, false) => FilterMethod::Bilinear,
+        (_, true) => FilterMethod::Trilinear,
     };
-    let wrap_t = match sampler.wrap_t() {
+    let wrap = |mode| match mode {
         WrappingMode::ClampToEdge => WrapMode::Clamp,
         WrappingMode::MirroredRepeat => WrapMode::Mirror,
         WrappingMode::Repeat => WrapMode::Tile,
     };
-    let mut s = SamplerInfo::new(filter, wrap_s);
-    s.wrap_mode.1 = wrap_t;
+    let mut s = SamplerInfo::new(filter, wrap(sampler.wrap_s()));
+    s.wrap_mode.1 = wrap(sampler.wrap_t());
     s
 }
 
@@ -446,9 +852,38 @@ fn load_scene(scene: &gltf::Scene) -> Result<GltfScene, GltfError> {
     })
 }
 
+/// Finds the only node with the given name.
+fn find_node(gltf: &gltf::Gltf, name: &str) -> Result<usize, GltfError> {
+    let matches = gltf.nodes()
+        .filter(|node| node.name() == Some(name))
+        .map(|node| node.index())
+        .collect::<Vec<_>>();
+    match matches.len() {
+        0 => Err(GltfError::MissingNode(name.to_string())),
+        1 => Ok(matches[0]),
+        count => Err(GltfError::AmbiguousNodeName(name.to_string(), count)),
+    }
+}
+
+/// Marks the nodes in the trees of the given roots.
+fn reachable_nodes(gltf: &gltf::Gltf, roots: Vec<gltf::Node>) -> Vec<bool> {
+    let mut reachable = vec![false; gltf.nodes().count()];
+    let mut stack = roots;
+    while let Some(node) = stack.pop() {
+        if !reachable[node.index()] {
+            reachable[node.index()] = true;
+            stack.extend(node.children());
+        }
+    }
+    reachable
+}
+
+/// Loads all nodes, so indices into them stay valid, but only the `included` ones get meshes.
 fn load_nodes(
     gltf: &gltf::Gltf,
     buffers: &Buffers,
+    extensions: &Extensions,
+    included: &[bool],
     options: &GltfSceneOptions,
 ) -> Result<Vec<GltfNode>, GltfError> {
     let mut node_map = HashMap::default();
@@ -456,7 +891,15 @@ fn load_nodes(
 
     for node in gltf.nodes() {
         let node_index = nodes.len();
-        let node = load_node(&node, buffers, node_index, &mut node_map, options)?;
+        let node = load_node(
+            &node,
+            buffers,
+            extensions,
+            node_index,
+            &mut node_map,
+            included[node_index],
+            options,
+        )?;
         nodes.push(node);
     }
 
@@ -467,18 +910,50 @@ fn load_nodes(
         }
     }
 
+    check_hierarchy(&nodes, options.max_node_depth)?;
     Ok(nodes)
 }
 
+/// Walks the node trees from their roots, making sure every node is reached exactly once and no
+/// node is nested deeper than `max_depth`.
+fn check_hierarchy(nodes: &[GltfNode], max_depth: usize) -> Result<(), GltfError> {
+    use gltf::json::Path;
+    use gltf::json::validation::Error as Reason;
+    let mut visited = vec![false; nodes.len()];
+    let mut stack = nodes
+        .iter()
+        .enumerate()
+        .filter(|&(_, node)| node.parent.is_none())
+        .map(|(index, _)| (index, 1))
+        .collect::<Vec<_>>();
+    while let Some((index, depth)) = stack.pop() {
+        if visited[index] {
+            return Err(GltfError::InvalidHierarchy(index));
+        }
+        if depth > max_depth {
+            let path = Path::new().field("nodes").index(index);
+            return Err(GltfError::GltfImporterError(importer::Error::Validation(vec![
+                (path, Reason::Invalid),
+            ])));
+        }
+        visited[index] = true;
+        stack.extend(nodes[index].children.iter().map(|&child| (child, depth + 1)));
+    }
+    match visited.iter().position(|&visited| !visited) {
+        Some(index) => Err(GltfError::InvalidHierarchy(index)),
+        None => Ok(()),
+    }
+}
+
 fn load_node(
     node: &gltf::Node,
     buffers: &Buffers,
+    extensions: &Extensions,
     node_index: usize,
     node_map: &mut HashMap<usize, usize>,
+    load_meshes: bool,
     options: &GltfSceneOptions,
 ) -> Result<GltfNode, GltfError> {
-    // TODO: skin
-
     let children = node.children().map(|c| c.index()).collect::<Vec<_>>();
 
     for child in node.children() {
@@ -486,14 +961,28 @@ fn load_node(
     }
 
     let primitives = match node.mesh() {
-        Some(mesh) => match load_mesh(&mesh, buffers, options) {
+        Some(ref mesh) if load_meshes => match load_mesh(mesh, buffers, options) {
             Err(err) => return Err(err),
             Ok(primitives) => primitives,
         },
-        None => Vec::default(),
+        _ => Vec::default(),
     };
 
-    let (translation, rotation, scale) = node.transform().decomposed();
+    // Weights on the node override the default weights of its mesh.
+    let morph_weights = node.weights()
+        .or_else(|| node.mesh().and_then(|mesh| mesh.weights()))
+        .map_or(Vec::new(), |weights| weights.to_vec());
+    let morph_target_names = node.mesh()
+        .map_or(Vec::new(), |mesh| extensions.target_names(mesh.index()));
+
+    // Nodes given as a matrix are decomposed into translation, rotation and scale.
+    let (mut translation, mut rotation, mut scale) = node.transform().decomposed();
+    if options.z_up {
+        translation = z_up(translation);
+        rotation = z_up_rotation(rotation);
+        scale = z_up_scale(scale);
+    }
+    let translation = scaled(vec![translation], options.scale)[0];
     let mut local_transform = LocalTransform::default();
     local_transform.translation = translation.into();
     // gltf quat format: [x, y, z, w], our quat format: [w, x, y, z]
@@ -506,6 +995,14 @@ fn load_node(
         children,
         parent: None,
         local_transform,
+        name: node.name().map(|name| name.to_string()),
+        mesh: node.mesh().map(|mesh| mesh.index()),
+        camera: node.camera().map(|camera| camera.index()),
+        light: extensions.node_light(node_index),
+        extras: extensions.node_extras(node_index),
+        skin: node.skin().map(|skin| skin.index()),
+        morph_weights,
+        morph_target_names,
     })
 }
 
@@ -516,52 +1013,99 @@ fn load_mesh(
 ) -> Result<Vec<GltfPrimitive>, GltfError> {
     // TODO: simplify loading here when we have support for indexed meshes
     // All attributes can then be mapped directly instead of using faces to unwind the indexing
-    use gltf_utils::PrimitiveIterators;
+    use gltf::mesh::Semantic;
 
     let mut primitives = vec![];
 
-    for primitive in mesh.primitives() {
-        let faces = primitive.indices_u32(buffers).map(|mut iter| {
-            let mut faces = vec![];
-            while let (Some(a), Some(b), Some(c)) = (iter.next(), iter.next(), iter.next()) {
-                faces.push(a as usize);
-                faces.push(b as usize);
-                faces.push(c as usize);
+    for (primitive_index, primitive) in mesh.primitives().enumerate() {
+        let indices = load_indices(&primitive, buffers)?;
+        let vertices = load_positions(&primitive, buffers, options)?
+            .ok_or(GltfError::MissingPositions)?;
+        let (topology, faces) = list_topology(primitive.mode(), indices, vertices.len());
+        let vertex_normals = load_vectors(&primitive, Semantic::Normals, buffers, options)?;
+        let vertex_tex_coords = load_tex_coords(&primitive, 0, buffers, options.flip_v)?;
+        let vertex_colors = load_colors(&primitive, buffers)?;
+        let mut vertex_extra_tex_coords = Vec::new();
+        for set in 1.. {
+            match load_tex_coords(&primitive, set, buffers, options.flip_v)? {
+                Some(tex_coords) => vertex_extra_tex_coords.push(tex_coords),
+                None => break,
             }
-            faces
-        });
+        }
+        let vertex_tangents = load_floats(&primitive, Semantic::Tangents, buffers)?
+            .map(|tangents| vec4s(&tangents));
+        let (joints, weights, truncated) = load_influences(&primitive, buffers)?;
+        if truncated > 0 {
+            warn!(
+                "{} vertices of primitive {} of mesh {} have more than 4 joint \
+                 influences, only the 4 strongest are kept",
+                truncated,
+                primitive_index,
+                mesh.index()
+            );
+        }
+        let vertex_morph_targets = load_morph_targets(&primitive, buffers, options)?;
 
-        let positions = primitive
-            .positions(buffers)
-            .map(|positions| match faces {
-                Some(ref faces) => {
-                    let vertices = positions.collect::<Vec<_>>();
-                    faces
-                        .iter()
-                        .map(|i| Separate::<Position>::new(vertices[*i]))
-                        .collect::<Vec<_>>()
+        let (faces, welded_vertices) = match options.weld {
+            Some(ref tolerances) => {
+                // The other attributes aren't compared within a tolerance, so vertices are only
+                // welded if they are equal and welding never replaces them.
+                let mut exact = vec![Vec::new(); vertices.len()];
+                if let Some(ref colors) = vertex_colors {
+                    push_bits(&mut exact, colors);
                 }
-                None => positions
-                    .map(|pos| Separate::<Position>::new(pos))
-                    .collect(),
-            })
-            .ok_or(GltfError::MissingPositions)?;
-
-        let colors = primitive
-            .colors_rgba_f32(0, 1., buffers)
-            .map(|colors| match faces {
-                Some(ref faces) => {
-                    let colors = colors.collect::<Vec<_>>();
-                    faces
-                        .iter()
-                        .map(|i| Separate::<Color>::new(colors[*i]))
-                        .collect()
+                for tex_coords in &vertex_extra_tex_coords {
+                    push_bits(&mut exact, tex_coords);
                 }
-                None => colors.map(|color| Separate::<Color>::new(color)).collect(),
-            });
+                if let Some(ref tangents) = vertex_tangents {
+                    push_bits(&mut exact, tangents);
+                }
+                if let Some(ref joints) = joints {
+                    for (bits, joints) in exact.iter_mut().zip(joints) {
+                        bits.extend(joints.iter().map(|&joint| joint as u32));
+                    }
+                }
+                if let Some(ref weights) = weights {
+                    push_bits(&mut exact, weights);
+                }
+                for target in &vertex_morph_targets {
+                    for offsets in &[&target.positions, &target.normals, &target.tangents] {
+                        if let Some(ref offsets) = **offsets {
+                            push_bits(&mut exact, offsets);
+                        }
+                    }
+                }
+                let remap = weld_vertices(
+                    &vertices,
+                    vertex_normals.as_ref().map(|normals| &normals[..]),
+                    vertex_tex_coords.as_ref().map(|tex_coords| &tex_coords[..]),
+                    Some(&exact[..]),
+                    tolerances,
+                );
+                let welded = remap.iter().enumerate().filter(|&(i, &j)| i != j).count();
+                let faces = match faces {
+                    Some(faces) => faces.into_iter().map(|i| remap[i]).collect(),
+                    None => remap,
+                };
+                (Some(faces), welded)
+            }
+            None => (faces, 0),
+        };
+
+        let positions = unwind(vertices.clone(), &faces)
+            .into_iter()
+            .map(|pos| Separate::<Position>::new(pos))
+            .collect::<Vec<_>>();
+
+        let colors = vertex_colors.map(|colors| {
+            unwind(colors, &faces)
+                .into_iter()
+                .map(|color| Separate::<Color>::new(color))
+                .collect()
+        });
 
-        let tex_coord = match primitive.tex_coords_f32(0, buffers) {
-            Some(tex_coords) => Some(tex_coords.collect::<Vec<[f32; 2]>>()),
+        let tex_coord = match vertex_tex_coords {
+            Some(tex_coords) => Some(tex_coords),
             None => match options.generate_tex_coords {
                 Some((u, v)) => Some((0..positions.len()).map(|_| [u, v]).collect()),
                 None => None,
@@ -576,60 +1120,1742 @@ fn load_mesh(
                 .collect(),
         });
 
-        let normals = primitive.normals(buffers).map(|normals| match faces {
-            Some(ref faces) => {
-                let normals = normals.collect::<Vec<_>>();
-                faces
-                    .iter()
-                    .map(|i| Separate::<Normal>::new(normals[*i]))
-                    .collect()
-            }
-            None => normals.map(|n| Separate::<Normal>::new(n)).collect(),
-        });
+        let extra_tex_coords = vertex_extra_tex_coords
+            .into_iter()
+            .map(|tex_coords| unwind(tex_coords, &faces))
+            .collect();
+
+        let normals = match vertex_normals {
+            Some(normals) => Some(
+                unwind(normals, &faces)
+                    .into_iter()
+                    .map(|n| Separate::<Normal>::new(n))
+                    .collect(),
+            ),
+            None => match options.generate_normals {
+                Some(generation) => {
+                    match topology {
+                        Primitive::TriangleList => {}
+                        _ => {
+                            let mode = primitive.mode();
+                            return Err(GltfError::NormalGenerationTopology(format!("{:?}", mode)))
+                        }
+                    }
+                    Some(
+                        generate_normals(&vertices, &faces, generation)
+                            .into_iter()
+                            .map(|n| Separate::<Normal>::new(n))
+                            .collect(),
+                    )
+                }
+                None => None,
+            },
+        };
 
-        let tangents = primitive.tangents(buffers).map(|tangents| match faces {
-            Some(ref faces) => {
-                let tangents = tangents.collect::<Vec<_>>();
-                faces
-                    .iter()
-                    .map(|i| {
-                        Separate::<Tangent>::new(
-                            [tangents[*i][0], tangents[*i][1], tangents[*i][2]],
-                        )
+        // Normal maps need tangents, so they are generated if the asset has none.
+        let tangents = match vertex_tangents {
+            Some(tangents) if options.z_up => Some(
+                unwind(tangents, &faces)
+                    .into_iter()
+                    .map(|t| {
+                        let direction = z_up([t[0], t[1], t[2]]);
+                        [direction[0], direction[1], direction[2], t[3]]
                     })
-                    .collect()
+                    .collect(),
+            ),
+            Some(tangents) => Some(unwind(tangents, &faces)),
+            None if primitive.material().normal_texture().is_some() => {
+                match generate_primitive_tangents(&primitive, buffers, &faces, options) {
+                    Ok(tangents) => Some(tangents),
+                    Err(reason) => {
+                        warn!(
+                            "Tangents of primitive {} of mesh {} were not generated: {}",
+                            primitive_index,
+                            mesh.index(),
+                            reason
+                        );
+                        None
+                    }
+                }
             }
-            None => tangents
+            None => None,
+        };
+        let tangent_handedness = tangents
+            .as_ref()
+            .map(|tangents| tangents.iter().map(|t| t[3]).collect());
+        let tangents = tangents.map(|tangents| {
+            tangents
+                .into_iter()
                 .map(|t| Separate::<Tangent>::new([t[0], t[1], t[2]]))
-                .collect(),
+                .collect()
         });
 
+        let joints = joints.map(|joints| unwind(joints, &faces));
+        let weights = weights.map(|weights| unwind(weights, &faces));
+
+        let morph_targets = vertex_morph_targets
+            .into_iter()
+            .map(|target| GltfMorphTarget {
+                positions: target.positions.map(|offsets| unwind(offsets, &faces)),
+                normals: target.normals.map(|offsets| unwind(offsets, &faces)),
+                tangents: target.tangents.map(|offsets| unwind(offsets, &faces)),
+            })
+            .collect();
+
         let material = primitive.material().index();
-        // TODO: joint ids and weights
-
-        match map_mode(primitive.mode()) {
-            Ok(primitive) => primitives.push(GltfPrimitive {
-                primitive,
-                indices: faces,
-                material,
-                attributes: (positions, colors, tex_coord, normals, tangents),
-                handle: None,
-            }),
-            Err(err) => return Err(err),
-        }
+        let bounds = load_bounds(&primitive, &vertices, options);
+
+        primitives.push(GltfPrimitive {
+            primitive: topology,
+            indices: faces,
+            material,
+            attributes: (positions, colors, tex_coord, normals, tangents),
+            extra_tex_coords,
+            tangent_handedness,
+            joints,
+            weights,
+            morph_targets,
+            bounds,
+            welded_vertices,
+            handle: None,
+        });
     }
     Ok(primitives)
 }
 
-fn map_mode(mode: gltf::mesh::Mode) -> Result<Primitive, GltfError> {
+/// Reads the morph targets of the primitive, with one offset per vertex of the primitive.
+fn load_morph_targets(
+    primitive: &gltf::Primitive,
+    buffers: &Buffers,
+    options: &GltfSceneOptions,
+) -> Result<Vec<GltfMorphTarget>, GltfError> {
+    let vertices = primitive
+        .get(&gltf::mesh::Semantic::Positions)
+        .map_or(0, |positions| positions.count());
+    let offsets = |accessor: Option<gltf::Accessor>, scale: f32| match accessor {
+        Some(accessor) => {
+            let mut offsets = vec3s(&read_floats(&accessor, buffers)?);
+            if options.z_up {
+                offsets = offsets.into_iter().map(z_up).collect();
+            }
+            if offsets.len() != vertices {
+                return Err(GltfError::MorphTargetLength(vertices, offsets.len()));
+            }
+            Ok(Some(scaled(offsets, scale)))
+        }
+        None => Ok(None),
+    };
+    primitive
+        .morph_targets()
+        .map(|target| {
+            Ok(GltfMorphTarget {
+                positions: offsets(target.positions(), options.scale)?,
+                normals: offsets(target.normals(), 1.0)?,
+                tangents: offsets(target.tangents(), 1.0)?,
+            })
+        })
+        .collect()
+}
+
+/// Reads the `COLOR_0` attribute as RGBA, colors without alpha are opaque.
+fn load_colors(
+    primitive: &gltf::Primitive,
+    buffers: &Buffers,
+) -> Result<Option<Vec<[f32; 4]>>, GltfError> {
+    use gltf::accessor::{DataType, Dimensions};
+    let accessor = match primitive.get(&gltf::mesh::Semantic::Colors(0)) {
+        Some(accessor) => accessor,
+        None => return Ok(None),
+    };
+    let channels = match accessor.dimensions() {
+        Dimensions::Vec3 => 3,
+        Dimensions::Vec4 => 4,
+        dimensions => {
+            return Err(GltfError::UnsupportedColorFormat(format!("{:?}", dimensions)))
+        }
+    };
+    let data_type = accessor.data_type();
+    let bytes = read_accessor_bytes(&accessor, buffers)?;
+    let components = match normalized_components(data_type, &bytes) {
+        Some(components) => components,
+        None => return Err(GltfError::UnsupportedColorFormat(format!("{:?}", data_type))),
+    };
+    Ok(Some(rgba_colors(&components, channels)))
+}
+
+/// Reads the components of a vertex attribute as floats.
+fn load_floats(
+    primitive: &gltf::Primitive,
+    semantic: gltf::mesh::Semantic,
+    buffers: &Buffers,
+) -> Result<Option<Vec<f32>>, GltfError> {
+    match primitive.get(&semantic) {
+        Some(accessor) => Ok(Some(read_floats(&accessor, buffers)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the components of an accessor as floats, whatever their component type.
+fn read_floats(accessor: &gltf::Accessor, buffers: &Buffers) -> Result<Vec<f32>, GltfError> {
+    let bytes = read_accessor_bytes(accessor, buffers)?;
+    Ok(float_components(accessor.data_type(), accessor.normalized(), &bytes))
+}
+
+fn vec3s(components: &[f32]) -> Vec<[f32; 3]> {
+    components.chunks(3).map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+fn vec4s(components: &[f32]) -> Vec<[f32; 4]> {
+    components.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]).collect()
+}
+
+/// Reads a vertex attribute of 3D vectors, converting them to Z-up if the options ask for it.
+fn load_vectors(
+    primitive: &gltf::Primitive,
+    semantic: gltf::mesh::Semantic,
+    buffers: &Buffers,
+    options: &GltfSceneOptions,
+) -> Result<Option<Vec<[f32; 3]>>, GltfError> {
+    let vectors = load_floats(primitive, semantic, buffers)?.map(|vectors| vec3s(&vectors));
+    if options.z_up {
+        Ok(vectors.map(|vectors| vectors.into_iter().map(z_up).collect()))
+    } else {
+        Ok(vectors)
+    }
+}
+
+/// Reads the positions of a primitive, converted to Z-up and scaled as the options ask for.
+fn load_positions(
+    primitive: &gltf::Primitive,
+    buffers: &Buffers,
+    options: &GltfSceneOptions,
+) -> Result<Option<Vec<[f32; 3]>>, GltfError> {
+    let positions = load_vectors(primitive, gltf::mesh::Semantic::Positions, buffers, options)?;
+    Ok(positions.map(|positions| scaled(positions, options.scale)))
+}
+
+/// Uses the bounds of the position accessor if it has them, as they are optional and not always
+/// right they are computed from the positions otherwise.  Debug builds check the accessor bounds
+/// against the positions and warn when they don't match.
+fn load_bounds(
+    primitive: &gltf::Primitive,
+    positions: &[[f32; 3]],
+    options: &GltfSceneOptions,
+) -> GltfBounds {
+    let vector = |value: Option<gltf::json::Value>| -> Option<[f32; 3]> {
+        let value = match value {
+            Some(value) => value,
+            None => return None,
+        };
+        let components = match value.as_array() {
+            Some(components) if components.len() == 3 => components,
+            _ => return None,
+        };
+        let mut vector = [0.; 3];
+        for (v, component) in vector.iter_mut().zip(components) {
+            *v = match component.as_f64() {
+                Some(component) => component as f32,
+                None => return None,
+            };
+        }
+        Some(vector)
+    };
+    let accessor = primitive.get(&gltf::mesh::Semantic::Positions);
+    let (min, max) = match accessor {
+        Some(accessor) => (vector(accessor.min()), vector(accessor.max())),
+        None => (None, None),
+    };
+    let computed = || bounds_of(positions);
+    match (min, max) {
+        (Some(min), Some(max)) => {
+            // The corners are converted like the positions were.
+            let corners = scaled(
+                if options.z_up {
+                    vec![z_up(min), z_up(max)]
+                } else {
+                    vec![min, max]
+                },
+                options.scale,
+            );
+            let bounds = bounds_of(&corners);
+            if cfg!(debug_assertions) && !bounds_match(&bounds, &computed()) {
+                warn!(
+                    "The bounds of a position accessor don't match its data, {:?} \
+                     instead of {:?}",
+                    bounds,
+                    computed()
+                );
+            }
+            bounds
+        }
+        _ => computed(),
+    }
+}
+
+/// Whether two bounds are the same, up to the rounding of converting the corners and the
+/// vertices separately.
+fn bounds_match(a: &GltfBounds, b: &GltfBounds) -> bool {
+    let close = |a: f32, b: f32| (a - b).abs() <= 1e-4 * a.abs().max(b.abs()).max(1.);
+    (0..3).all(|i| close(a.min[i], b.min[i]) && close(a.max[i], b.max[i]))
+}
+
+/// The bounds of some points, all zeros if there are none.
+fn bounds_of(points: &[[f32; 3]]) -> GltfBounds {
+    if points.is_empty() {
+        return GltfBounds {
+            min: [0.; 3],
+            max: [0.; 3],
+        };
+    }
+    let mut bounds = GltfBounds {
+        min: points[0],
+        max: points[0],
+    };
+    for point in points {
+        for axis in 0..3 {
+            bounds.min[axis] = bounds.min[axis].min(point[axis]);
+            bounds.max[axis] = bounds.max[axis].max(point[axis]);
+        }
+    }
+    bounds
+}
+
+/// Scales positions or translations, vectors are left as they are when the scale is 1.
+fn scaled(vectors: Vec<[f32; 3]>, scale: f32) -> Vec<[f32; 3]> {
+    if scale == 1.0 {
+        return vectors;
+    }
+    vectors
+        .into_iter()
+        .map(|v| [v[0] * scale, v[1] * scale, v[2] * scale])
+        .collect()
+}
+
+/// Rotates a Y-up vector to Z-up, +Y becomes +Z and +Z becomes -Y.
+fn z_up(v: [f32; 3]) -> [f32; 3] {
+    [v[0], -v[2], v[1]]
+}
+
+/// Rotates the axis of a `[x, y, z, w]` quaternion to Z-up.
+fn z_up_rotation(q: [f32; 4]) -> [f32; 4] {
+    let axis = z_up([q[0], q[1], q[2]]);
+    [axis[0], axis[1], axis[2], q[3]]
+}
+
+/// Scales along the Y and Z axes swap places when rotating to Z-up.
+fn z_up_scale(s: [f32; 3]) -> [f32; 3] {
+    [s[0], s[2], s[1]]
+}
+
+/// Changes the basis of a column-major transform from Y-up to Z-up, so it transforms Z-up
+/// vectors like the original transformed Y-up ones.
+fn z_up_matrix(m: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let column = |c: [f32; 4]| [c[0], -c[2], c[1], c[3]];
+    let negated = |c: [f32; 4]| [-c[0], -c[1], -c[2], -c[3]];
+    [column(m[0]), negated(column(m[2])), column(m[1]), column(m[3])]
+}
+
+/// Reads the indices of a primitive, whichever of the allowed widths they have.
+fn load_indices(
+    primitive: &gltf::Primitive,
+    buffers: &Buffers,
+) -> Result<Option<Vec<usize>>, GltfError> {
+    use gltf::accessor::DataType;
+    let accessor = match primitive.indices() {
+        Some(accessor) => accessor,
+        None => return Ok(None),
+    };
+    match accessor.data_type() {
+        DataType::U8 | DataType::U16 | DataType::U32 => {
+            Ok(Some(read_integers(&accessor, buffers)?))
+        }
+        data_type => Err(GltfError::UnsupportedIndexFormat(format!("{:?}", data_type))),
+    }
+}
+
+/// Reads the components of an accessor of unsigned integers, like indices and joints.
+fn read_integers(accessor: &gltf::Accessor, buffers: &Buffers) -> Result<Vec<usize>, GltfError> {
+    use gltf::accessor::DataType::*;
+    let size = match accessor.data_type() {
+        I8 | U8 => 1,
+        I16 | U16 => 2,
+        U32 | F32 => 4,
+    };
+    let bytes = read_accessor_bytes(accessor, buffers)?;
+    Ok(read_indices(&bytes, size, bytes.len() / size)?)
+}
+
+/// Reads the `TEXCOORD_n` attribute of the given set, `flip_v` maps V to `1 - V`.
+fn load_tex_coords(
+    primitive: &gltf::Primitive,
+    set: u32,
+    buffers: &Buffers,
+    flip_v: bool,
+) -> Result<Option<Vec<[f32; 2]>>, GltfError> {
+    let accessor = match primitive.get(&gltf::mesh::Semantic::TexCoords(set)) {
+        Some(accessor) => accessor,
+        None => return Ok(None),
+    };
+    let data_type = accessor.data_type();
+    let bytes = read_accessor_bytes(&accessor, buffers)?;
+    match normalized_components(data_type, &bytes) {
+        Some(components) => Ok(Some(
+            components
+                .chunks(2)
+                .map(|c| if flip_v { [c[0], 1. - c[1]] } else { [c[0], c[1]] })
+                .collect(),
+        )),
+        None => Err(GltfError::UnsupportedTexCoordFormat(format!("{:?}", data_type))),
+    }
+}
+
+/// Changes a texture transform to work on texture coordinates with a flipped V axis, mapping
+/// flipped coordinates to where the original transform maps the unflipped ones, flipped.
+fn flip_v_transform(transform: TextureTransform) -> TextureTransform {
+    let (sin, cos) = transform.rotation.sin_cos();
+    let scale_v = transform.scale[1];
+    TextureTransform {
+        offset: [
+            transform.offset[0] + sin * scale_v,
+            1. - transform.offset[1] - cos * scale_v,
+        ],
+        rotation: -transform.rotation,
+        ..transform
+    }
+}
+
+/// Converts little endian vertex attribute components to floats, unsigned integers are
+/// normalized so their largest value maps to 1.  `None` for component types colors and texture
+/// coordinates can't use.
+fn normalized_components(data_type: gltf::accessor::DataType, bytes: &[u8]) -> Option<Vec<f32>> {
+    use gltf::accessor::DataType;
+    match data_type {
+        DataType::U8 | DataType::U16 | DataType::F32 => {
+            Some(float_components(data_type, true, bytes))
+        }
+        _ => None,
+    }
+}
+
+/// Converts little endian components of any type to floats.  Normalized integers map their
+/// largest value to 1, and signed ones map their smallest value to -1 like the value after it.
+/// Integers that aren't normalized keep their value.
+fn float_components(
+    data_type: gltf::accessor::DataType,
+    normalized: bool,
+    bytes: &[u8],
+) -> Vec<f32> {
+    use gltf::accessor::DataType;
+    let integer = |c: &[u8]| c.iter().rev().fold(0, |bits, &byte| bits << 8 | byte as u32);
+    let (size, max) = match data_type {
+        DataType::I8 => (1, 127.),
+        DataType::U8 => (1, 255.),
+        DataType::I16 => (2, 32767.),
+        DataType::U16 => (2, 65535.),
+        DataType::U32 => (4, 4_294_967_295.),
+        DataType::F32 => return bytes.chunks(4).map(|c| f32::from_bits(integer(c))).collect(),
+    };
+    bytes
+        .chunks(size)
+        .map(|c| {
+            let value = match data_type {
+                DataType::I8 => c[0] as i8 as f32,
+                DataType::I16 => integer(c) as u16 as i16 as f32,
+                _ => integer(c) as f32,
+            };
+            if normalized {
+                (value / max).max(-1.)
+            } else {
+                value
+            }
+        })
+        .collect()
+}
+
+fn rgba_colors(components: &[f32], channels: usize) -> Vec<[f32; 4]> {
+    components
+        .chunks(channels)
+        .map(|c| [c[0], c[1], c[2], if channels == 4 { c[3] } else { 1. }])
+        .collect()
+}
+
+/// Maps every vertex to the first vertex within the tolerances of it, which may be itself.
+/// Vertices with `exact` bits are only welded if their bits are equal too.  Positions are
+/// bucketed into a grid with cells as large as the position tolerance, so only vertices in
+/// neighbouring cells are compared.
+fn weld_vertices(
+    positions: &[[f32; 3]],
+    normals: Option<&[[f32; 3]]>,
+    tex_coords: Option<&[[f32; 2]]>,
+    exact: Option<&[Vec<u32>]>,
+    tolerances: &WeldTolerances,
+) -> Vec<usize> {
+    let cell_size = tolerances.position.max(1e-6);
+    let cell = |p: [f32; 3]| {
+        [
+            (p[0] / cell_size).floor() as i64,
+            (p[1] / cell_size).floor() as i64,
+            (p[2] / cell_size).floor() as i64,
+        ]
+    };
+    let min_cos = tolerances.normal_angle.cos();
+    let matches = |a: usize, b: usize| {
+        let (p, q) = (positions[a], positions[b]);
+        let (dx, dy, dz) = (p[0] - q[0], p[1] - q[1], p[2] - q[2]);
+        let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+        let normals_match = normals.map_or(true, |normals| {
+            let (n, m) = (normals[a], normals[b]);
+            let lengths = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt()
+                * (m[0] * m[0] + m[1] * m[1] + m[2] * m[2]).sqrt();
+            let dot = n[0] * m[0] + n[1] * m[1] + n[2] * m[2];
+            dot >= min_cos * lengths
+        });
+        let tex_coords_match = tex_coords.map_or(true, |tex_coords| {
+            let (s, t) = (tex_coords[a], tex_coords[b]);
+            ((s[0] - t[0]).powi(2) + (s[1] - t[1]).powi(2)).sqrt() <= tolerances.tex_coord
+        });
+        let exact_match = exact.map_or(true, |exact| exact[a] == exact[b]);
+        distance <= tolerances.position && normals_match && tex_coords_match && exact_match
+    };
+
+    let mut grid = FnvHashMap::<[i64; 3], Vec<usize>>::default();
+    let mut remap = Vec::with_capacity(positions.len());
+    for (index, &position) in positions.iter().enumerate() {
+        let key = cell(position);
+        let mut found = None;
+        'search: for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    let candidates = grid.get(&[key[0] + dx, key[1] + dy, key[2] + dz]);
+                    let candidate = candidates
+                        .and_then(|candidates| candidates.iter().find(|&&c| matches(c, index)));
+                    if let Some(&candidate) = candidate {
+                        found = Some(candidate);
+                        break 'search;
+                    }
+                }
+            }
+        }
+        match found {
+            Some(candidate) => remap.push(candidate),
+            None => {
+                grid.entry(key).or_insert_with(Vec::new).push(index);
+                remap.push(index);
+            }
+        }
+    }
+    remap
+}
+
+/// Appends the bits of the components of the value of every vertex to the bits of the vertex.
+fn push_bits<T: AsRef<[f32]>>(bits: &mut [Vec<u32>], values: &[T]) {
+    for (bits, value) in bits.iter_mut().zip(values) {
+        bits.extend(value.as_ref().iter().map(|component| component.to_bits()));
+    }
+}
+
+/// Orders the vertex attributes like the unwound positions.
+fn unwind<T: Copy>(values: Vec<T>, faces: &Option<Vec<usize>>) -> Vec<T> {
+    match *faces {
+        Some(ref faces) => faces.iter().map(|i| values[*i]).collect(),
+        None => values,
+    }
+}
+
+/// Generates tangents from the texture coordinates of the normal map of the primitive, or gives
+/// the reason they can't be generated.
+fn generate_primitive_tangents(
+    primitive: &gltf::Primitive,
+    buffers: &Buffers,
+    faces: &Option<Vec<usize>>,
+    options: &GltfSceneOptions,
+) -> Result<Vec<[f32; 4]>, &'static str> {
+    {
+        use gltf::mesh::Mode::*;
+        match primitive.mode() {
+            Triangles | TriangleStrip | TriangleFan => {}
+            _ => return Err("the primitive isn't made of triangles"),
+        }
+    }
+    let set = primitive
+        .material()
+        .normal_texture()
+        .map_or(0, |normal| normal.tex_coord());
+    // Tangents follow the V axis of glTF even when it is flipped, as normal maps are made for it.
+    let tex_coords = match load_tex_coords(primitive, set, buffers, false) {
+        Ok(Some(tex_coords)) => tex_coords,
+        Ok(None) => return Err("the normal map has no texture coordinates"),
+        Err(_) => return Err("the texture coordinates of the normal map can't be read"),
+    };
+    use gltf::mesh::Semantic;
+    let read_error = "the vertex attributes can't be read";
+    let vertices = load_positions(primitive, buffers, options)
+        .map_err(|_| read_error)?
+        .unwrap_or_else(Vec::new);
+    let normals = match load_vectors(primitive, Semantic::Normals, buffers, options) {
+        Err(_) => return Err(read_error),
+        Ok(Some(normals)) => unwind(normals, faces),
+        Ok(None) => match options.generate_normals {
+            Some(generation) => generate_normals(&vertices, faces, generation),
+            None => return Err("the primitive has no normals"),
+        },
+    };
+    Ok(generate_tangents(&vertices, &tex_coords, &normals, faces))
+}
+
+/// Generates a tangent for each vertex of a triangle list from the directions the texture
+/// coordinates increase in.  `normals` are ordered like the positions unwound by `faces`, and so
+/// are the tangents.
+fn generate_tangents(
+    vertices: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+    normals: &[[f32; 3]],
+    faces: &Option<Vec<usize>>,
+) -> Vec<[f32; 4]> {
+    let indices = match *faces {
+        Some(ref faces) => faces.clone(),
+        None => (0..vertices.len()).collect(),
+    };
+    let sub = |a: [f32; 3], b: [f32; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let mut tangents = vec![[0.; 3]; vertices.len()];
+    let mut bitangents = vec![[0.; 3]; vertices.len()];
+    for triangle in indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+        let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+        let (edge1, edge2) = (sub(vertices[b], vertices[a]), sub(vertices[c], vertices[a]));
+        let (du1, dv1) = (tex_coords[b][0] - tex_coords[a][0], tex_coords[b][1] - tex_coords[a][1]);
+        let (du2, dv2) = (tex_coords[c][0] - tex_coords[a][0], tex_coords[c][1] - tex_coords[a][1]);
+        let det = du1 * dv2 - du2 * dv1;
+        if det == 0. {
+            continue;
+        }
+        for axis in 0..3 {
+            let tangent = (edge1[axis] * dv2 - edge2[axis] * dv1) / det;
+            let bitangent = (edge2[axis] * du1 - edge1[axis] * du2) / det;
+            for &vertex in triangle {
+                tangents[vertex][axis] += tangent;
+                bitangents[vertex][axis] += bitangent;
+            }
+        }
+    }
+    indices
+        .iter()
+        .zip(normals)
+        .map(|(&vertex, &normal)| {
+            // Gram-Schmidt makes the tangent perpendicular to the normal.
+            let tangent = tangents[vertex];
+            let along = dot(normal, tangent);
+            let tangent = normalize([
+                tangent[0] - normal[0] * along,
+                tangent[1] - normal[1] * along,
+                tangent[2] - normal[2] * along,
+            ]);
+            let cross = [
+                normal[1] * tangent[2] - normal[2] * tangent[1],
+                normal[2] * tangent[0] - normal[0] * tangent[2],
+                normal[0] * tangent[1] - normal[1] * tangent[0],
+            ];
+            let handedness = if dot(cross, bitangents[vertex]) < 0. {
+                -1.
+            } else {
+                1.
+            };
+            [tangent[0], tangent[1], tangent[2], handedness]
+        })
+        .collect()
+}
+
+/// Generates a normal for each vertex of a triangle list, in the same order as the positions
+/// unwound by `faces`.
+fn generate_normals(
+    vertices: &[[f32; 3]],
+    faces: &Option<Vec<usize>>,
+    generation: NormalGeneration,
+) -> Vec<[f32; 3]> {
+    let indices = match *faces {
+        Some(ref faces) => faces.clone(),
+        None => (0..vertices.len()).collect(),
+    };
+    let face_normal = |triangle: &[usize]| {
+        let (a, b, c) = (
+            vertices[triangle[0]],
+            vertices[triangle[1]],
+            vertices[triangle[2]],
+        );
+        let (u, v) = (
+            [b[0] - a[0], b[1] - a[1], b[2] - a[2]],
+            [c[0] - a[0], c[1] - a[1], c[2] - a[2]],
+        );
+        // The length of the cross product is twice the area of the triangle.
+        [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ]
+    };
+    let mut normals = Vec::with_capacity(indices.len());
+    match generation {
+        NormalGeneration::Flat => for triangle in indices.chunks(3) {
+            let normal = if triangle.len() == 3 {
+                normalize(face_normal(triangle))
+            } else {
+                [0.; 3]
+            };
+            normals.extend(triangle.iter().map(|_| normal));
+        },
+        NormalGeneration::Smooth => {
+            let mut sums = vec![[0.; 3]; vertices.len()];
+            for triangle in indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+                let normal = face_normal(triangle);
+                for &vertex in triangle {
+                    for axis in 0..3 {
+                        sums[vertex][axis] += normal[axis];
+                    }
+                }
+            }
+            normals.extend(indices.iter().map(|&vertex| normalize(sums[vertex])));
+        }
+    }
+    normals
+}
+
+/// Scales the vector to unit length, zero vectors are left alone.
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length > 0. {
+        [vector[0] / length, vector[1] / length, vector[2] / length]
+    } else {
+        vector
+    }
+}
+
+/// Reads the joints and weights of every `JOINTS_n` and `WEIGHTS_n` set.  The renderer takes 4
+/// influences per vertex, so when there are several sets the 4 strongest influences of every
+/// vertex are kept, and the number of vertices that lost influences is returned with them.
+fn load_influences(
+    primitive: &gltf::Primitive,
+    buffers: &Buffers,
+) -> Result<(Option<Vec<[u16; 4]>>, Option<Vec<[f32; 4]>>, usize), GltfError> {
+    use gltf::mesh::Semantic;
+    let mut joint_sets = Vec::new();
+    let mut weight_sets = Vec::new();
+    for set in 0.. {
+        let joints = primitive.get(&Semantic::Joints(set));
+        let weights = primitive.get(&Semantic::Weights(set));
+        if joints.is_none() && weights.is_none() {
+            break;
+        }
+        joint_sets.push(match joints {
+            Some(accessor) => Some(read_integers(&accessor, buffers)?),
+            None => None,
+        });
+        weight_sets.push(match weights {
+            Some(accessor) => {
+                let data_type = accessor.data_type();
+                let bytes = read_accessor_bytes(&accessor, buffers)?;
+                match normalized_components(data_type, &bytes) {
+                    Some(components) => Some(components),
+                    None => {
+                        return Err(GltfError::UnsupportedWeightFormat(format!("{:?}", data_type)))
+                    }
+                }
+            }
+            None => None,
+        });
+    }
+    let has_joints = joint_sets.iter().any(Option::is_some);
+    let has_weights = weight_sets.iter().any(Option::is_some);
+
+    let vertices = primitive
+        .get(&Semantic::Positions)
+        .map_or(0, |positions| positions.count());
+    let mut joints = Vec::with_capacity(vertices);
+    let mut weights = Vec::with_capacity(vertices);
+    let mut truncated = 0;
+    for vertex in 0..vertices {
+        let mut influences = joint_sets
+            .iter()
+            .zip(&weight_sets)
+            .flat_map(|(joint_set, weight_set)| {
+                (0..4).map(move |i| {
+                    let joint = set_component(joint_set, vertex * 4 + i).unwrap_or(0);
+                    let weight = set_component(weight_set, vertex * 4 + i).unwrap_or(0.);
+                    (joint as u16, weight)
+                })
+            })
+            .collect::<Vec<_>>();
+        if influences.len() > 4 {
+            influences.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+            if influences[4..].iter().any(|&(_, weight)| weight > 0.) {
+                truncated += 1;
+            }
+        }
+        influences.resize(4, (0, 0.));
+        joints.push([influences[0].0, influences[1].0, influences[2].0, influences[3].0]);
+        weights.push(normalize_weights([
+            influences[0].1,
+            influences[1].1,
+            influences[2].1,
+            influences[3].1,
+        ]));
+    }
+    Ok((
+        if has_joints { Some(joints) } else { None },
+        if has_weights { Some(weights) } else { None },
+        truncated,
+    ))
+}
+
+fn set_component<T: Copy>(set: &Option<Vec<T>>, index: usize) -> Option<T> {
+    set.as_ref().and_then(|set| set.get(index).cloned())
+}
+
+/// Scales the weights to sum up to 1, exporters don't always do it exactly.
+fn normalize_weights(weights: [f32; 4]) -> [f32; 4] {
+    let sum = weights.iter().sum::<f32>();
+    if sum > 0. {
+        [
+            weights[0] / sum,
+            weights[1] / sum,
+            weights[2] / sum,
+            weights[3] / sum,
+        ]
+    } else {
+        weights
+    }
+}
+
+/// Converts a primitive to a point, line or triangle list, as the vertices are unwound by the
+/// indices anyway.  Strips, loops and fans get indices even if the primitive has none, and
+/// incomplete lines and triangles at the end of the indices are dropped.
+fn list_topology(
+    mode: gltf::mesh::Mode,
+    indices: Option<Vec<usize>>,
+    vertices: usize,
+) -> (Primitive, Option<Vec<usize>>) {
     use gltf::mesh::Mode::*;
+    let whole = |mut indices: Vec<usize>, size: usize| {
+        let len = indices.len() - indices.len() % size;
+        indices.truncate(len);
+        indices
+    };
+    let strip = |indices: Option<Vec<usize>>| -> Vec<usize> {
+        indices.unwrap_or_else(|| (0..vertices).collect())
+    };
     match mode {
-        Points => Ok(Primitive::PointList),
-        Lines => Ok(Primitive::LineList),
-        LineLoop => Err(GltfError::PrimitiveMissingInGfx("LineLoop".to_string())),
-        LineStrip => Ok(Primitive::LineStrip),
-        Triangles => Ok(Primitive::TriangleList),
-        TriangleStrip => Ok(Primitive::TriangleStrip),
-        TriangleFan => Err(GltfError::PrimitiveMissingInGfx("TriangleFan".to_string())),
+        Points => (Primitive::PointList, indices),
+        Lines => (Primitive::LineList, indices.map(|indices| whole(indices, 2))),
+        Triangles => (Primitive::TriangleList, indices.map(|indices| whole(indices, 3))),
+        LineStrip => {
+            let strip = strip(indices);
+            let lines = (1..strip.len()).flat_map(|i| vec![strip[i - 1], strip[i]]);
+            (Primitive::LineList, Some(lines.collect()))
+        }
+        LineLoop => {
+            let strip = strip(indices);
+            let len = strip.len();
+            let lines = (0..len)
+                .filter(|_| len > 1)
+                .flat_map(|i| vec![strip[i], strip[(i + 1) % len]]);
+            (Primitive::LineList, Some(lines.collect()))
+        }
+        // Every other triangle of a strip is flipped to keep the winding order.
+        TriangleStrip => {
+            let strip = strip(indices);
+            let triangles = (2..strip.len()).flat_map(|i| if i % 2 == 0 {
+                vec![strip[i - 2], strip[i - 1], strip[i]]
+            } else {
+                vec![strip[i - 2], strip[i], strip[i - 1]]
+            });
+            (Primitive::TriangleList, Some(triangles.collect()))
+        }
+        TriangleFan => {
+            let strip = strip(indices);
+            let triangles = (2..strip.len()).flat_map(|i| vec![strip[i - 1], strip[i], strip[0]]);
+            (Primitive::TriangleList, Some(triangles.collect()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cache::tests::CountingSource;
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| {
+                let bits = value.to_bits();
+                vec![bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]
+            })
+            .collect()
+    }
+
+    /// Imports `scene` as `scene.gltf`, with `files` next to it.
+    fn load_inline(
+        scene: &[u8],
+        files: &[(&str, &[u8])],
+        options: GltfSceneOptions,
+    ) -> Result<GltfSceneAsset, GltfError> {
+        let mut source = CountingSource::default();
+        source.files.insert("scene.gltf".to_string(), scene.to_vec());
+        for &(name, data) in files {
+            source.files.insert(name.to_string(), data.to_vec());
+        }
+        load_gltf(Arc::new(source), "scene.gltf", options)
+    }
+
+    #[test]
+    fn material_factors_default_to_the_specification() {
+        fn color(texture: &GltfTexture) -> [f32; 4] {
+            match texture.data {
+                TextureData::Rgba(color, _) => color,
+                _ => panic!("expected a color texture"),
+            }
+        }
+
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "materials": [
+                {},
+                {
+                    "pbrMetallicRoughness": {
+                        "baseColorFactor": [0.5, 0.5, 0.5, 1.0],
+                        "metallicFactor": 0.25,
+                        "roughnessFactor": 0.75
+                    }
+                }
+            ]
+        }"#;
+        let gltf = load_inline(scene, &[], GltfSceneOptions::default()).unwrap();
+        let (defaults, factors) = (&gltf.materials[0], &gltf.materials[1]);
+        assert_eq!(color(&defaults.base_color.0), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(color(&defaults.metallic.0), [1.0; 4]);
+        assert_eq!(color(&defaults.roughness.0), [1.0; 4]);
+        assert_eq!(defaults.base_color.0.index, None);
+        assert_eq!(color(&factors.base_color.0), [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(color(&factors.metallic.0), [0.25; 4]);
+        assert_eq!(color(&factors.roughness.0), [0.75; 4]);
+    }
+
+    #[test]
+    fn alpha_modes_and_double_sidedness_are_imported() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "materials": [
+                {},
+                { "alphaMode": "MASK", "alphaCutoff": 0.25, "doubleSided": true },
+                { "alphaMode": "BLEND" }
+            ]
+        }"#;
+        let gltf = load_inline(scene, &[], GltfSceneOptions::default()).unwrap();
+        let imported = gltf.materials
+            .iter()
+            .map(|material| (material.alpha, material.double_sided))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            imported,
+            vec![
+                ((AlphaMode::Opaque, 0.5), false),
+                ((AlphaMode::Mask, 0.25), true),
+                ((AlphaMode::Blend, 0.5), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn samplers_set_the_filter_and_wrapping_of_textures() {
+        use gfx::texture::{FilterMethod, WrapMode};
+
+        let gltf = Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "samplers": [
+                    {},
+                    { "magFilter": 9728, "wrapS": 33071, "wrapT": 33648 },
+                    { "magFilter": 9729, "minFilter": 9987 }
+                ]
+            }"#,
+        ).unwrap()
+            .validate_completely()
+            .unwrap();
+        let samplers = gltf.samplers()
+            .map(|sampler| {
+                let info = load_sampler_info(&sampler);
+                (info.filter, info.wrap_mode.0, info.wrap_mode.1)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            samplers,
+            vec![
+                (FilterMethod::Bilinear, WrapMode::Tile, WrapMode::Tile),
+                (FilterMethod::Scale, WrapMode::Clamp, WrapMode::Mirror),
+                (FilterMethod::Trilinear, WrapMode::Tile, WrapMode::Tile),
+            ]
+        );
+    }
+
+    #[test]
+    fn z_up_matrices_transform_z_up_vectors_like_the_originals() {
+        // Column-major, a rotation of 90 degrees around Y followed by a translation.
+        let m = [
+            [0., 0., -1., 0.],
+            [0., 1., 0., 0.],
+            [1., 0., 0., 0.],
+            [2., 3., 4., 1.],
+        ];
+        let transform = |m: [[f32; 4]; 4], v: [f32; 3]| {
+            let mut out = [m[3][0], m[3][1], m[3][2]];
+            for row in 0..3 {
+                for column in 0..3 {
+                    out[row] += m[column][row] * v[column];
+                }
+            }
+            out
+        };
+        let v = [1., 2., 3.];
+        assert_eq!(transform(z_up_matrix(m), z_up(v)), z_up(transform(m, v)));
+        assert_eq!(z_up([0., 1., 0.]), [0., 0., 1.]);
+        assert_eq!(z_up_scale([1., 2., 3.]), [1., 3., 2.]);
+    }
+
+    #[test]
+    fn scaled_imports_scale_positions_and_translations() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "cube.bin", "byteLength": 96 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 96 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 8,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [1.0, 1.0, 1.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 0 }] }],
+            "cameras": [
+                {
+                    "type": "perspective",
+                    "perspective": { "yfov": 1.0, "znear": 0.1, "zfar": 100.0 }
+                },
+                {
+                    "type": "orthographic",
+                    "orthographic": { "xmag": 1.0, "ymag": 2.0, "znear": 0.5, "zfar": 10.0 }
+                }
+            ],
+            "nodes": [{ "mesh": 0, "translation": [1.0, 0.0, 0.0] }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let corners = (0..8)
+            .flat_map(|i| vec![(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32])
+            .collect::<Vec<_>>();
+        let cube = f32_bytes(&corners);
+        let mut options = GltfSceneOptions::default();
+        options.scale = 2.0;
+        let gltf = load_inline(scene, &[("cube.bin", &cube[..])], options).unwrap();
+        let node = &gltf.nodes[0];
+        let expected = (0..8)
+            .map(|i| {
+                let corner = [(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32];
+                Separate::<Position>::new([corner[0] * 2., corner[1] * 2., corner[2] * 2.])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(node.primitives[0].attributes.0, expected);
+        let translation: [f32; 3] = node.local_transform.translation.into();
+        assert_eq!(translation, [2.0, 0.0, 0.0]);
+        assert_eq!(
+            gltf.cameras,
+            vec![
+                GltfCamera::Perspective {
+                    aspect_ratio: None,
+                    yfov: 1.0,
+                    znear: 0.2,
+                    zfar: Some(200.0),
+                },
+                GltfCamera::Orthographic {
+                    xmag: 2.0,
+                    ymag: 4.0,
+                    znear: 1.0,
+                    zfar: 20.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_chosen_scene_is_imported() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "point.bin", "byteLength": 12 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 1,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [0.0, 0.0, 0.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 0 }] }],
+            "nodes": [{ "mesh": 0 }, { "mesh": 0, "children": [2] }, { "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }, { "nodes": [1] }],
+            "scene": 0
+        }"#;
+        let point = f32_bytes(&[0., 0., 0.]);
+        let files = [("point.bin", &point[..])];
+        let mut options = GltfSceneOptions::default();
+        options.scene = Some(1);
+        let gltf = load_inline(scene, &files, options.clone()).unwrap();
+        assert_eq!(gltf.default_scene, Some(1));
+        assert!(gltf.nodes[0].primitives.is_empty());
+        assert_eq!(gltf.nodes[1].primitives.len(), 1);
+        assert_eq!(gltf.nodes[2].primitives.len(), 1);
+
+        options.scene = Some(2);
+        match load_inline(scene, &files, options) {
+            Err(GltfError::MissingScene(2, 2)) => {}
+            other => panic!("expected a missing scene, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn named_nodes_are_imported_with_their_descendants() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "point.bin", "byteLength": 12 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 1,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [0.0, 0.0, 0.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 0 }] }],
+            "nodes": [
+                { "name": "Props", "children": [1, 3] },
+                { "name": "Barrel", "mesh": 0, "children": [2] },
+                { "name": "Lid", "mesh": 0 },
+                { "name": "Crate", "mesh": 0 },
+                { "name": "Crate", "mesh": 0 }
+            ],
+            "scenes": [{ "nodes": [0, 4] }]
+        }"#;
+        let point = f32_bytes(&[0., 0., 0.]);
+        let files = [("point.bin", &point[..])];
+        let mut options = GltfSceneOptions::default();
+        options.node = Some("Barrel".to_string());
+        let gltf = load_inline(scene, &files, options.clone()).unwrap();
+        assert_eq!(gltf.root_node, Some(1));
+        let loaded = gltf.nodes
+            .iter()
+            .map(|node| !node.primitives.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(loaded, vec![false, true, true, false, false]);
+        assert_eq!(gltf.names.node("Barrel"), Some(1));
+        assert_eq!(gltf.names.nodes["Crate"], vec![3, 4]);
+        assert_eq!(gltf.names.mesh("Barrel"), None);
+
+        options.node = Some("Crate".to_string());
+        match load_inline(scene, &files, options.clone()) {
+            Err(GltfError::AmbiguousNodeName(ref name, 2)) if name == "Crate" => {}
+            other => panic!("expected an ambiguous name, got {:?}", other),
+        }
+        options.node = Some("Table".to_string());
+        match load_inline(scene, &files, options) {
+            Err(GltfError::MissingNode(ref name)) if name == "Table" => {}
+            other => panic!("expected a missing node, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vertices_within_the_tolerances_are_welded() {
+        let positions = [
+            [0., 0., 0.],
+            [1., 0., 0.],
+            [0., 1., 0.],
+            [1., 0., 0.000001],
+            [0., 1., 0.],
+            [1., 1., 0.],
+        ];
+        let up = [0., 0., 1.];
+        let normals = [up, up, up, up, [1., 0., 0.], up];
+        let tolerances = WeldTolerances::default();
+        assert_eq!(
+            weld_vertices(&positions, None, None, None, &tolerances),
+            vec![0, 1, 2, 1, 2, 5]
+        );
+        assert_eq!(
+            weld_vertices(&positions, Some(&normals), None, None, &tolerances),
+            vec![0, 1, 2, 1, 4, 5]
+        );
+        let tex_coords = [[0., 0.], [1., 0.], [0., 1.], [0.5, 0.], [0., 1.], [1., 1.]];
+        assert_eq!(
+            weld_vertices(&positions, None, Some(&tex_coords), None, &tolerances),
+            vec![0, 1, 2, 3, 2, 5]
+        );
+        // Vertices with different colors, joints or other attributes compared exactly stay
+        // apart even within the tolerances.
+        let exact = vec![vec![0], vec![0], vec![0], vec![1], vec![0], vec![0]];
+        assert_eq!(
+            weld_vertices(&positions, None, None, Some(&exact[..]), &tolerances),
+            vec![0, 1, 2, 3, 2, 5]
+        );
+    }
+
+    #[test]
+    fn flipped_transforms_map_flipped_coordinates() {
+        let apply = |transform: &TextureTransform, uv: [f32; 2]| {
+            let m = transform.matrix();
+            [
+                m[0][0] * uv[0] + m[0][1] * uv[1] + m[0][2],
+                m[1][0] * uv[0] + m[1][1] * uv[1] + m[1][2],
+            ]
+        };
+        let flip = |uv: [f32; 2]| [uv[0], 1. - uv[1]];
+        let transform = TextureTransform {
+            offset: [0.25, 0.5],
+            rotation: 0.5,
+            scale: [2., 3.],
+            tex_coord: Some(1),
+        };
+        let flipped = flip_v_transform(transform);
+        assert_eq!(flipped.tex_coord, Some(1));
+        for &uv in &[[0., 0.], [1., 0.], [0.25, 0.75]] {
+            let expected = flip(apply(&transform, uv));
+            let actual = apply(&flipped, flip(uv));
+            assert!((expected[0] - actual[0]).abs() < 1e-5);
+            assert!((expected[1] - actual[1]).abs() < 1e-5);
+        }
+        assert_eq!(
+            flip_v_transform(TextureTransform::default()),
+            TextureTransform::default()
+        );
+    }
+
+    #[test]
+    fn bounds_enclose_the_points() {
+        let bounds = bounds_of(&[[0., 1., 2.], [-1., 3., 0.], [2., 0., 1.]]);
+        assert_eq!(
+            bounds,
+            GltfBounds {
+                min: [-1., 0., 0.],
+                max: [2., 3., 2.],
+            }
+        );
+        let (center, radius) = GltfBounds {
+            min: [-1., -2., -2.],
+            max: [1., 2., 2.],
+        }.sphere();
+        assert_eq!(center, [0., 0., 0.]);
+        assert_eq!(radius, 3.);
+    }
+
+    #[test]
+    fn bounds_match_up_to_rounding() {
+        let bounds = GltfBounds {
+            min: [-1., 0., 100.],
+            max: [0.1, 1., 200.],
+        };
+        let rounded = GltfBounds {
+            min: [-1.000_000_1, 0., 100.000_01],
+            max: [0.1 * 3. / 3., 1., 200.000_02],
+        };
+        assert!(bounds_match(&bounds, &rounded));
+        let wrong = GltfBounds {
+            min: [-1., 0., 100.],
+            max: [0.2, 1., 200.],
+        };
+        assert!(!bounds_match(&bounds, &wrong));
+    }
+
+    #[test]
+    fn deferred_images_are_decoded_when_resolved() {
+        use base64;
+
+        let mut source = CountingSource::default();
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": "pixel.png" }],
+            "textures": [{ "source": 0 }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }]
+        }"#;
+        let png = base64::decode(concat!(
+            "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJ",
+            "AAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg=="
+        )).unwrap();
+        source.files.insert("scene.gltf".to_string(), scene.to_vec());
+        source.files.insert("pixel.png".to_string(), png);
+        let source = Arc::new(source);
+        let mut options = GltfSceneOptions::default();
+        options.defer_images = true;
+        let gltf = load_gltf(source.clone(), "scene.gltf", options).unwrap();
+        assert_eq!(source.loads("pixel.png"), 0);
+        let texture = &gltf.materials[0].base_color.0;
+        let deferred = texture.deferred.as_ref().unwrap();
+        assert_eq!(deferred.format, ImageFormat::Png);
+        match texture.data {
+            TextureData::Rgba(..) => {}
+            _ => panic!("expected a placeholder color"),
+        }
+        match deferred.resolve().unwrap() {
+            TextureData::Image(..) => {}
+            _ => panic!("expected a decoded image"),
+        }
+        assert_eq!(source.loads("pixel.png"), 1);
+    }
+
+    #[test]
+    fn failed_decodes_name_the_image() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": "broken.png" }],
+            "textures": [{ "source": 0 }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }]
+        }"#;
+        let files = [("broken.png", &b"not a png"[..])];
+        match load_inline(scene, &files, GltfSceneOptions::default()) {
+            Err(GltfError::Asset(ref err)) => {
+                assert_eq!(err.to_string(), "Failed to decode image 0 from broken.png");
+            }
+            other => panic!("expected a decoding error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn indices_of_every_width_give_the_same_triangles() {
+        let indices = [0u32, 1, 2, 2, 1, 3];
+        let positions = f32_bytes(&[0., 0., 0., 1., 0., 0., 0., 1., 0., 1., 1., 0.]);
+        let load = |component_type: u32, size: usize| {
+            let mut data = positions.clone();
+            for &index in &indices {
+                data.extend((0..size).map(|byte| (index >> (8 * byte)) as u8));
+            }
+            let scene = format!(
+                r#"{{
+                    "asset": {{ "version": "2.0" }},
+                    "buffers": [{{ "uri": "quad.bin", "byteLength": {} }}],
+                    "bufferViews": [
+                        {{ "buffer": 0, "byteLength": 48 }},
+                        {{ "buffer": 0, "byteOffset": 48, "byteLength": {} }}
+                    ],
+                    "accessors": [
+                        {{
+                            "bufferView": 0,
+                            "componentType": 5126,
+                            "count": 4,
+                            "type": "VEC3",
+                            "min": [0.0, 0.0, 0.0],
+                            "max": [1.0, 1.0, 0.0]
+                        }},
+                        {{
+                            "bufferView": 1,
+                            "componentType": {},
+                            "count": 6,
+                            "type": "SCALAR"
+                        }}
+                    ],
+                    "meshes": [{{
+                        "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}]
+                    }}],
+                    "nodes": [{{ "mesh": 0 }}],
+                    "scenes": [{{ "nodes": [0] }}]
+                }}"#,
+                data.len(),
+                data.len() - 48,
+                component_type
+            );
+            let files = [("quad.bin", &data[..])];
+            let options = GltfSceneOptions::default();
+            let mut gltf = load_inline(scene.as_bytes(), &files, options).unwrap();
+            let primitive = gltf.nodes.remove(0).primitives.remove(0);
+            (primitive.indices, primitive.attributes.0)
+        };
+        let shorts = load(5123, 2);
+        assert_eq!(shorts.0, Some(vec![0, 1, 2, 2, 1, 3]));
+        assert_eq!(load(5121, 1), shorts);
+        assert_eq!(load(5125, 4), shorts);
+    }
+
+    #[test]
+    fn joint_sets_are_combined_into_the_strongest_influences() {
+        let mut data = f32_bytes(&[0., 0., 0., 1., 0., 0., 0., 1., 0.]);
+        for _ in 0..3 {
+            data.extend(&[0, 1, 2, 3]);
+        }
+        for _ in 0..3 {
+            data.extend(&[4, 5, 6, 7]);
+        }
+        for _ in 0..3 {
+            data.extend(f32_bytes(&[0.1, 0.3, 0.05, 0.05]));
+        }
+        for _ in 0..3 {
+            data.extend(f32_bytes(&[0.2, 0.12, 0.15, 0.03]));
+        }
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "skinned.bin", "byteLength": 156 }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": 48, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": 60, "byteLength": 48 },
+                { "buffer": 0, "byteOffset": 108, "byteLength": 48 }
+            ],
+            "accessors": [
+                {
+                    "bufferView": 0,
+                    "componentType": 5126,
+                    "count": 3,
+                    "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0],
+                    "max": [1.0, 1.0, 0.0]
+                },
+                { "bufferView": 1, "componentType": 5121, "count": 3, "type": "VEC4" },
+                { "bufferView": 2, "componentType": 5121, "count": 3, "type": "VEC4" },
+                { "bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC4" },
+                { "bufferView": 4, "componentType": 5126, "count": 3, "type": "VEC4" }
+            ],
+            "meshes": [{
+                "primitives": [{
+                    "attributes": {
+                        "POSITION": 0,
+                        "JOINTS_0": 1,
+                        "JOINTS_1": 2,
+                        "WEIGHTS_0": 3,
+                        "WEIGHTS_1": 4
+                    }
+                }]
+            }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let files = [("skinned.bin", &data[..])];
+        let mut gltf = load_inline(scene, &files, GltfSceneOptions::default()).unwrap();
+        let primitive = gltf.nodes.remove(0).primitives.remove(0);
+        let joints = primitive.joints.unwrap();
+        let weights = primitive.weights.unwrap();
+        assert_eq!(joints, vec![[1, 4, 6, 5]; 3]);
+        for vertex in weights {
+            let expected = [0.3 / 0.77, 0.2 / 0.77, 0.15 / 0.77, 0.12 / 0.77];
+            for (weight, expected) in vertex.iter().zip(&expected) {
+                assert!((weight - expected).abs() < 1e-5, "{:?}", vertex);
+            }
+        }
+    }
+
+    #[test]
+    fn interleaved_attributes_are_read_by_stride() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "interleaved.bin", "byteLength": 72 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 72, "byteStride": 24 }],
+            "accessors": [
+                {
+                    "bufferView": 0,
+                    "componentType": 5126,
+                    "count": 3,
+                    "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0],
+                    "max": [1.0, 1.0, 0.0]
+                },
+                {
+                    "bufferView": 0,
+                    "byteOffset": 12,
+                    "componentType": 5126,
+                    "count": 3,
+                    "type": "VEC3"
+                }
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "NORMAL": 1 } }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        // Each vertex is a position followed by its normal.
+        let data = f32_bytes(&[
+            0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+            1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+            0.0, 1.0, 0.0, 1.0, 0.0, 0.0,
+        ]);
+        let files = [("interleaved.bin", &data[..])];
+        let gltf = load_inline(scene, &files, GltfSceneOptions::default()).unwrap();
+        let attributes = &gltf.nodes[0].primitives[0].attributes;
+        assert_eq!(
+            attributes.0,
+            vec![
+                Separate::<Position>::new([0.0, 0.0, 0.0]),
+                Separate::<Position>::new([1.0, 0.0, 0.0]),
+                Separate::<Position>::new([0.0, 1.0, 0.0]),
+            ]
+        );
+        assert_eq!(
+            attributes.3,
+            Some(vec![
+                Separate::<Normal>::new([0.0, 0.0, 1.0]),
+                Separate::<Normal>::new([0.0, 1.0, 0.0]),
+                Separate::<Normal>::new([1.0, 0.0, 0.0]),
+            ])
+        );
+    }
+
+    #[test]
+    fn primitives_without_indices_keep_their_vertices_in_order() {
+        let scene = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "triangle.bin", "byteLength": 36 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 3,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [1.0, 1.0, 0.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+        let data = f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let files = [("triangle.bin", &data[..])];
+        let gltf = load_inline(scene, &files, GltfSceneOptions::default()).unwrap();
+        let primitive = &gltf.nodes[0].primitives[0];
+        assert_eq!(primitive.primitive, Primitive::TriangleList);
+        assert_eq!(primitive.indices, None);
+        assert_eq!(
+            primitive.attributes.0,
+            vec![
+                Separate::<Position>::new([0.0, 0.0, 0.0]),
+                Separate::<Position>::new([1.0, 0.0, 0.0]),
+                Separate::<Position>::new([0.0, 1.0, 0.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_loops_and_fans_become_lists() {
+        use gltf::mesh::Mode::*;
+        assert_eq!(
+            list_topology(LineStrip, None, 3),
+            (Primitive::LineList, Some(vec![0, 1, 1, 2]))
+        );
+        assert_eq!(
+            list_topology(LineLoop, Some(vec![2, 0, 1]), 3),
+            (Primitive::LineList, Some(vec![2, 0, 0, 1, 1, 2]))
+        );
+        assert_eq!(
+            list_topology(TriangleStrip, None, 4),
+            (Primitive::TriangleList, Some(vec![0, 1, 2, 1, 3, 2]))
+        );
+        assert_eq!(
+            list_topology(TriangleFan, None, 4),
+            (Primitive::TriangleList, Some(vec![1, 2, 0, 2, 3, 0]))
+        );
+        assert_eq!(
+            list_topology(Triangles, Some(vec![0, 1, 2, 3]), 4),
+            (Primitive::TriangleList, Some(vec![0, 1, 2]))
+        );
+        assert_eq!(list_topology(Points, None, 4), (Primitive::PointList, None));
+    }
+
+    fn node(parent: Option<usize>, children: Vec<usize>) -> GltfNode {
+        GltfNode {
+            primitives: Vec::new(),
+            parent,
+            children,
+            local_transform: LocalTransform::default(),
+            name: None,
+            mesh: None,
+            camera: None,
+            light: None,
+            extras: None,
+            skin: None,
+            morph_weights: Vec::new(),
+            morph_target_names: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn components_of_every_type_are_converted_to_floats() {
+        use gltf::accessor::DataType::*;
+        let half = f32_bytes(&[0.5]);
+        let table = vec![
+            (I8, false, vec![0x81, 0x7f], vec![-127., 127.]),
+            (I8, true, vec![0x80, 0x81, 0x00, 0x7f], vec![-1., -1., 0., 1.]),
+            (U8, false, vec![0x00, 0xff], vec![0., 255.]),
+            (U8, true, vec![0x00, 0x33, 0xff], vec![0., 0.2, 1.]),
+            (I16, false, vec![0x00, 0x80, 0xff, 0x7f], vec![-32768., 32767.]),
+            (I16, true, vec![0x00, 0x80, 0x01, 0x80, 0xff, 0x7f], vec![-1., -1., 1.]),
+            (U16, false, vec![0x34, 0x12], vec![4660.]),
+            (U16, true, vec![0x00, 0x00, 0xff, 0xff], vec![0., 1.]),
+            (U32, false, vec![0x78, 0x56, 0x34, 0x12], vec![305419896.]),
+            (F32, false, half.clone(), vec![0.5]),
+            (F32, true, half, vec![0.5]),
+        ];
+        for (data_type, normalized, bytes, expected) in table {
+            assert_eq!(
+                float_components(data_type, normalized, &bytes),
+                expected,
+                "{:?} normalized: {}",
+                data_type,
+                normalized
+            );
+        }
+    }
+
+    #[test]
+    fn weights_are_normalized() {
+        assert_eq!(normalize_weights([0.5, 0.5, 0.5, 0.5]), [0.25; 4]);
+        assert_eq!(normalize_weights([2., 0., 0., 0.]), [1., 0., 0., 0.]);
+        assert_eq!(normalize_weights([0.; 4]), [0.; 4]);
+    }
+
+    #[test]
+    fn cubic_spline_samplers_have_tangents() {
+        use animation::InterpolationType::*;
+        assert!(check_sampler_output(&Linear, 4, 4).is_ok());
+        assert!(check_sampler_output(&Step, 4, 4).is_ok());
+        assert!(check_sampler_output(&CubicSpline, 4, 12).is_ok());
+        match check_sampler_output(&CubicSpline, 4, 4) {
+            Err(GltfError::InvalidSamplerOutput(12, 4)) => {}
+            other => panic!("expected invalid sampler output, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flat_normals_face_out_of_each_triangle() {
+        let vertices = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+        let faces = Some(vec![0, 1, 2, 0, 3, 1]);
+        let normals = generate_normals(&vertices, &faces, NormalGeneration::Flat);
+        assert_eq!(
+            normals,
+            vec![
+                [0., 0., 1.],
+                [0., 0., 1.],
+                [0., 0., 1.],
+                [0., 1., 0.],
+                [0., 1., 0.],
+                [0., 1., 0.],
+            ]
+        );
+    }
+
+    #[test]
+    fn smooth_normals_are_shared_by_indexed_vertices() {
+        let vertices = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+        let faces = Some(vec![0, 1, 2, 0, 3, 1]);
+        let normals = generate_normals(&vertices, &faces, NormalGeneration::Smooth);
+        let shared = normalize([0., 1., 1.]);
+        assert_eq!(normals[0], shared);
+        assert_eq!(normals[1], shared);
+        assert_eq!(normals[2], [0., 0., 1.]);
+        assert_eq!(normals[3], shared);
+        assert_eq!(normals[4], [0., 1., 0.]);
+    }
+
+    #[test]
+    fn tangents_follow_the_texture_coordinates() {
+        let vertices = [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]];
+        let normals = [[0., 0., 1.]; 3];
+        let tex_coords = [[0., 0.], [1., 0.], [0., 1.]];
+        let tangents = generate_tangents(&vertices, &tex_coords, &normals, &None);
+        assert_eq!(tangents, vec![[1., 0., 0., 1.]; 3]);
+
+        let mirrored = [[1., 0.], [0., 0.], [1., 1.]];
+        let tangents = generate_tangents(&vertices, &mirrored, &normals, &None);
+        assert_eq!(tangents, vec![[-1., 0., 0., -1.]; 3]);
+    }
+
+    #[test]
+    fn unsigned_color_components_are_normalized() {
+        use gltf::accessor::DataType;
+        assert_eq!(
+            normalized_components(DataType::U8, &[0, 51, 255]).unwrap(),
+            vec![0., 0.2, 1.]
+        );
+        assert_eq!(
+            normalized_components(DataType::U16, &[0, 0, 0xff, 0x7f, 0xff, 0xff]).unwrap(),
+            vec![0., 32767. / 65535., 1.]
+        );
+        let mut floats = Vec::new();
+        for &value in &[0.25f32, 1.5] {
+            let bits = value.to_bits();
+            floats.extend((0..4).map(|byte| (bits >> (byte * 8)) as u8));
+        }
+        assert_eq!(
+            normalized_components(DataType::F32, &floats).unwrap(),
+            vec![0.25, 1.5]
+        );
+        assert_eq!(normalized_components(DataType::I8, &[0]), None);
+    }
+
+    #[test]
+    fn rgb_colors_are_opaque() {
+        let components = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        assert_eq!(
+            rgba_colors(&components, 3),
+            vec![[0.1, 0.2, 0.3, 1.], [0.4, 0.5, 0.6, 1.]]
+        );
+        assert_eq!(rgba_colors(&components[..4], 4), vec![[0.1, 0.2, 0.3, 0.4]]);
+    }
+
+    #[test]
+    fn trees_pass_the_hierarchy_check() {
+        let nodes = vec![
+            node(None, vec![1, 2]),
+            node(Some(0), vec![]),
+            node(Some(0), vec![3]),
+            node(Some(2), vec![]),
+            node(None, vec![]),
+        ];
+        assert!(check_hierarchy(&nodes, 256).is_ok());
+    }
+
+    #[test]
+    fn cycles_fail_the_hierarchy_check() {
+        let nodes = vec![
+            node(None, vec![]),
+            node(Some(2), vec![2]),
+            node(Some(1), vec![1]),
+        ];
+        match check_hierarchy(&nodes, 256) {
+            Err(GltfError::InvalidHierarchy(1)) => {}
+            other => panic!("expected an invalid hierarchy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shared_children_fail_the_hierarchy_check() {
+        let nodes = vec![
+            node(None, vec![2]),
+            node(None, vec![2]),
+            node(Some(1), vec![]),
+        ];
+        match check_hierarchy(&nodes, 256) {
+            Err(GltfError::InvalidHierarchy(2)) => {}
+            other => panic!("expected an invalid hierarchy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deep_hierarchies_fail_the_hierarchy_check() {
+        let nodes = (0..10)
+            .map(|index| match index {
+                0 => node(None, vec![1]),
+                9 => node(Some(8), vec![]),
+                _ => node(Some(index - 1), vec![index + 1]),
+            })
+            .collect::<Vec<_>>();
+        assert!(check_hierarchy(&nodes, 10).is_ok());
+        match check_hierarchy(&nodes, 9) {
+            Err(GltfError::GltfImporterError(importer::Error::Validation(errs))) => {
+                assert_eq!(errs.len(), 1);
+                assert_eq!(errs[0].0.to_string(), "nodes[9]");
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
     }
 }
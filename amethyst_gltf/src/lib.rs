@@ -9,50 +9,133 @@ extern crate gltf;
 extern crate gltf_utils;
 extern crate imagefmt;
 extern crate itertools;
+#[macro_use]
+extern crate log;
+extern crate rayon;
 extern crate specs;
 
+pub use cache::CachedSource;
+pub use format::{ImageFormat, ImageReference};
 pub use format::GltfSceneFormat;
 pub use systems::GltfSceneLoaderSystem;
 
+use std::fmt;
+use std::sync::Arc;
+
 use animation::{Animation, Sampler};
-use assets::{Asset, Error as AssetError, Handle};
+use assets::{Asset, Error as AssetError, Handle, Result as AssetResult, Source};
+use core::cgmath::Matrix4;
 use core::transform::LocalTransform;
+use fnv::FnvHashMap;
 use gfx::Primitive;
-use renderer::{MeshHandle, TextureData, TextureHandle, VertexBufferCombination};
+use gltf::json::Value;
+use renderer::{MeshHandle, TextureData, TextureHandle, TextureMetadata, VertexBufferCombination};
 use specs::DenseVecStorage;
 
+mod cache;
 mod format;
 mod systems;
 
 /// A single graphics primitive
 #[derive(Debug)]
 pub struct GltfPrimitive {
+    /// A point, line or triangle list, other glTF modes are converted to lists when importing.
+    /// The passes of the renderer draw triangle lists, other lists need a pass created for them.
     pub primitive: Primitive,
     pub material: Option<usize>,
+    /// The indices the vertices were unwound with, `None` if the primitive has no indices and its
+    /// vertices are already in draw order.
     pub indices: Option<Vec<usize>>,
     pub attributes: VertexBufferCombination,
+    /// The texture coordinate sets from `TEXCOORD_1` onwards, the first set is in `attributes`.
+    pub extra_tex_coords: Vec<Vec<[f32; 2]>>,
+    /// The indices into the joints of the skin of the node for each vertex.
+    pub joints: Option<Vec<[u16; 4]>>,
+    /// The handedness of the tangent of each vertex, the bitangent is
+    /// `cross(normal, tangent) * handedness`.
+    pub tangent_handedness: Option<Vec<f32>>,
+    /// The weights of the joints of each vertex, normalized to sum up to 1.  When the primitive
+    /// has several `JOINTS_n` and `WEIGHTS_n` sets only the 4 strongest influences are kept.
+    pub weights: Option<Vec<[f32; 4]>>,
+    /// The morph targets that can be blended onto the primitive, `morph_weights` of the node
+    /// gives the default weight of each.
+    pub morph_targets: Vec<GltfMorphTarget>,
+    /// The bounds of the positions, before any morph targets are blended in.
+    pub bounds: GltfBounds,
+    /// How many vertices took the attributes of an earlier vertex when welding with
+    /// `GltfSceneOptions::weld`.  The attributes are still unwound to one vertex per corner, so
+    /// this doesn't make the vertex buffers smaller.
+    pub welded_vertices: usize,
     pub handle: Option<MeshHandle>,
 }
 
+/// Axis-aligned bounding box of the vertices of a primitive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GltfBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl GltfBounds {
+    /// The center and radius of a sphere enclosing the box.
+    pub fn sphere(&self) -> ([f32; 3], f32) {
+        let center = [
+            (self.min[0] + self.max[0]) / 2.,
+            (self.min[1] + self.max[1]) / 2.,
+            (self.min[2] + self.max[2]) / 2.,
+        ];
+        let extent = [
+            self.max[0] - center[0],
+            self.max[1] - center[1],
+            self.max[2] - center[2],
+        ];
+        let radius = (extent[0] * extent[0] + extent[1] * extent[1] + extent[2] * extent[2]).sqrt();
+        (center, radius)
+    }
+}
+
+/// A morph target, the offset of each vertex attribute added when blending in the target
+#[derive(Clone, Debug)]
+pub struct GltfMorphTarget {
+    pub positions: Option<Vec<[f32; 3]>>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tangents: Option<Vec<[f32; 3]>>,
+}
+
 /// Alpha mode for material
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AlphaMode {
+    /// Alpha is ignored, the material is fully opaque.
     Opaque,
+    /// Fragments with alpha below the alpha cutoff are discarded, the rest are opaque.
     Mask,
+    /// Alpha blends the material with what is behind it.
     Blend,
 }
 
 /// GLTF material, PBR based
+///
+/// Textures are paired with the factor they are multiplied with.  Materials without a texture get
+/// a single color texture of their factor, and a factor of 1, so the texture alone gives the
+/// material parameter.
 #[derive(Debug)]
 pub struct GltfMaterial {
-    base_color: (GltfTexture, [f32; 4]),
-    metallic: (GltfTexture, f32),
-    roughness: (GltfTexture, f32),
-    emissive: (GltfTexture, [f32; 3]),
-    normal: Option<(GltfTexture, f32)>,
-    occlusion: Option<(GltfTexture, f32)>,
-    alpha: (AlphaMode, f32),
-    double_sided: bool,
+    pub base_color: (GltfTexture, [f32; 4]),
+    pub metallic: (GltfTexture, f32),
+    pub roughness: (GltfTexture, f32),
+    pub emissive: (GltfTexture, [f32; 3]),
+    /// The normal map and its scale.
+    pub normal: Option<(GltfTexture, f32)>,
+    /// The occlusion map and its strength.
+    pub occlusion: Option<(GltfTexture, f32)>,
+    /// The alpha mode and the alpha cutoff of `AlphaMode::Mask`, 0.5 by default.
+    pub alpha: (AlphaMode, f32),
+    /// Whether back faces are drawn too, instead of being culled.
+    pub double_sided: bool,
+    /// Whether the material uses `KHR_materials_unlit`, and should be drawn without lighting.
+    pub unlit: bool,
+    /// The application specific `extras` of the material, as they are in the asset.
+    pub extras: Option<Value>,
 }
 
 /// A GLTF defined texture, will be in `TextureData` format in the output from the loader.
@@ -60,11 +143,92 @@ pub struct GltfMaterial {
 pub struct GltfTexture {
     pub data: TextureData,
     pub handle: Option<TextureHandle>,
+    /// The index of the glTF texture this was loaded from, `None` for textures made from material
+    /// factors.
+    pub index: Option<usize>,
+    /// The texture coordinate set this texture is sampled with, unless `transform` overrides it.
+    pub tex_coord: u32,
+    /// How the texture coordinates are transformed before sampling this texture.
+    pub transform: TextureTransform,
+    /// The image to decode when it's needed, `data` is a white placeholder until then.  Only
+    /// scenes loaded with `GltfSceneOptions::defer_images` have these.
+    pub deferred: Option<DeferredImage>,
 }
 
 impl GltfTexture {
     pub fn new(data: TextureData) -> Self {
-        Self { data, handle: None }
+        Self {
+            data,
+            handle: None,
+            index: None,
+            tex_coord: 0,
+            transform: TextureTransform::default(),
+            deferred: None,
+        }
+    }
+}
+
+/// An image that is decoded when it's needed instead of when the scene is loaded
+#[derive(Clone)]
+pub struct DeferredImage {
+    pub reference: ImageReference,
+    pub format: ImageFormat,
+    metadata: TextureMetadata,
+    /// The channel taken out of the decoded image, for metallic and roughness textures.
+    channel: Option<usize>,
+    source: Arc<Source>,
+}
+
+impl DeferredImage {
+    /// Reads and decodes the image.
+    pub fn resolve(&self) -> AssetResult<TextureData> {
+        format::resolve_image(self)
+    }
+}
+
+impl fmt::Debug for DeferredImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeferredImage")
+            .field("reference", &self.reference)
+            .field("format", &self.format)
+            .field("channel", &self.channel)
+            .finish()
+    }
+}
+
+/// The texture coordinate transform from `KHR_texture_transform`, identity if the extension isn't
+/// used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureTransform {
+    pub offset: [f32; 2],
+    /// Counter-clockwise rotation in radians.
+    pub rotation: f32,
+    pub scale: [f32; 2],
+    /// The texture coordinate set to use instead of the one of the texture reference.
+    pub tex_coord: Option<u32>,
+}
+
+impl TextureTransform {
+    /// The row-major matrix mapping texture coordinates, offset * rotation * scale.
+    pub fn matrix(&self) -> [[f32; 3]; 3] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (sx, sy) = (self.scale[0], self.scale[1]);
+        [
+            [cos * sx, sin * sy, self.offset[0]],
+            [-sin * sx, cos * sy, self.offset[1]],
+            [0., 0., 1.],
+        ]
+    }
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        TextureTransform {
+            offset: [0., 0.],
+            rotation: 0.,
+            scale: [1., 1.],
+            tex_coord: None,
+        }
     }
 }
 
@@ -75,6 +239,155 @@ pub struct GltfNode {
     pub parent: Option<usize>,
     pub children: Vec<usize>,
     pub local_transform: LocalTransform,
+    pub name: Option<String>,
+    /// The index of the glTF mesh the primitives were loaded from.
+    pub mesh: Option<usize>,
+    /// The index of the camera attached to this node, looking down its negative Z axis.
+    pub camera: Option<usize>,
+    /// The index of the light attached to this node, shining down its negative Z axis.
+    pub light: Option<usize>,
+    /// The application specific `extras` of the node, as they are in the asset.
+    pub extras: Option<Value>,
+    /// The index of the skin deforming the meshes of this node.
+    pub skin: Option<usize>,
+    /// The default weight of each morph target of the mesh of this node.
+    pub morph_weights: Vec<f32>,
+    /// The name of each morph target of the mesh of this node, empty if the asset has none.
+    pub morph_target_names: Vec<String>,
+}
+
+/// A skin, binding the vertices of meshes to joint nodes
+#[derive(Debug)]
+pub struct GltfSkin {
+    /// The nodes used as joints, the joint indices of vertices index into this.
+    pub joints: Vec<usize>,
+    /// The inverse bind matrix of each joint, in column-major order.  Identities if the asset
+    /// doesn't define them.
+    pub inverse_bind_matrices: Vec<[[f32; 4]; 4]>,
+    /// The node that is the common root of the joints, if the asset specifies one.
+    pub skeleton: Option<usize>,
+}
+
+/// A camera projection
+#[derive(Clone, Debug, PartialEq)]
+pub enum GltfCamera {
+    Perspective {
+        /// The aspect ratio of the field of view, `None` uses the aspect ratio of the viewport.
+        aspect_ratio: Option<f32>,
+        /// The vertical field of view in radians.
+        yfov: f32,
+        znear: f32,
+        /// The distance to the far clipping plane, `None` means there is no far plane.
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        /// Half the width of the view.
+        xmag: f32,
+        /// Half the height of the view.
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+/// A light from the `KHR_lights_punctual` extension
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfLight {
+    pub name: Option<String>,
+    /// The linear RGB color of the light.
+    pub color: [f32; 3],
+    /// The brightness of the light as given by the asset, in candela for point and spot lights
+    /// and in lux for directional lights.
+    pub intensity: f32,
+    pub kind: GltfLightKind,
+}
+
+/// The type of a light, with the properties specific to it
+#[derive(Clone, Debug, PartialEq)]
+pub enum GltfLightKind {
+    /// Light coming from infinitely far away, along the negative Z axis of its node.
+    Directional,
+    /// Light shining from the origin of its node in every direction.
+    Point {
+        /// The distance at which the light reaches zero, `None` means it is unlimited.
+        range: Option<f32>,
+    },
+    /// Light shining from the origin of its node in a cone along its negative Z axis.
+    Spot {
+        /// The distance at which the light reaches zero, `None` means it is unlimited.
+        range: Option<f32>,
+        /// The angle in radians from the axis at which the light starts falling off.
+        inner_cone_angle: f32,
+        /// The angle in radians from the axis at which the light reaches zero.
+        outer_cone_angle: f32,
+    },
+}
+
+impl GltfCamera {
+    /// The projection matrix of the camera, as defined by the glTF specification.
+    /// `viewport_aspect` is used when the camera has no aspect ratio of its own.
+    pub fn projection(&self, viewport_aspect: f32) -> Matrix4<f32> {
+        match *self {
+            GltfCamera::Perspective {
+                aspect_ratio,
+                yfov,
+                znear,
+                zfar,
+            } => {
+                let aspect = aspect_ratio.unwrap_or(viewport_aspect);
+                let focal = 1. / (0.5 * yfov).tan();
+                let (depth, offset) = match zfar {
+                    Some(zfar) => (
+                        (zfar + znear) / (znear - zfar),
+                        2. * zfar * znear / (znear - zfar),
+                    ),
+                    None => (-1., -2. * znear),
+                };
+                // Matrix4::new takes the columns one after another.
+                Matrix4::new(
+                    focal / aspect,
+                    0.,
+                    0.,
+                    0.,
+                    0.,
+                    focal,
+                    0.,
+                    0.,
+                    0.,
+                    0.,
+                    depth,
+                    -1.,
+                    0.,
+                    0.,
+                    offset,
+                    0.,
+                )
+            }
+            GltfCamera::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => Matrix4::new(
+                1. / xmag,
+                0.,
+                0.,
+                0.,
+                0.,
+                1. / ymag,
+                0.,
+                0.,
+                0.,
+                0.,
+                2. / (znear - zfar),
+                0.,
+                0.,
+                0.,
+                (zfar + znear) / (znear - zfar),
+                1.,
+            ),
+        }
+    }
 }
 
 /// A single scene is defined as a list of the root nodes in the node hierarchy for the full asset
@@ -86,6 +399,7 @@ pub struct GltfScene {
 /// A single animation
 #[derive(Debug)]
 pub struct GltfAnimation {
+    pub name: Option<String>,
     // node index, vec will be same size as samplers, and reference the sampler at the same index
     pub nodes: Vec<usize>,
     pub samplers: Vec<Sampler>,
@@ -94,10 +408,161 @@ pub struct GltfAnimation {
 }
 
 /// Options used when loading a GLTF file
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct GltfSceneOptions {
     pub generate_tex_coords: Option<(f32, f32)>,
+    /// Generate normals for triangle primitives that have none.
+    pub generate_normals: Option<NormalGeneration>,
     pub load_animations: bool,
+    /// How strictly the file is checked against the specification.
+    pub validation: ValidationLevel,
+    /// How many missing bytes at the end of a buffer are tolerated.  Buffers that are a little
+    /// short are padded with zeros and a warning is logged instead of failing the import.
+    pub buffer_padding: usize,
+    /// Called as the import progresses, for example to update a loading screen.
+    pub progress: Option<ProgressCallback>,
+    /// Convert the asset from the Y-up coordinates of glTF to Z-up, by rotating +Y onto +Z.
+    /// Vertices, node transforms, skins and animations are all converted, cameras keep looking
+    /// along the -Z axis of their node.
+    pub z_up: bool,
+    /// Uniform scale applied to vertex positions and translations, for example 0.01 to import an
+    /// asset made in centimeters into a world in meters.  The clipping planes and orthographic
+    /// extents of cameras are scaled too.  Rotations and normals are unaffected.
+    pub scale: f32,
+    /// Only find the images of textures instead of decoding them, they can be decoded later with
+    /// `DeferredImage::resolve`.
+    pub defer_images: bool,
+    /// How deep the node hierarchy may nest, root nodes being at depth 1.  Deeper hierarchies are
+    /// rejected, since loading them into a scene recurses once per level.
+    pub max_node_depth: usize,
+    /// Names of supported extensions to ignore, such as `KHR_texture_transform`.  Assets that
+    /// require one of them fail to load with `Error::ExtensionDisabled`.
+    pub disabled_extensions: Vec<String>,
+    /// The index of the scene to import, instead of the default scene.  Only the nodes of that
+    /// scene get their meshes and only its materials decode their images, the other nodes are
+    /// kept so node indices stay valid.  `None` imports every scene.
+    pub scene: Option<usize>,
+    /// The name of a node to import with its descendants instead of a scene, they are spawned
+    /// in place of the scene and only they get their meshes and decode their images.  Imports
+    /// fail if no node or several nodes have the name.
+    pub node: Option<String>,
+    /// Unify the attributes of vertices of a primitive that are within these tolerances of each
+    /// other, rewriting its indices to share them.  Smooth normal generation then averages
+    /// across the welded vertices.  This doesn't shrink the vertex buffers: the renderer has no
+    /// index buffers, so the attributes are still unwound to one vertex per corner.
+    pub weld: Option<WeldTolerances>,
+    /// Map the V texture coordinate of every set to `1 - V`, for renderers with the origin of
+    /// textures at the bottom left.  Texture transforms are changed to match.
+    pub flip_v: bool,
+}
+
+impl Default for GltfSceneOptions {
+    fn default() -> Self {
+        GltfSceneOptions {
+            generate_tex_coords: None,
+            generate_normals: None,
+            load_animations: false,
+            validation: ValidationLevel::default(),
+            buffer_padding: 0,
+            progress: None,
+            z_up: false,
+            scale: 1.0,
+            defer_images: false,
+            max_node_depth: 256,
+            disabled_extensions: Vec::new(),
+            scene: None,
+            node: None,
+            weld: None,
+            flip_v: false,
+        }
+    }
+}
+
+/// A stage reached while importing a glTF file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportProgress {
+    /// The JSON of the file has been parsed.
+    Parsed,
+    /// A buffer has been loaded, `index` of the `count` buffers of the file.
+    BufferLoaded { index: usize, count: usize },
+    /// An image has been decoded, `index` of the `count` images of the file.  Images are decoded
+    /// in parallel, so they aren't reported in order, and images used by several textures are
+    /// reported once per texture.
+    ImageDecoded { index: usize, count: usize },
+}
+
+/// Callback reporting the progress of a glTF import
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<Fn(ImportProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    /// Create a new callback, it can be called from several threads at the same time.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(ImportProgress) + Send + Sync + 'static,
+    {
+        ProgressCallback(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// How strictly glTF files are validated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationLevel {
+    /// Every part of the file is validated.
+    Complete,
+    /// Only what's needed to load the file safely is validated: the structure of the file, the
+    /// buffer lengths and the buffer URIs of binary files.  Meant for loading slightly broken
+    /// assets during development.
+    Minimal,
+    /// Nothing is validated, broken files can cause panics while loading.
+    None,
+}
+
+impl Default for ValidationLevel {
+    fn default() -> Self {
+        ValidationLevel::Complete
+    }
+}
+
+/// How normals are generated for primitives without them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalGeneration {
+    /// Every vertex gets the normal of its triangle.
+    Flat,
+    /// Every vertex gets the area weighted average normal of the triangles sharing it.  Only
+    /// indexed primitives share vertices, others are shaded flat.
+    Smooth,
+}
+
+/// How close vertices must be to be welded together
+///
+/// Vertices are merged when their positions, normals and first set of texture coordinates are all
+/// within these tolerances.  Their other attributes, like colors, joints, tangents and morph
+/// target offsets, must be equal, so welding never replaces them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeldTolerances {
+    /// The largest distance between merged positions.
+    pub position: f32,
+    /// The largest angle in radians between merged normals.
+    pub normal_angle: f32,
+    /// The largest distance between merged texture coordinates.
+    pub tex_coord: f32,
+}
+
+impl Default for WeldTolerances {
+    fn default() -> Self {
+        WeldTolerances {
+            position: 1e-5,
+            normal_angle: 1e-3,
+            tex_coord: 1e-5,
+        }
+    }
 }
 
 /// Actual asset produced on finished loading of a GLTF scene file.
@@ -107,10 +572,60 @@ pub struct GltfSceneAsset {
     pub scenes: Vec<GltfScene>,
     pub materials: Vec<GltfMaterial>,
     pub animations: Vec<GltfAnimation>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+    pub skins: Vec<GltfSkin>,
     pub default_scene: Option<usize>,
+    /// The node spawned instead of a scene, when `GltfSceneOptions::node` chose one.
+    pub root_node: Option<usize>,
+    pub names: GltfNames,
+    /// The application specific `extras` of every glTF mesh, indexed by `GltfNode::mesh`.
+    pub mesh_extras: Vec<Option<Value>>,
     pub options: GltfSceneOptions,
 }
 
+/// Indices of the named parts of a GLTF scene
+///
+/// Names aren't unique in glTF, so every name maps to the indices of all the parts with it, in
+/// ascending order.
+#[derive(Clone, Debug, Default)]
+pub struct GltfNames {
+    /// Indices into `GltfSceneAsset::nodes`.
+    pub nodes: FnvHashMap<String, Vec<usize>>,
+    /// Indices of glTF meshes, as in `GltfNode::mesh`.
+    pub meshes: FnvHashMap<String, Vec<usize>>,
+    /// Indices into `GltfSceneAsset::materials`.
+    pub materials: FnvHashMap<String, Vec<usize>>,
+    /// Indices into `GltfSceneAsset::animations`, empty unless animations are loaded.
+    pub animations: FnvHashMap<String, Vec<usize>>,
+}
+
+impl GltfNames {
+    /// The index of the first node with the given name.
+    pub fn node(&self, name: &str) -> Option<usize> {
+        self.nodes.get(name).map(|indices| indices[0])
+    }
+
+    /// The index of the first mesh with the given name.
+    pub fn mesh(&self, name: &str) -> Option<usize> {
+        self.meshes.get(name).map(|indices| indices[0])
+    }
+
+    /// The index of the first material with the given name.
+    pub fn material(&self, name: &str) -> Option<usize> {
+        self.materials.get(name).map(|indices| indices[0])
+    }
+}
+
+impl GltfSceneAsset {
+    /// Finds the first animation with the given name.
+    pub fn animation(&self, name: &str) -> Option<&GltfAnimation> {
+        self.animations
+            .iter()
+            .find(|animation| animation.name.as_ref().map_or(false, |n| n == name))
+    }
+}
+
 impl Into<Result<GltfSceneAsset, AssetError>> for GltfSceneAsset {
     fn into(self) -> Result<GltfSceneAsset, AssetError> {
         Ok(self)
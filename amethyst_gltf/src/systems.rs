@@ -97,17 +97,23 @@ impl<'a> System<'a> for GltfSceneLoaderSystem {
                 > = Vec::default();
                 let mut node_map = HashMap::default();
 
-                // Use the default scene if set, otherwise use the first scene.
+                // Use the chosen node if set, otherwise the default scene if set, otherwise the
+                // first scene.
                 // Note that the format will throw an error if the default scene is not set,
                 // and there are more than one scene in the GLTF, so defaulting to scene 0 is safe
-                let scene_index = scene_asset.default_scene.unwrap_or(0);
-                let scene = &scene_asset.scenes[scene_index];
+                let root_nodes = match scene_asset.root_node {
+                    Some(node_index) => vec![node_index],
+                    None => {
+                        let scene_index = scene_asset.default_scene.unwrap_or(0);
+                        scene_asset.scenes[scene_index].root_nodes.clone()
+                    }
+                };
 
                 // If we only have one root node in the scene, we load that node onto the attached
                 // entity
-                if scene.root_nodes.len() == 1 {
+                if root_nodes.len() == 1 {
                     load_node(
-                        scene.root_nodes[0],
+                        root_nodes[0],
                         &entity,
                         scene_asset,
                         &mut local_transforms,
@@ -127,7 +133,7 @@ impl<'a> System<'a> for GltfSceneLoaderSystem {
                 } else {
                     // If we have multiple root nodes in the scene, we need to create new entities
                     // for each root node and set their parent reference to the attached entity
-                    for root_node_index in &scene.root_nodes {
+                    for root_node_index in &root_nodes {
                         let root_entity = entities.create();
                         parents.insert(root_entity, Parent { entity });
                         transforms.insert(root_entity, Transform::default());
@@ -426,6 +432,7 @@ fn load_material(
     // TODO: emissive factor
     // TODO: alpha
     // TODO: double sided
+    // TODO: unlit
     let albedo = material
         .base_color
         .0
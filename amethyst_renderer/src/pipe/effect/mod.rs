@@ -5,7 +5,7 @@
 use std::mem;
 
 use fnv::FnvHashMap as HashMap;
-use gfx::{Primitive, ShaderSet};
+use gfx::{Primitive, Rect, ShaderSet};
 use gfx::Bind;
 use gfx::buffer::{Info as BufferInfo, Role as BufferRole};
 use gfx::memory::Usage;
@@ -70,6 +70,8 @@ pub struct Effect {
     pub data: Data,
     const_bufs: HashMap<String, usize>,
     globals: HashMap<String, usize>,
+    blend_variants: Vec<PipelineState<Meta>>,
+    active_blend_variant: Option<usize>,
 }
 
 impl Effect {
@@ -132,8 +134,21 @@ impl Effect {
         self.data.vertex_bufs.clear();
     }
 
+    /// Selects the pipeline state used by `draw`.
+    ///
+    /// `Some(i)` uses the `i`th blend variant added with `EffectBuilder::with_blend_variant`,
+    /// `None` uses the blending the effect was built with.
+    pub fn use_blend_variant(&mut self, variant: Option<usize>) {
+        assert!(variant.map_or(true, |i| i < self.blend_variants.len()));
+        self.active_blend_variant = variant;
+    }
+
     pub fn draw(&mut self, slice: &Slice, enc: &mut Encoder) {
-        enc.draw(&slice, &self.pso, &self.data);
+        let pso = match self.active_blend_variant {
+            Some(i) => &self.blend_variants[i],
+            None => &self.pso,
+        };
+        enc.draw(&slice, pso, &self.data);
     }
 }
 
@@ -171,6 +186,7 @@ pub struct EffectBuilder<'a> {
     prog: ProgramSource<'a>,
     rast: Rasterizer,
     const_bufs: Vec<BufferInfo>,
+    blend_variants: Vec<Blend>,
 }
 
 impl<'a> EffectBuilder<'a> {
@@ -183,6 +199,7 @@ impl<'a> EffectBuilder<'a> {
             rast: Rasterizer::new_fill().with_cull_back(),
             prog: src,
             const_bufs: Vec::new(),
+            blend_variants: Vec::new(),
         }
     }
 
@@ -272,6 +289,14 @@ impl<'a> EffectBuilder<'a> {
         self
     }
 
+    /// Adds an alternative pipeline state with `blend` used for every blended output.
+    ///
+    /// Variants are numbered in the order they're added, see `Effect::use_blend_variant`.
+    pub fn with_blend_variant(&mut self, blend: Blend) -> &mut Self {
+        self.blend_variants.push(blend);
+        self
+    }
+
     /// Adds a texture sampler to this `Effect`.
     pub fn with_texture(&mut self, name: &'a str) -> &mut Self {
         self.init.samplers.push(name);
@@ -279,6 +304,15 @@ impl<'a> EffectBuilder<'a> {
         self
     }
 
+    /// Enables the scissor test for this `Effect`.
+    ///
+    /// The scissor rectangle is stored in the `scissor` field of the effect data and covers the
+    /// whole target until changed.
+    pub fn with_scissor(&mut self) -> &mut Self {
+        self.init.scissor = true;
+        self
+    }
+
     /// Adds a vertex buffer to this `Effect`.
     pub fn with_raw_vertex_buffer(
         &mut self,
@@ -299,6 +333,15 @@ impl<'a> EffectBuilder<'a> {
         let prog = self.prog.compile(fac)?;
         let pso = fac.create_pipeline_state(&prog, self.prim, self.rast, self.init.clone())?;
 
+        let mut blend_variants = Vec::with_capacity(self.blend_variants.len());
+        for &blend in &self.blend_variants {
+            let mut init = self.init.clone();
+            for out in &mut init.out_blends {
+                out.2 = blend;
+            }
+            blend_variants.push(fac.create_pipeline_state(&prog, self.prim, self.rast, init)?);
+        }
+
         let mut data = Data::default();
 
         let const_bufs = self.init
@@ -341,12 +384,23 @@ impl<'a> EffectBuilder<'a> {
         data.out_depth = self.out
             .depth_buf()
             .map(|db| (db.as_output.clone(), (0, 0)));
+        if self.init.scissor {
+            let (w, h) = self.out.size();
+            data.scissor = Some(Rect {
+                x: 0,
+                y: 0,
+                w: w as u16,
+                h: h as u16,
+            });
+        }
 
         Ok(Effect {
             pso,
             data,
             const_bufs,
             globals,
+            blend_variants,
+            active_blend_variant: None,
         })
     }
 }
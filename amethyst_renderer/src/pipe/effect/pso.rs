@@ -11,6 +11,7 @@ type AccessInfo = pso::AccessInfo<Resources>;
 type DepthStencilTarget = target::DepthStencilTarget<DepthFormat>;
 type Manager = handle::Manager<Resources>;
 type RenderTarget = target::RenderTarget<ColorFormat>;
+type Scissor = target::Scissor;
 type BlendTarget = target::BlendTarget<ColorFormat>;
 type RawDataSet = pso::RawDataSet<Resources>;
 type InitResult<'r, M> = Result<M, InitError<&'r str>>;
@@ -22,6 +23,7 @@ pub struct Meta {
     out_colors: Vec<RenderTarget>,
     out_blends: Vec<BlendTarget>,
     out_depth: Option<DepthStencilTarget>,
+    scissor: bool,
     samplers: Vec<Sampler>,
     textures: Vec<RawShaderResource>,
     vertex_bufs: Vec<RawVertexBuffer>,
@@ -35,6 +37,7 @@ pub struct Init<'d> {
     pub out_colors: Vec<<RenderTarget as DataLink<'d>>::Init>,
     pub out_blends: Vec<<BlendTarget as DataLink<'d>>::Init>,
     pub out_depth: Option<<DepthStencilTarget as DataLink<'d>>::Init>,
+    pub scissor: bool,
     pub samplers: Vec<<Sampler as DataLink<'d>>::Init>,
     pub textures: Vec<<RawShaderResource as DataLink<'d>>::Init>,
     pub vertex_bufs: Vec<<RawVertexBuffer as DataLink<'d>>::Init>,
@@ -132,6 +135,12 @@ impl<'d> PipelineInit for Init<'d> {
             meta.out_depth = Some(meta_depth);
         }
 
+        if self.scissor {
+            let mut meta_scissor = <Scissor as DataLink<'d>>::new();
+            desc.scissor = meta_scissor.link_scissor();
+            meta.scissor = true;
+        }
+
         for smp in &self.samplers {
             let mut meta_smp = <Sampler as DataLink<'d>>::new();
             for info in &info.samplers {
@@ -181,6 +190,7 @@ pub struct Data {
     pub out_colors: Vec<<RenderTarget as DataBind<Resources>>::Data>,
     pub out_blends: Vec<<BlendTarget as DataBind<Resources>>::Data>,
     pub out_depth: Option<<DepthStencilTarget as DataBind<Resources>>::Data>,
+    pub scissor: Option<<Scissor as DataBind<Resources>>::Data>,
     pub samplers: Vec<<Sampler as DataBind<Resources>>::Data>,
     pub textures: Vec<<RawShaderResource as DataBind<Resources>>::Data>,
     pub vertex_bufs: Vec<<RawVertexBuffer as DataBind<Resources>>::Data>,
@@ -215,6 +225,10 @@ impl PipelineData<Resources> for Data {
             meta_depth.bind_to(out, &depth, mgr, acc);
         }
 
+        if let (true, Some(scissor)) = (meta.scissor, self.scissor) {
+            out.scissor = scissor;
+        }
+
         let samplers = meta.samplers.iter().zip(&self.samplers);
         for (meta_samp, samp) in samplers {
             meta_samp.bind_to(out, &samp, mgr, acc);
@@ -53,6 +53,8 @@ pub struct ScreenDimensions {
     h: f32,
     /// Width divided by height.
     aspect_ratio: f32,
+    /// The number of pixels per logical pixel.
+    hidpi: f32,
     pub(crate) dirty: bool,
 }
 
@@ -63,6 +65,7 @@ impl ScreenDimensions {
             w: w as f32,
             h: h as f32,
             aspect_ratio: w as f32 / h as f32,
+            hidpi: 1.0,
             dirty: false,
         }
     }
@@ -82,6 +85,17 @@ impl ScreenDimensions {
         self.aspect_ratio
     }
 
+    /// Returns the number of pixels per logical pixel of the window, this is larger than 1.0 on
+    /// high DPI displays.
+    pub fn hidpi_factor(&self) -> f32 {
+        self.hidpi
+    }
+
+    /// Updates the hidpi factor of the screen.  This doesn't resize the window.
+    pub fn update_hidpi_factor(&mut self, factor: f32) {
+        self.hidpi = factor;
+    }
+
     /// Updates the width and height of the screen and recomputes the aspect
     /// ratio.
     pub fn update(&mut self, w: u32, h: u32) {
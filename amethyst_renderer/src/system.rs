@@ -110,6 +110,11 @@ where
             }
         }
 
+        let hidpi_factor = self.renderer.window().hidpi_factor();
+        if hidpi_factor != screen_dimensions.hidpi_factor() {
+            screen_dimensions.update_hidpi_factor(hidpi_factor);
+        }
+
         // Send resource size changes to the window
         if screen_dimensions.dirty {
             self.renderer.window().set_inner_size(
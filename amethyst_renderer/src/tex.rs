@@ -46,6 +46,12 @@ impl Texture {
     pub fn view(&self) -> &RawShaderResourceView {
         &self.view
     }
+
+    /// Returns the width and height of the texture in texels.
+    pub fn size(&self) -> (u16, u16) {
+        let (w, h, _, _) = self.texture.get_info().kind.get_dimensions();
+        (w, h)
+    }
 }
 
 impl Asset for Texture {
@@ -0,0 +1,16 @@
+use specs::{Component, NullStorage};
+
+/// A component that draws this entity with additive blending, which is useful for glowing
+/// elements.
+///
+/// Additive entities are drawn after every other entity.  They keep their z order among
+/// themselves, but are always drawn on top of entities that use the normal alpha blending.
+/// The glyph brush always uses alpha blending, so additive text is rasterized into a texture
+/// whenever it changes like text with a `color_gradient`, unless it's drawn from distance fields
+/// with `DrawUi::with_sdf_text`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UiAdditiveBlend;
+
+impl Component for UiAdditiveBlend {
+    type Storage = NullStorage<Self>;
+}
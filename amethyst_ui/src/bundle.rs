@@ -27,6 +27,9 @@ impl<'a, 'b> ECSBundle<'a, 'b> for UiBundle {
         builder: DispatcherBuilder<'a, 'b>,
     ) -> Result<DispatcherBuilder<'a, 'b>> {
         world.register::<UiImage>();
+        world.register::<UiClip>();
+        world.register::<UiAdditiveBlend>();
+        world.register::<UiOpacity>();
         world.register::<UiTransform>();
         world.register::<UiText>();
         world.register::<TextEditing>();
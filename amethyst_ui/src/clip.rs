@@ -0,0 +1,13 @@
+use specs::{Component, NullStorage};
+
+/// A component that clips the drawing of this entity, and of every entity nested under it with
+/// a `Parent`, to this entity's `UiTransform`.
+///
+/// Nested clips intersect with the clips above them.  Text is drawn by the glyph brush without
+/// a scissor test, so glyphs that are partly outside of the clip are hidden entirely.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UiClip;
+
+impl Component for UiClip {
+    type Storage = NullStorage<Self>;
+}
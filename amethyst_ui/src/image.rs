@@ -6,8 +6,55 @@ use specs::{Component, DenseVecStorage};
 pub struct UiImage {
     /// The texture to display
     pub texture: TextureHandle,
+    /// If this is set the image is drawn as a nine-slice, see `NineSlice`.
+    pub nine_slice: Option<NineSlice>,
+    /// The region of the texture to display, as `[u_min, v_min, u_max, v_max]`.
+    ///
+    /// Defaults to the whole texture.
+    pub tex_coords: [f32; 4],
+    /// A color the texture is multiplied with.
+    ///
+    /// Defaults to white, which leaves the texture unchanged.
+    pub color: [f32; 4],
+    /// Mirrors the image horizontally.
+    pub flip_x: bool,
+    /// Mirrors the image vertically.
+    pub flip_y: bool,
+}
+
+impl UiImage {
+    /// Initializes a new `UiImage` stretching the whole texture over its `UiTransform`.
+    pub fn new(texture: TextureHandle) -> UiImage {
+        UiImage {
+            texture,
+            nine_slice: None,
+            tex_coords: [0., 0., 1., 1.],
+            color: [1., 1., 1., 1.],
+            flip_x: false,
+            flip_y: false,
+        }
+    }
 }
 
 impl Component for UiImage {
     type Storage = DenseVecStorage<Self>;
 }
+
+/// Borders of a texture, in texels, that are kept unscaled when the image is drawn.
+///
+/// With `UiImage::tex_coords` set the borders are measured from the edges of that region.
+///
+/// The corners are drawn at their original size, the edges are only stretched along their
+/// length and the center is stretched to fill the rest of the `UiTransform`.  If the transform
+/// is too small to fit the borders they're scaled down evenly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NineSlice {
+    /// Width of the left border.
+    pub left: f32,
+    /// Width of the right border.
+    pub right: f32,
+    /// Height of the top border.
+    pub top: f32,
+    /// Height of the bottom border.
+    pub bottom: f32,
+}
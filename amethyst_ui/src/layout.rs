@@ -0,0 +1,684 @@
+//! Glyph layout for `UiText`.
+
+use std::collections::HashMap;
+use std::f32::{INFINITY, NEG_INFINITY};
+use std::hash::{Hash, Hasher};
+
+use gfx_glyph::{BuiltInLineBreaker, Color, FontId, GlyphPositioner, HorizontalAlign, Layout,
+                Scale, VariedSection, VerticalAlign};
+use rusttype::{point, Font, Point, PositionedGlyph, Rect};
+
+use super::*;
+use pass::ClipRect;
+
+/// Lays out a `UiText` by positioning its glyphs with the glyph brush's own layout, then
+/// applying the spacing options of the text on top of that.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UiLayout {
+    /// The glyph brush layout matching the overflow and alignment of the text.
+    pub layout: Layout<BuiltInLineBreaker>,
+    /// The horizontal alignment of the text.
+    pub align_h: HorizontalAlignment,
+    /// The vertical alignment of the text.
+    pub align_v: VerticalAlignment,
+    /// The direction the text runs in.
+    pub direction: TextDirection,
+    /// A multiplier for the distance between lines.
+    pub line_spacing: f32,
+    /// Whether the kerning of the font is applied.
+    pub kerning: bool,
+    /// Extra space added after every glyph.
+    pub letter_spacing: f32,
+    /// The distance between tab stops, tabs aren't moved to tab stops if this is 0.0.
+    pub tab_width: f32,
+    /// Whether glyphs are moved to whole pixels.
+    pub pixel_perfect: bool,
+    /// If this is set glyphs that aren't entirely within this rectangle are dropped.  Glyphs
+    /// without an outline only need to be within its left and right edges.
+    pub visible: Option<ClipRect>,
+}
+
+impl UiLayout {
+    /// Creates the layout used to draw `ui_text` with `hidpi` pixels per logical pixel.
+    pub fn new(ui_text: &UiText, hidpi: f32) -> UiLayout {
+        let line_breaker = BuiltInLineBreaker::UnicodeLineBreaker;
+        let h_align = match ui_text.align_h {
+            HorizontalAlignment::Left | HorizontalAlignment::Justify => HorizontalAlign::Left,
+            HorizontalAlignment::Center => HorizontalAlign::Center,
+            HorizontalAlignment::Right => HorizontalAlign::Right,
+        };
+        let v_align = match ui_text.align_v {
+            VerticalAlignment::Top => VerticalAlign::Top,
+            VerticalAlignment::Center => VerticalAlign::Center,
+            VerticalAlignment::Bottom => VerticalAlign::Bottom,
+        };
+        let layout = match ui_text.overflow {
+            Overflow::Clip | Overflow::Truncate => Layout::SingleLine {
+                line_breaker,
+                h_align,
+                v_align,
+            },
+            Overflow::Wrap => Layout::Wrap {
+                line_breaker,
+                h_align,
+                v_align,
+            },
+        };
+        UiLayout {
+            layout,
+            align_h: ui_text.align_h,
+            align_v: ui_text.align_v,
+            direction: ui_text.direction,
+            line_spacing: ui_text.line_spacing,
+            kerning: ui_text.kerning,
+            letter_spacing: ui_text.letter_spacing * hidpi,
+            tab_width: ui_text.tab_width * hidpi,
+            pixel_perfect: ui_text.pixel_perfect,
+            visible: None,
+        }
+    }
+}
+
+impl Hash for UiLayout {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.layout.hash(hasher);
+        self.align_h.hash(hasher);
+        self.align_v.hash(hasher);
+        self.direction.hash(hasher);
+        self.line_spacing.to_bits().hash(hasher);
+        self.kerning.hash(hasher);
+        self.letter_spacing.to_bits().hash(hasher);
+        self.tab_width.to_bits().hash(hasher);
+        self.pixel_perfect.hash(hasher);
+        self.visible
+            .map(|visible| {
+                (
+                    visible.left.to_bits(),
+                    visible.top.to_bits(),
+                    visible.right.to_bits(),
+                    visible.bottom.to_bits(),
+                )
+            })
+            .hash(hasher);
+    }
+}
+
+impl GlyphPositioner for UiLayout {
+    fn calculate_glyphs<'font>(
+        &self,
+        font: &HashMap<FontId, Font<'font>>,
+        section: &VariedSection,
+    ) -> Vec<(PositionedGlyph<'font>, Color, FontId)> {
+        let mut glyphs = self.layout.calculate_glyphs(font, section);
+        hide_line_feeds(&mut glyphs, font, section);
+        if !self.kerning {
+            remove_kerning(&mut glyphs, self.align_h);
+        }
+        if self.letter_spacing != 0.0 {
+            space_letters(&mut glyphs, self.letter_spacing, self.align_h);
+        }
+        if self.tab_width > 0.0 {
+            expand_tabs(&mut glyphs, font, section, self);
+        }
+        if let (HorizontalAlignment::Justify, Layout::Wrap { .. }) = (self.align_h, self.layout) {
+            justify_lines(&mut glyphs, font, section);
+        }
+        if self.direction == TextDirection::RightToLeft {
+            mirror_lines(&mut glyphs);
+        }
+        if self.line_spacing != 1.0 {
+            space_lines(&mut glyphs, self.line_spacing, self.align_v);
+        }
+        if self.pixel_perfect {
+            for glyph in &mut glyphs {
+                let pos = glyph.0.position();
+                let pos = point(pos.x.round(), pos.y.round());
+                glyph.0 = glyph.0.clone().into_unpositioned().positioned(pos);
+            }
+        }
+        if let Some(visible) = self.visible {
+            glyphs.retain(|glyph| {
+                let x = glyph.0.position().x;
+                let advance = glyph.0.unpositioned().h_metrics().advance_width;
+                let within_lines = glyph.0.pixel_bounding_box().map_or(true, |bb| {
+                    bb.min.y as f32 >= visible.top && bb.max.y as f32 <= visible.bottom
+                });
+                x >= visible.left && x + advance <= visible.right && within_lines
+            });
+        }
+        glyphs
+    }
+
+    fn bounds_rect(&self, section: &VariedSection) -> Rect<f32> {
+        self.layout.bounds_rect(section)
+    }
+}
+
+/// Replaces the glyphs of line feeds, which the glyph brush lays out as line breaks but still
+/// draws, with spaces.
+///
+/// There is a glyph for every char of the section.
+fn hide_line_feeds<'font>(
+    glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>,
+    font: &HashMap<FontId, Font<'font>>,
+    section: &VariedSection,
+) {
+    let chars = section.text.iter().flat_map(|text| text.text.chars());
+    for (glyph, c) in glyphs.iter_mut().zip(chars) {
+        if c != '\n' {
+            continue;
+        }
+        let space = font.get(&glyph.2).and_then(|font| font.glyph(' '));
+        if let Some(space) = space {
+            let scale = glyph.0.scale();
+            let position = glyph.0.position();
+            glyph.0 = space.scaled(scale).positioned(position);
+        }
+    }
+}
+
+/// Moves every glyph so it starts where the glyph before it on the same line ends, undoing the
+/// kerning between them.
+///
+/// Glyphs on the same line share a baseline.  Centered and right aligned lines are moved so they
+/// stay aligned.
+fn remove_kerning<'font>(
+    glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>,
+    align_h: HorizontalAlignment,
+) {
+    let mut line_start = 0;
+    while line_start < glyphs.len() {
+        let baseline = glyphs[line_start].0.position().y;
+        let line_len = glyphs[line_start..]
+            .iter()
+            .take_while(|glyph| glyph.0.position().y == baseline)
+            .count();
+        let line = &mut glyphs[line_start..line_start + line_len];
+        let start = line[0].0.position().x;
+        let end = {
+            let last = &line[line_len - 1].0;
+            last.position().x + last.unpositioned().h_metrics().advance_width
+        };
+        let width = line.iter()
+            .map(|glyph| glyph.0.unpositioned().h_metrics().advance_width)
+            .sum::<f32>();
+        let shift = match align_h {
+            HorizontalAlignment::Left | HorizontalAlignment::Justify => 0.0,
+            HorizontalAlignment::Center => (start + width - end) / 2.0,
+            HorizontalAlignment::Right => start + width - end,
+        };
+        let mut x = start - shift;
+        for glyph in line.iter_mut() {
+            let advance = glyph.0.unpositioned().h_metrics().advance_width;
+            let pos = point(x, glyph.0.position().y);
+            glyph.0 = glyph.0.clone().into_unpositioned().positioned(pos);
+            x += advance;
+        }
+        line_start += line_len;
+    }
+}
+
+/// Moves every glyph right by `letter_spacing` for every glyph before it on the same line.
+///
+/// Glyphs on the same line share a baseline.  Wrapping doesn't take the extra space into
+/// account.  Centered and right aligned lines are moved so they stay aligned.
+fn space_letters<'font>(
+    glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>,
+    letter_spacing: f32,
+    align_h: HorizontalAlignment,
+) {
+    let mut line_start = 0;
+    while line_start < glyphs.len() {
+        let baseline = glyphs[line_start].0.position().y;
+        let line_len = glyphs[line_start..]
+            .iter()
+            .take_while(|glyph| glyph.0.position().y == baseline)
+            .count();
+        let extra = line_len as f32 * letter_spacing;
+        let shift = match align_h {
+            HorizontalAlignment::Left | HorizontalAlignment::Justify => 0.0,
+            HorizontalAlignment::Center => extra / 2.0,
+            HorizontalAlignment::Right => extra,
+        };
+        let line = &mut glyphs[line_start..line_start + line_len];
+        for (i, glyph) in line.iter_mut().enumerate() {
+            let pos = glyph.0.position();
+            let x = pos.x + i as f32 * letter_spacing - shift;
+            glyph.0 = glyph.0.clone().into_unpositioned().positioned(point(x, pos.y));
+        }
+        line_start += line_len;
+    }
+}
+
+/// Moves the text after every tab to the next tab stop, which are `layout.tab_width` apart starting
+/// from the first glyph of the line.
+///
+/// Glyphs on the same line share a baseline.  Tabs are replaced with spaces stretched to the
+/// width they take up with `stretch_space`.  Wrapping doesn't take the extra space into account.
+/// Centered and right aligned lines are moved so they stay aligned.
+fn expand_tabs<'font>(
+    glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>,
+    font: &HashMap<FontId, Font<'font>>,
+    section: &VariedSection,
+    layout: &UiLayout,
+) {
+    let mut chars = section.text.iter().flat_map(|text| text.text.chars());
+    let mut line_start = 0;
+    while line_start < glyphs.len() {
+        let baseline = glyphs[line_start].0.position().y;
+        let line_len = glyphs[line_start..]
+            .iter()
+            .take_while(|glyph| glyph.0.position().y == baseline)
+            .count();
+        let line = &mut glyphs[line_start..line_start + line_len];
+        let origin = line[0].0.position().x;
+        let mut extra = 0.0;
+        for (glyph, c) in line.iter_mut().zip(chars.by_ref()) {
+            let pos = glyph.0.position();
+            let x = pos.x + extra;
+            if c == '\t' {
+                // The text after the tab starts at the first stop past the end of the tab.
+                let tab_width = layout.tab_width;
+                let end = x + layout.letter_spacing - origin;
+                let advance = origin + ((end / tab_width).floor() + 1.0) * tab_width - x
+                    - layout.letter_spacing;
+                let old_advance = glyph.0.unpositioned().h_metrics().advance_width;
+                if stretch_space(glyph, font, point(x, pos.y), advance) {
+                    extra += advance - old_advance;
+                    continue;
+                }
+            }
+            glyph.0 = glyph.0.clone().into_unpositioned().positioned(point(x, pos.y));
+        }
+        let shift = match layout.align_h {
+            HorizontalAlignment::Left | HorizontalAlignment::Justify => 0.0,
+            HorizontalAlignment::Center => extra / 2.0,
+            HorizontalAlignment::Right => extra,
+        };
+        if shift != 0.0 {
+            for glyph in line.iter_mut() {
+                let pos = glyph.0.position();
+                let pos = point(pos.x - shift, pos.y);
+                glyph.0 = glyph.0.clone().into_unpositioned().positioned(pos);
+            }
+        }
+        line_start += line_len;
+    }
+}
+
+/// Widens the spaces between the words of every wrapped line so the line ends at the right edge
+/// of the bounds of `section`.  The last line of every paragraph isn't changed.
+///
+/// Glyphs on the same line share a baseline.  Spaces are stretched like tabs in `expand_tabs`.
+fn justify_lines<'font>(
+    glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>,
+    font: &HashMap<FontId, Font<'font>>,
+    section: &VariedSection,
+) {
+    let right = section.screen_position.0 + section.bounds.0;
+    let chars = section
+        .text
+        .iter()
+        .flat_map(|text| text.text.chars())
+        .collect::<Vec<_>>();
+    if !right.is_finite() || chars.len() != glyphs.len() {
+        return;
+    }
+    let mut line_start = 0;
+    while line_start < glyphs.len() {
+        let baseline = glyphs[line_start].0.position().y;
+        let line_len = glyphs[line_start..]
+            .iter()
+            .take_while(|glyph| glyph.0.position().y == baseline)
+            .count();
+        let line_end = line_start + line_len;
+        let line_chars = &chars[line_start..line_end];
+        // Paragraphs end at line feeds, which are part of the line they end.
+        let paragraph_end = line_end == glyphs.len() || line_chars[line_len - 1] == '\n';
+        let first = line_chars.iter().position(|c| !c.is_whitespace());
+        let last = line_chars.iter().rposition(|c| !c.is_whitespace());
+        if let (false, Some(first), Some(last)) = (paragraph_end, first, last) {
+            let gaps = line_chars[first..last].iter().filter(|&&c| c == ' ').count();
+            let end = {
+                let glyph = &glyphs[line_start + last].0;
+                glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+            };
+            if gaps > 0 && end < right {
+                let share = (right - end) / gaps as f32;
+                let mut extra = 0.0;
+                for (i, glyph) in glyphs[line_start..line_end].iter_mut().enumerate() {
+                    let pos = glyph.0.position();
+                    let x = pos.x + extra;
+                    if i > first && i < last && line_chars[i] == ' ' {
+                        let advance = glyph.0.unpositioned().h_metrics().advance_width + share;
+                        if stretch_space(glyph, font, point(x, pos.y), advance) {
+                            extra += share;
+                            continue;
+                        }
+                    }
+                    glyph.0 = glyph.0.clone().into_unpositioned().positioned(point(x, pos.y));
+                }
+            }
+        }
+        line_start = line_end;
+    }
+}
+
+/// Replaces `glyph` with a space of the same font and height stretched to `advance`, placed at
+/// `position`.  Spaces aren't drawn, but the caret and selection cover them.
+///
+/// Returns false and leaves `glyph` unchanged if the font has no space with an advance.
+fn stretch_space<'font>(
+    glyph: &mut (PositionedGlyph<'font>, Color, FontId),
+    font: &HashMap<FontId, Font<'font>>,
+    position: Point<f32>,
+    advance: f32,
+) -> bool {
+    let space = match font.get(&glyph.2).and_then(|font| font.glyph(' ')) {
+        Some(space) => space,
+        None => return false,
+    };
+    let scale = glyph.0.scale();
+    let space_advance = space.clone().scaled(scale).h_metrics().advance_width;
+    if space_advance <= 0.0 {
+        return false;
+    }
+    let scale = Scale {
+        x: scale.x * advance / space_advance,
+        y: scale.y,
+    };
+    glyph.0 = space.scaled(scale).positioned(position);
+    true
+}
+
+/// Mirrors every line horizontally within the area it covers, so the first glyph of a line is
+/// at its right edge.
+///
+/// Glyphs on the same line share a baseline.  The glyphs themselves aren't mirrored, and the
+/// lines keep their alignment.
+fn mirror_lines<'font>(glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>) {
+    let mut line_start = 0;
+    while line_start < glyphs.len() {
+        let baseline = glyphs[line_start].0.position().y;
+        let line_len = glyphs[line_start..]
+            .iter()
+            .take_while(|glyph| glyph.0.position().y == baseline)
+            .count();
+        let line = &mut glyphs[line_start..line_start + line_len];
+        let (left, right) = line.iter().fold((INFINITY, NEG_INFINITY), |(left, right), glyph| {
+            let x = glyph.0.position().x;
+            let advance = glyph.0.unpositioned().h_metrics().advance_width;
+            (left.min(x), right.max(x + advance))
+        });
+        for glyph in line.iter_mut() {
+            let pos = glyph.0.position();
+            let advance = glyph.0.unpositioned().h_metrics().advance_width;
+            let x = left + right - pos.x - advance;
+            glyph.0 = glyph.0.clone().into_unpositioned().positioned(point(x, pos.y));
+        }
+        line_start += line_len;
+    }
+}
+
+/// Scales the distance of every line from the first line by `line_spacing`.
+///
+/// Glyphs on the same line share a baseline.  Centered and bottom aligned text is moved so it
+/// stays aligned.
+fn space_lines<'font>(
+    glyphs: &mut Vec<(PositionedGlyph<'font>, Color, FontId)>,
+    line_spacing: f32,
+    align_v: VerticalAlignment,
+) {
+    let (first, last) = match (glyphs.first(), glyphs.last()) {
+        (Some(first), Some(last)) => (first.0.position().y, last.0.position().y),
+        _ => return,
+    };
+    let extra = (last - first) * (line_spacing - 1.0);
+    let shift = match align_v {
+        VerticalAlignment::Top => 0.0,
+        VerticalAlignment::Center => extra / 2.0,
+        VerticalAlignment::Bottom => extra,
+    };
+    for glyph in glyphs.iter_mut() {
+        let pos = glyph.0.position();
+        let y = first + (pos.y - first) * line_spacing - shift;
+        glyph.0 = glyph.0.clone().into_unpositioned().positioned(point(pos.x, y));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gfx_glyph::{Scale, SectionText};
+    use rusttype::{FontCollection, GlyphId};
+
+    use super::*;
+
+    fn layout_glyphs(
+        layout: UiLayout,
+        text: &str,
+        bounds: (f32, f32),
+    ) -> Vec<PositionedGlyph<'static>> {
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let mut fonts = HashMap::new();
+        fonts.insert(FontId(0), font);
+        let section = VariedSection {
+            screen_position: (0., 0.),
+            bounds,
+            z: 0.,
+            layout: layout.layout,
+            text: vec![
+                SectionText {
+                    text,
+                    scale: Scale::uniform(20.),
+                    color: [0.; 4],
+                    font_id: FontId(0),
+                },
+            ],
+        };
+        layout
+            .calculate_glyphs(&fonts, &section)
+            .into_iter()
+            .map(|glyph| glyph.0)
+            .collect()
+    }
+
+    fn left_top_layout(wrap: bool) -> UiLayout {
+        let line_breaker = BuiltInLineBreaker::UnicodeLineBreaker;
+        let h_align = HorizontalAlign::Left;
+        let v_align = VerticalAlign::Top;
+        UiLayout {
+            layout: if wrap {
+                Layout::Wrap {
+                    line_breaker,
+                    h_align,
+                    v_align,
+                }
+            } else {
+                Layout::SingleLine {
+                    line_breaker,
+                    h_align,
+                    v_align,
+                }
+            },
+            align_h: HorizontalAlignment::Left,
+            align_v: VerticalAlignment::Top,
+            direction: TextDirection::LeftToRight,
+            line_spacing: 1.0,
+            kerning: true,
+            letter_spacing: 0.0,
+            tab_width: 0.0,
+            pixel_perfect: false,
+            visible: None,
+        }
+    }
+
+    fn layout_baselines(line_spacing: f32) -> Vec<f32> {
+        let layout = UiLayout {
+            line_spacing,
+            ..left_top_layout(true)
+        };
+        // Every word is wrapped onto its own line.
+        layout_glyphs(layout, "a b c", (1., INFINITY))
+            .iter()
+            .map(|glyph| glyph.position().y)
+            .collect()
+    }
+
+    #[test]
+    fn glyphs_outside_of_the_visible_area_are_dropped() {
+        let layout = UiLayout {
+            visible: Some(ClipRect {
+                left: 0.,
+                top: 0.,
+                right: INFINITY,
+                bottom: 25.,
+            }),
+            ..left_top_layout(true)
+        };
+        // Every word is wrapped onto its own line, only the first line is within the area.
+        let glyphs = layout_glyphs(layout, "a b c", (1., INFINITY));
+        let outlined = glyphs
+            .iter()
+            .filter(|glyph| glyph.pixel_bounding_box().is_some())
+            .count();
+        assert_eq!(outlined, 1);
+    }
+
+    fn layout_width(letter_spacing: f32) -> f32 {
+        let layout = UiLayout {
+            letter_spacing,
+            ..left_top_layout(false)
+        };
+        let glyphs = layout_glyphs(layout, "abcde", (INFINITY, INFINITY));
+        let last = glyphs.last().unwrap();
+        last.position().x + last.unpositioned().h_metrics().advance_width
+    }
+
+    #[test]
+    fn line_spacing_scales_line_distance() {
+        let normal = layout_baselines(1.0);
+        let double = layout_baselines(2.0);
+        assert_eq!(normal.len(), double.len());
+        let last = normal.len() - 1;
+        assert_eq!(double[0], normal[0]);
+        let expected = (normal[last] - normal[0]) * 2.0;
+        assert!((double[last] - double[0] - expected).abs() < 1e-3);
+        assert!(normal[last] > normal[0]);
+    }
+
+    #[test]
+    fn letter_spacing_widens_text() {
+        let normal = layout_width(0.0);
+        let spaced = layout_width(3.0);
+        // The last of the five glyphs is moved by the spacing of the four before it.
+        assert!((spaced - normal - 12.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn right_to_left_starts_at_right_edge() {
+        let layout = left_top_layout(false);
+        let ltr = layout_glyphs(layout, "abc", (INFINITY, INFINITY));
+        let rtl = layout_glyphs(
+            UiLayout {
+                direction: TextDirection::RightToLeft,
+                ..layout
+            },
+            "abc",
+            (INFINITY, INFINITY),
+        );
+        // The line covers the same area, with the glyphs in reverse order.
+        assert_eq!(rtl[2].position().x, ltr[0].position().x);
+        let rtl_right = rtl[0].position().x + rtl[0].unpositioned().h_metrics().advance_width;
+        let ltr_right = ltr[2].position().x + ltr[2].unpositioned().h_metrics().advance_width;
+        assert!((rtl_right - ltr_right).abs() < 1e-3);
+        assert!(rtl[0].position().x > rtl[1].position().x);
+    }
+
+    #[test]
+    fn pixel_perfect_glyphs_are_on_whole_pixels() {
+        let positions = |pixel_perfect| {
+            let layout = UiLayout {
+                letter_spacing: 0.3,
+                pixel_perfect,
+                ..left_top_layout(false)
+            };
+            layout_glyphs(layout, "abcde", (INFINITY, INFINITY))
+                .iter()
+                .map(|glyph| glyph.position())
+                .collect::<Vec<_>>()
+        };
+        assert!(positions(false).iter().any(|pos| pos.x.fract() != 0.));
+        for pos in positions(true) {
+            assert_eq!(pos.x.fract(), 0.);
+            assert_eq!(pos.y.fract(), 0.);
+        }
+    }
+
+    #[test]
+    fn line_feeds_break_lines_without_a_glyph() {
+        let glyphs = layout_glyphs(left_top_layout(true), "a\nb", (INFINITY, INFINITY));
+        assert_eq!(glyphs.len(), 3);
+        assert!(glyphs[2].position().y > glyphs[0].position().y);
+        // The font has no glyph for a line feed, so it would be drawn as the missing glyph.
+        assert_ne!(glyphs[1].id(), GlyphId(0));
+    }
+
+    #[test]
+    fn tabs_advance_to_the_next_stop() {
+        let layout = UiLayout {
+            tab_width: 100.,
+            ..left_top_layout(false)
+        };
+        for text in &["a\tb", "aa\tb"] {
+            let glyphs = layout_glyphs(layout, text, (INFINITY, INFINITY));
+            let first = glyphs.first().unwrap().position().x;
+            let tab = &glyphs[glyphs.len() - 2];
+            let tab_end = tab.position().x + tab.unpositioned().h_metrics().advance_width;
+            let last = glyphs.last().unwrap().position().x;
+            assert!((last - first - 100.).abs() < 1e-3);
+            assert!((tab_end - last).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn unkerned_glyphs_follow_their_advances() {
+        let layout = UiLayout {
+            kerning: false,
+            ..left_top_layout(false)
+        };
+        let glyphs = layout_glyphs(layout, "AVAWTo", (INFINITY, INFINITY));
+        for pair in glyphs.windows(2) {
+            let end = pair[0].position().x + pair[0].unpositioned().h_metrics().advance_width;
+            assert!((pair[1].position().x - end).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn justified_lines_reach_the_right_edge() {
+        let text = "aa bb cc dd";
+        let right = |glyph: &PositionedGlyph| {
+            glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+        };
+        // Wrap before "dd" with room to spare on the first line.
+        let line = layout_glyphs(left_top_layout(false), text, (INFINITY, INFINITY));
+        let width = right(&line[8]) + (right(&line[9]) - right(&line[8])) / 2.;
+        let normal = layout_glyphs(left_top_layout(true), text, (width, INFINITY));
+        let layout = UiLayout {
+            align_h: HorizontalAlignment::Justify,
+            ..left_top_layout(true)
+        };
+        let justified = layout_glyphs(layout, text, (width, INFINITY));
+        assert!(normal[9].position().y > normal[0].position().y);
+        assert!(right(&normal[7]) < width);
+        assert_eq!(justified[0].position().x, normal[0].position().x);
+        assert!((right(&justified[7]) - width).abs() < 1e-3);
+        // The last line isn't justified.
+        assert_eq!(justified[9].position().x, normal[9].position().x);
+        assert_eq!(justified[10].position().x, normal[10].position().x);
+    }
+}
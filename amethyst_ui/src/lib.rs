@@ -14,6 +14,8 @@ extern crate fnv;
 extern crate gfx;
 extern crate gfx_glyph;
 extern crate hibitset;
+#[macro_use]
+extern crate log;
 extern crate rusttype;
 extern crate shrev;
 extern crate specs;
@@ -21,23 +23,35 @@ extern crate unicode_normalization;
 extern crate unicode_segmentation;
 extern crate winit;
 
+mod blend;
 mod bundle;
+mod clip;
 mod focused;
 mod format;
 mod image;
+mod layout;
+mod measure;
+mod opacity;
 mod pass;
 mod resize;
+mod sdf;
 mod text;
 mod transform;
 
+pub use self::blend::UiAdditiveBlend;
 pub use self::bundle::UiBundle;
+pub use self::clip::UiClip;
 pub use self::focused::UiFocused;
 pub use self::format::{FontAsset, FontHandle, OtfFormat, TtfFormat};
-pub use self::image::UiImage;
+pub use self::image::{NineSlice, UiImage};
+pub use self::measure::measure_text;
+pub use self::opacity::UiOpacity;
 pub use self::pass::DrawUi;
 pub use self::resize::{ResizeSystem, UiResize};
-pub use self::text::{TextEditing, UiSystem, UiText};
-pub use self::transform::UiTransform;
+pub use self::text::{HorizontalAlignment, OutlineQuality, OutlineStyle, Overflow, TextDecoration,
+                     TextDirection, TextEditing, TextSpan, UiSystem, UiText, VerticalAlignment};
+pub use self::transform::{Anchor, ScaleMode, UiTransform};
 
-/// How many times the cursor blinks per second while editing text.
+/// How many times the cursor blinks per second while editing text, unless the `TextEditing`
+/// sets its own rate.
 const CURSOR_BLINK_RATE: f32 = 2.0;
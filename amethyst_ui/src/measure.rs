@@ -0,0 +1,99 @@
+use gfx_glyph::{BuiltInLineBreaker, FontId, GlyphPositioner, HorizontalAlign, Layout,
+                SectionText, VariedSection, VerticalAlign};
+use rusttype::Font;
+
+use super::*;
+use layout::UiLayout;
+use pass::{glyph_area, glyph_scale, push_normalized};
+
+/// Measures the width and height `text` takes up when drawn with `font` at `font_size`.
+///
+/// The text is wrapped to fit within `bounds` the same way `DrawUi` lays out a left aligned
+/// `UiText` with `Overflow::Wrap`, so this doesn't need anything to be drawn first.  Empty text
+/// measures `(0.0, 0.0)`.
+pub fn measure_text(
+    text: &str,
+    font: &FontAsset,
+    font_size: f32,
+    bounds: (f32, f32),
+) -> (f32, f32) {
+    // Control characters are replaced like in the text `DrawUi` renders for a `UiText`.
+    let mut string = String::new();
+    push_normalized(&mut string, text, true, false);
+    let ui_layout = UiLayout {
+        layout: Layout::Wrap {
+            line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Top,
+        },
+        align_h: HorizontalAlignment::Left,
+        align_v: VerticalAlignment::Top,
+        direction: TextDirection::LeftToRight,
+        line_spacing: 1.0,
+        kerning: true,
+        letter_spacing: 0.0,
+        tab_width: 0.0,
+        pixel_perfect: false,
+        visible: None,
+    };
+    let scale = glyph_scale(font_size, 1.);
+    let section = VariedSection {
+        screen_position: (0., 0.),
+        bounds,
+        z: 0.,
+        layout: ui_layout.layout,
+        text: vec![
+            SectionText {
+                text: &string,
+                scale,
+                color: [0.; 4],
+                font_id: FontId(0),
+            },
+        ],
+    };
+    let glyphs = ui_layout.calculate_glyphs(&font_map(&font.0), &section);
+    let ascent = font.0.v_metrics(scale).ascent;
+    match glyph_area(glyphs.iter().map(|glyph| (&glyph.0, ascent))) {
+        Some(area) => (area.right - area.left, area.bottom - area.top),
+        None => (0., 0.),
+    }
+}
+
+/// Builds the font map the glyph brush layouts expect from a single font.
+fn font_map<M>(font: &Font<'static>) -> M
+where
+    M: Default + Extend<(FontId, Font<'static>)>,
+{
+    let mut map = M::default();
+    map.extend(Some((FontId(0), font.clone())));
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use rusttype::FontCollection;
+    use std::f32::INFINITY;
+
+    use super::*;
+
+    #[test]
+    fn control_characters_measure_like_drawn_text() {
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let font = FontAsset(font);
+        let measure = |text| measure_text(text, &font, 20., (INFINITY, INFINITY));
+        assert_eq!(measure(""), (0., 0.));
+        // Tabs and other control characters are drawn as spaces.
+        let spaced = measure("a b");
+        assert!(spaced.0 > 0. && spaced.1 > 0.);
+        assert_eq!(measure("a\tb"), spaced);
+        assert_eq!(measure("a\u{7}b"), spaced);
+        // Line feeds start a new line.
+        let lines = measure("a\nb");
+        assert!(lines.1 > spaced.1);
+    }
+}
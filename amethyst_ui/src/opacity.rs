@@ -0,0 +1,19 @@
+use specs::{Component, DenseVecStorage};
+
+/// A component that fades this entity, and every entity nested under it with a `Parent`.
+///
+/// The opacity multiplies into the alpha of the `UiImage` and `UiText` of the entity, and of
+/// all its descendants.  Nested opacities multiply, so a `0.5` under a `0.5` is drawn at
+/// `0.25`.  Entities with an effective opacity of `0` aren't drawn at all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiOpacity(pub f32);
+
+impl Default for UiOpacity {
+    fn default() -> Self {
+        UiOpacity(1.0)
+    }
+}
+
+impl Component for UiOpacity {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -1,44 +1,125 @@
 //! Simple flat forward drawing pass.
 
 use std::cmp::{Ordering, PartialOrd};
+use std::collections::HashMap as StdHashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
 use amethyst_assets::{AssetStorage, Loader, WeakHandle};
-use amethyst_renderer::{Encoder, Factory, Mesh, MeshHandle, PosTex, Resources, ScreenDimensions,
-                        Texture, TextureData, TextureHandle, TextureMetadata, VertexFormat};
+use amethyst_core::transform::Parent;
+use amethyst_renderer::{ColorFormat, Encoder, Factory, Mesh, MeshHandle, PosTex, Resources,
+                        ScreenDimensions, Texture, TextureData, TextureHandle, TextureMetadata,
+                        VertexFormat};
 use amethyst_renderer::error::Result;
-use amethyst_renderer::pipe::{Effect, NewEffect};
+use amethyst_renderer::pipe::{ColorBuffer, DepthBuffer, Effect, NewEffect};
 use amethyst_renderer::pipe::pass::{Pass, PassData};
 use cgmath::vec4;
 use fnv::FnvHashMap as HashMap;
+use gfx::format::{ChannelType, Formatted};
 use gfx::preset::blend;
 use gfx::pso::buffer::ElemStride;
+use gfx::{Rect, Slice};
 use gfx::state::ColorMask;
-use gfx_glyph::{BuiltInLineBreaker, FontId, GlyphBrush, GlyphBrushBuilder, HorizontalAlign,
-                Layout, Scale, SectionText, VariedSection, VerticalAlign};
+use gfx::traits::Pod;
+use gfx_glyph::{BuiltInLineBreaker, FontId, GlyphBrush, GlyphBrushBuilder, GlyphPositioner,
+                HorizontalAlign, Layout, Scale, SectionText, VariedSection, VerticalAlign};
 use hibitset::BitSet;
-use rusttype::Point;
+use rusttype::{point, Font, Point, PositionedGlyph};
 use specs::{Entities, Entity, Fetch, Join, ReadStorage, WriteStorage};
 use unicode_segmentation::UnicodeSegmentation;
 
 use super::*;
+use layout::UiLayout;
+use sdf::{SdfAtlas, SDF_SIZE};
 
 const VERT_SRC: &[u8] = include_bytes!("shaders/vertex.glsl");
 const FRAG_SRC: &[u8] = include_bytes!("shaders/frag.glsl");
+const FRAG_PREMULTIPLIED_SRC: &[u8] = include_bytes!("shaders/frag_premultiplied.glsl");
+const FRAG_SDF_SRC: &[u8] = include_bytes!("shaders/frag_sdf.glsl");
+const FRAG_SDF_PREMULTIPLIED_SRC: &[u8] = include_bytes!("shaders/frag_sdf_premultiplied.glsl");
+
+/// The maximum number of quads drawn with a single draw call, this has to match the size of the
+/// `Quads` array in the vertex shader.
+const MAX_QUADS: usize = 128;
 
 #[derive(Copy, Clone, Debug)]
 #[allow(dead_code)] // This is used by the shaders
 #[repr(C)]
 struct VertexArgs {
     proj_vec: [f32; 4],
+}
+
+/// The number of glyph brushes `DrawUi` keeps by default.
+const MAX_GLYPH_BRUSHES: usize = 32;
+
+/// How many seconds identical text drawing errors are coalesced for before they're logged again.
+const ERROR_LOG_INTERVAL: u64 = 5;
+
+/// The least distance in logical pixels kept between the caret of scrolled text and the edges of
+/// its `UiTransform`.
+const SCROLL_MARGIN: f32 = 4.0;
+
+/// The blend variant of the effect used for `UiAdditiveBlend` entities.
+const ADDITIVE_BLEND: usize = 0;
+
+/// The texture coordinates covering a whole texture.
+const FULL_TEX_RECT: [f32; 4] = [0., 0., 1., 1.];
+
+/// A tint that leaves the texture unchanged.
+const NO_TINT: [f32; 4] = [1., 1., 1., 1.];
+
+/// The position and size of a single quad, an array of these is indexed by instance id in the
+/// vertex shader.
+#[derive(Copy, Clone, Debug)]
+#[allow(dead_code)] // This is used by the shaders
+#[repr(C)]
+struct QuadArgs {
     coord: [f32; 2],
     dimension: [f32; 2],
+    /// The texture coordinates mapped onto the quad, as `[u_min, v_min, u_max, v_max]`.
+    tex_rect: [f32; 4],
+    /// The color the texture is multiplied with.
+    color: [f32; 4],
+    /// Whether the texture is mirrored horizontally and vertically, `1.0` if it is.
+    flip: [f32; 2],
+    /// The point the quad is rotated around, in pixels.
+    pivot: [f32; 2],
+    /// The clockwise rotation of the quad in radians.
+    rotation: f32,
+    /// Whether the alpha of the texture is a signed distance field rather than coverage, `1.0`
+    /// if it is.  Only the shaders used with `DrawUi::with_sdf_text` read this.
+    distance_field: f32,
+    // std140 pads the size of array elements to a multiple of 16 bytes.
+    _padding: [f32; 2],
+}
+
+unsafe impl Pod for QuadArgs {}
+
+impl QuadArgs {
+    /// A quad showing the whole texture unchanged.
+    fn new(coord: [f32; 2], dimension: [f32; 2]) -> Self {
+        QuadArgs {
+            coord,
+            dimension,
+            tex_rect: FULL_TEX_RECT,
+            color: NO_TINT,
+            flip: [0.; 2],
+            pivot: [0.; 2],
+            rotation: 0.,
+            distance_field: 0.,
+            _padding: [0.; 2],
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 struct CachedDrawOrder {
     pub cached: BitSet,
     pub cache: Vec<(f32, Entity)>,
+    /// Whether `cache` may be out of order and needs to be sorted.
+    pub dirty: bool,
 }
 
 /// A color used to query a hashmap for a cached texture of that color.
@@ -59,21 +140,63 @@ impl Hash for KeyColor {
     }
 }
 
+/// Gradient text rasterized into a texture, together with where it's drawn.
+struct GradientText {
+    /// A hash of everything the pixels were rasterized from, see `gradient_key`.
+    key: u64,
+    texture: TextureHandle,
+    coord: [f32; 2],
+    dimension: [f32; 2],
+}
+
+/// The last error the glyph brush returned while drawing text.
+struct TextDrawError {
+    message: String,
+    /// How many times the error occurred again since it was last logged.
+    repeats: u32,
+    logged_at: Instant,
+}
+
 /// Draw Ui elements.  UI won't display without this.  It's recommended this be your last pass.
+///
+/// Text is drawn by `gfx_glyph` by default, which rasterizes glyphs at the exact size they're
+/// drawn at.  See `with_sdf_text` for drawing them from signed distance fields instead.
+///
+/// Images with the same texture and clip that follow each other in the draw order are drawn with
+/// a single draw call.  Text drawn between them ends the batch, so images interleaved with text,
+/// such as icons with count labels, take a draw call each.  Giving the text a higher z than all
+/// of the images draws it after them, which lets the images be batched.
 pub struct DrawUi {
     mesh_handle: MeshHandle,
     cached_draw_order: CachedDrawOrder,
     cached_color_textures: HashMap<KeyColor, TextureHandle>,
+    /// The textures of rasterized text, the newest last.  A new texture is loaded whenever the
+    /// text of an entity changes, older textures are kept until the newest one is loaded so the
+    /// text doesn't flicker while it changes.
+    gradient_texts: HashMap<Entity, Vec<GradientText>>,
     glyph_brushes: GlyphBrushCache,
     next_brush_cache_id: u32,
+    max_glyph_brushes: usize,
+    /// The number of times the pass has been applied, used to find the least recently used
+    /// glyph brush.
+    frame: u64,
+    text_error: Option<TextDrawError>,
+    premultiplied_alpha: bool,
+    sdf_text: bool,
+    /// The distance fields of the glyphs drawn with `sdf_text`.
+    sdf_atlas: SdfAtlas,
+    /// The buffers drawn to instead of the target of the stage, if they're set.
+    target: Option<(ColorBuffer, DepthBuffer)>,
 }
 
+/// Glyph brushes by id, along with their fonts and the last frame they were used in.
 type GlyphBrushCache =
     HashMap<
         u32,
         (
             GlyphBrush<'static, Resources, Factory>,
-            WeakHandle<FontAsset>,
+            Vec<WeakHandle<FontAsset>>,
+            u64,
         ),
     >;
 
@@ -113,12 +236,75 @@ impl DrawUi {
             cached_draw_order: CachedDrawOrder {
                 cached: BitSet::new(),
                 cache: Vec::new(),
+                dirty: false,
             },
             cached_color_textures: HashMap::default(),
+            gradient_texts: HashMap::default(),
             glyph_brushes: HashMap::default(),
             next_brush_cache_id: 0,
+            max_glyph_brushes: MAX_GLYPH_BRUSHES,
+            frame: 0,
+            text_error: None,
+            premultiplied_alpha: false,
+            sdf_text: false,
+            sdf_atlas: SdfAtlas::new(),
+            target: None,
         }
     }
+
+    /// Sets the maximum number of glyph brushes kept at once, each of which has its own glyph
+    /// cache texture.  There is a brush for every combination of fonts used by a `UiText`.
+    ///
+    /// When a new brush would exceed the limit the least recently used brush is dropped.  Brushes
+    /// used in the current frame are never dropped, so the limit may be exceeded temporarily if
+    /// a single frame uses more combinations of fonts than this.
+    pub fn with_max_glyph_brushes(mut self, max: usize) -> Self {
+        self.max_glyph_brushes = max;
+        self
+    }
+
+    /// Sets whether the pass outputs colors with premultiplied alpha and blends them as such,
+    /// which avoids dark fringes around translucent edges when the UI is composited over
+    /// something else.  Off by default.
+    ///
+    /// Textures and colors given to UI components are still expected to use straight alpha.
+    /// `gfx_glyph` always draws with straight alpha, so in this mode text is rasterized into a
+    /// texture along with its shadow and outline, like text with a `color_gradient`.  Every
+    /// change to a text loads a new texture asset for its entity, so text that changes every
+    /// frame is slower to draw than with `gfx_glyph`.
+    pub fn with_premultiplied_alpha(mut self, premultiplied: bool) -> Self {
+        self.premultiplied_alpha = premultiplied;
+        self
+    }
+
+    /// Sets whether text is drawn from signed distance fields, which stay sharp across a wide
+    /// range of sizes, such as when the UI is zoomed.  Off by default.
+    ///
+    /// The distance field of every glyph is computed once at a fixed size and kept in a single
+    /// atlas texture, so glyphs appear a frame after they're first drawn.  Text with a
+    /// `color_gradient` is still rasterized at the size it's drawn at.
+    pub fn with_sdf_text(mut self, sdf: bool) -> Self {
+        self.sdf_text = sdf;
+        self
+    }
+
+    /// Draws the UI to `color` and `depth` instead of the target of the stage, such as to
+    /// sample the UI as a texture in a later pass.  The buffers are cleared to transparent
+    /// black every frame before the UI is drawn.
+    ///
+    /// The UI is laid out in screen pixels, so the buffers should have the size of the screen.
+    pub fn with_target(mut self, color: ColorBuffer, depth: DepthBuffer) -> Self {
+        self.target = Some((color, depth));
+        self
+    }
+
+    /// The last error that occurred while drawing text, if there was one.
+    ///
+    /// Errors are logged as warnings when they occur, repeats of the same error are only logged
+    /// every few seconds.
+    pub fn last_text_error(&self) -> Option<&str> {
+        self.text_error.as_ref().map(|error| error.message.as_str())
+    }
 }
 
 impl<'a> PassData<'a> for DrawUi {
@@ -131,21 +317,34 @@ impl<'a> PassData<'a> for DrawUi {
         Fetch<'a, AssetStorage<FontAsset>>,
         Fetch<'a, UiFocused>,
         ReadStorage<'a, UiImage>,
-        ReadStorage<'a, UiTransform>,
+        WriteStorage<'a, UiTransform>,
         WriteStorage<'a, UiText>,
-        ReadStorage<'a, TextEditing>,
+        WriteStorage<'a, TextEditing>,
+        ReadStorage<'a, UiClip>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, UiAdditiveBlend>,
+        ReadStorage<'a, UiOpacity>,
     );
 }
 
 impl Pass for DrawUi {
     fn compile(&self, effect: NewEffect) -> Result<Effect> {
         use std::mem;
+        let (frag_src, alpha_blend) = match (self.premultiplied_alpha, self.sdf_text) {
+            (false, false) => (FRAG_SRC, blend::ALPHA),
+            (true, false) => (FRAG_PREMULTIPLIED_SRC, blend::PREMULTIPLIED),
+            (false, true) => (FRAG_SDF_SRC, blend::ALPHA),
+            (true, true) => (FRAG_SDF_PREMULTIPLIED_SRC, blend::PREMULTIPLIED),
+        };
         effect
-            .simple(VERT_SRC, FRAG_SRC)
+            .simple(VERT_SRC, frag_src)
             .with_raw_constant_buffer("VertexArgs", mem::size_of::<VertexArgs>(), 1)
+            .with_raw_constant_buffer("Quads", mem::size_of::<QuadArgs>(), MAX_QUADS)
             .with_raw_vertex_buffer(PosTex::ATTRIBUTES, PosTex::size() as ElemStride, 0)
             .with_texture("albedo")
-            .with_blended_output("color", ColorMask::all(), blend::ALPHA, None)
+            .with_blended_output("color", ColorMask::all(), alpha_blend, None)
+            .with_blend_variant(blend::ADD)
+            .with_scissor()
             .build()
     }
 
@@ -163,9 +362,13 @@ impl Pass for DrawUi {
             font_storage,
             focused,
             ui_image,
-            ui_transform,
+            mut ui_transform,
             mut ui_text,
-            editing,
+            mut editing,
+            ui_clip,
+            parent,
+            additive,
+            ui_opacity,
         ): (
             Entities<'a>,
             Fetch<'a, Loader>,
@@ -175,26 +378,48 @@ impl Pass for DrawUi {
             Fetch<'a, AssetStorage<FontAsset>>,
             Fetch<'a, UiFocused>,
             ReadStorage<'a, UiImage>,
-            ReadStorage<'a, UiTransform>,
+            WriteStorage<'a, UiTransform>,
             WriteStorage<'a, UiText>,
-            ReadStorage<'a, TextEditing>,
+            WriteStorage<'a, TextEditing>,
+            ReadStorage<'a, UiClip>,
+            ReadStorage<'a, Parent>,
+            ReadStorage<'a, UiAdditiveBlend>,
+            ReadStorage<'a, UiOpacity>,
         ),
     ) {
+        use std::f32::{INFINITY, NEG_INFINITY};
+
+        // Point the effect and the glyph brushes at the alternate target.  This is redone every
+        // frame as the outputs are reset whenever the stage's target changes.
+        if let Some((ref color, ref depth)) = self.target {
+            effect.data.out_blends.clear();
+            effect.data.out_blends.push(color.as_output.clone());
+            effect.data.out_depth = Some((depth.as_output.clone(), (0, 0)));
+            encoder.clear(&color.as_output, [0., 0., 0., 0.]);
+            encoder.clear_depth(&depth.as_output, 1.);
+            encoder.clear_stencil(&depth.as_output, 0);
+        }
+
         // Populate and update the draw order cache.
         {
             let bitset = &mut self.cached_draw_order.cached;
+            let dirty = &mut self.cached_draw_order.dirty;
             self.cached_draw_order.cache.retain(|&(_z, entity)| {
                 let keep = ui_transform.get(entity).is_some();
                 if !keep {
                     bitset.remove(entity.id());
+                    *dirty = true;
                 }
                 keep
             });
         }
 
-
         for &mut (ref mut z, entity) in &mut self.cached_draw_order.cache {
-            *z = ui_transform.get(entity).unwrap().z;
+            let new_z = ui_transform.get(entity).unwrap().z;
+            if *z != new_z {
+                *z = new_z;
+                self.cached_draw_order.dirty = true;
+            }
         }
 
         // Attempt to insert the new entities in sorted position.  Should reduce work during
@@ -214,18 +439,21 @@ impl Pass for DrawUi {
                         .insert(pos, (transform.z, entity)),
                     None => self.cached_draw_order.cache.push((transform.z, entity)),
                 }
+                self.cached_draw_order.dirty = true;
             }
         }
         self.cached_draw_order.cached = transform_set;
 
         // Sort from largest z value to smallest z value.
-        // Most of the time this shouldn't do anything but you still need it for if the z values
-        // change.
-        self.cached_draw_order
-            .cache
-            .sort_unstable_by(|&(z1, _), &(z2, _)| {
-                z2.partial_cmp(&z1).unwrap_or(Ordering::Equal)
-            });
+        // This is skipped unless entities were added or removed or their z values changed.
+        if self.cached_draw_order.dirty {
+            self.cached_draw_order
+                .cache
+                .sort_unstable_by(|&(z1, _), &(z2, _)| {
+                    z2.partial_cmp(&z1).unwrap_or(Ordering::Equal)
+                });
+            self.cached_draw_order.dirty = false;
+        }
 
         let proj_vec = vec4(
             2. / screen_dimensions.width(),
@@ -234,6 +462,15 @@ impl Pass for DrawUi {
             1.,
         );
 
+        // Resolve the screen positions and sizes of transforms, in logical pixels.
+        let screen_size = {
+            let hidpi = screen_dimensions.hidpi_factor();
+            (screen_dimensions.width() / hidpi, screen_dimensions.height() / hidpi)
+        };
+        for transform in (&mut ui_transform).join() {
+            transform.resolve(screen_size.0, screen_size.1);
+        }
+
         let mesh = match mesh_storage.get(&self.mesh_handle) {
             Some(mesh) => mesh,
             None => return,
@@ -245,151 +482,353 @@ impl Pass for DrawUi {
         };
         effect.data.vertex_bufs.push(vbuf);
 
+        // Remove gradient textures that are no longer drawn.  With premultiplied alpha all text
+        // is rasterized into these textures.
+        let premultiplied = self.premultiplied_alpha && !self.sdf_text;
+        self.gradient_texts.retain(|&entity, _| {
+            entities.is_alive(entity)
+                && ui_text
+                    .get(entity)
+                    .map_or(false, |text| premultiplied || text.color_gradient.is_some())
+        });
+
+        self.frame += 1;
+
         // Remove brushes whose fonts have been dropped.
         self.glyph_brushes
-            .retain(|&_id, ref mut value| !value.1.is_dead());
+            .retain(|&_id, ref mut value| !value.1.iter().any(|font| font.is_dead()));
+        self.sdf_atlas.remove_dead_fonts();
+        let sdf_texture = self.sdf_atlas.texture(&tex_storage);
 
-        for &(_z, entity) in &self.cached_draw_order.cache {
+        let vertex_args = VertexArgs {
+            proj_vec: proj_vec.into(),
+        };
+        effect.update_constant_buffer("VertexArgs", &vertex_args, encoder);
+
+        // Transforms and text sizes are in logical pixels, everything is drawn in pixels.
+        let hidpi = screen_dimensions.hidpi_factor();
+        let full_screen = Rect {
+            x: 0,
+            y: 0,
+            w: screen_dimensions.width() as u16,
+            h: screen_dimensions.height() as u16,
+        };
+        // The clip of every entity drawn this frame, resolved through its parents.
+        let mut clip_cache = HashMap::default();
+        // The opacity of every entity drawn this frame, multiplied through its parents.
+        let mut opacity_cache = HashMap::default();
+        // Auto sized text transforms are resized once everything is drawn.
+        let mut resized = Vec::new();
+        // Likewise, the scroll offsets of edited text are updated at the end.
+        let mut scrolled = Vec::new();
+
+        // Images that share a texture and follow each other in the draw order are batched into
+        // a single draw call.  Only consecutive images are batched so overlapping elements are
+        // still drawn back to front, text between images ends the batch.
+        let mut image_batch: Option<((TextureHandle, Rect), Vec<QuadArgs>)> = None;
+        // Additive entities are drawn after all others so the blending only changes once.
+        let (additive_order, alpha_order): (Vec<_>, Vec<_>) = self.cached_draw_order
+            .cache
+            .iter()
+            .map(|&(_z, entity)| entity)
+            .partition(|&entity| additive.get(entity).is_some());
+        let draw_order = alpha_order
+            .into_iter()
+            .map(|entity| (entity, false))
+            .chain(additive_order.into_iter().map(|entity| (entity, true)));
+        let mut drawing_additive = false;
+        for (entity, is_additive) in draw_order {
+            if is_additive && !drawing_additive {
+                draw_image_batch(image_batch.take(), &tex_storage, effect, encoder, mesh.slice());
+                effect.use_blend_variant(Some(ADDITIVE_BLEND));
+                drawing_additive = true;
+            }
+            let opacity = resolve_opacity(entity, &ui_opacity, &parent, &mut opacity_cache);
+            if opacity <= 0. {
+                continue;
+            }
             // This won't panic as we guaranteed earlier these entities are present.
+            let clip = resolve_clip(entity, &ui_transform, &ui_clip, &parent, &mut clip_cache);
+            let scissor = clip.map_or(full_screen, |clip| {
+                clip.scaled(hidpi).scissor(screen_dimensions.height())
+            });
             let ui_transform = ui_transform.get(entity).unwrap();
-            let vertex_args = VertexArgs {
-                proj_vec: proj_vec.into(),
-                coord: [ui_transform.x, ui_transform.y],
-                dimension: [ui_transform.width, ui_transform.height],
-            };
-            effect.update_constant_buffer("VertexArgs", &vertex_args, encoder);
-            if let Some(image) = ui_image
-                .get(entity)
-                .and_then(|image| tex_storage.get(&image.texture))
-            {
-                effect.data.textures.push(image.view().clone());
-                effect.data.samplers.push(image.sampler().clone());
-                effect.draw(mesh.slice(), encoder);
-                effect.data.textures.clear();
-                effect.data.samplers.clear();
+            if let Some(image) = ui_image.get(entity) {
+                let key = (image.texture.clone(), scissor);
+                let (finished, quads) = batch_quads(&mut image_batch, key);
+                draw_image_batch(finished, &tex_storage, effect, encoder, mesh.slice());
+                let quad = QuadArgs {
+                    tex_rect: image.tex_coords,
+                    color: fade(image.color, opacity),
+                    flip: [image.flip_x as u32 as f32, image.flip_y as u32 as f32],
+                    pivot: [
+                        (ui_transform.pixel_x + ui_transform.pixel_width / 2.) * hidpi,
+                        (ui_transform.pixel_y + ui_transform.pixel_height / 2.) * hidpi,
+                    ],
+                    rotation: ui_transform.rotation,
+                    ..QuadArgs::new(
+                        [ui_transform.pixel_x * hidpi, ui_transform.pixel_y * hidpi],
+                        [ui_transform.pixel_width * hidpi, ui_transform.pixel_height * hidpi],
+                    )
+                };
+                match image.nine_slice {
+                    Some(nine_slice) => if let Some(texture) = tex_storage.get(&image.texture) {
+                        nine_slice_quads(quad, nine_slice, texture.size(), hidpi, quads);
+                    },
+                    None => quads.push(quad),
+                }
             }
 
             if let Some(ui_text) = ui_text.get_mut(entity) {
+                // Text has to be drawn on top of every image before it.
+                draw_image_batch(image_batch.take(), &tex_storage, effect, encoder, mesh.slice());
+                // Highlights and the cursor are clipped along with the text.
+                effect.data.scissor = Some(scissor);
                 // Maintain glyph brushes.
-                if ui_text.brush_id.is_none() || ui_text.font != ui_text.cached_font {
-                    let font = match font_storage.get(&ui_text.font) {
-                        Some(font) => font,
-                        None => continue,
-                    };
+                // The index of a font in this list is its `FontId` in the glyph brush.
+                let mut fonts = vec![ui_text.font.clone()];
+                for span in &ui_text.spans {
+                    if !fonts.contains(&span.font) {
+                        fonts.push(span.font.clone());
+                    }
+                }
+                // The brush may have been dropped to make room for others.
+                let brush_dropped = ui_text
+                    .brush_id
+                    .map_or(true, |id| !self.glyph_brushes.contains_key(&id));
+                if brush_dropped || fonts != ui_text.cached_fonts {
                     let mut new_id = self.glyph_brushes
                         .iter()
-                        .filter_map(|(id, ref value)| value.1.upgrade().map(|h| (id, h)))
-                        .find(|&(_id, ref handle)| *handle == ui_text.font)
-                        .map(|(id, _handle)| *id);
+                        .find(|&(_id, ref value)| {
+                            value.1.len() == fonts.len()
+                                && value.1.iter().zip(&fonts).all(|(weak, font)| {
+                                    weak.upgrade().map_or(false, |handle| handle == *font)
+                                })
+                        })
+                        .map(|(id, _value)| *id);
 
                     if new_id.is_none() {
+                        let font_assets = fonts
+                            .iter()
+                            .map(|font| font_storage.get(font).map(|font| font.0.clone()))
+                            .collect::<Option<Vec<_>>>();
+                        let mut font_assets = match font_assets {
+                            Some(font_assets) => font_assets.into_iter(),
+                            None => continue,
+                        };
+                        let mut builder =
+                            GlyphBrushBuilder::using_font(font_assets.next().unwrap());
+                        for font in font_assets {
+                            builder.add_font(font);
+                        }
+                        if self.glyph_brushes.len() >= self.max_glyph_brushes {
+                            evict_glyph_brush(&mut self.glyph_brushes, self.frame);
+                        }
                         new_id = Some(self.next_brush_cache_id);
                         self.glyph_brushes.insert(
                             self.next_brush_cache_id,
                             (
-                                GlyphBrushBuilder::using_font(font.0.clone())
-                                    .build(factory.clone()),
-                                ui_text.font.downgrade(),
+                                builder.build(factory.clone()),
+                                fonts.iter().map(|font| font.downgrade()).collect(),
+                                self.frame,
                             ),
                         );
                         self.next_brush_cache_id += 1;
                     }
                     ui_text.brush_id = new_id;
-                    ui_text.cached_font = ui_text.font.clone();
+                    ui_text.cached_fonts = fonts.clone();
                 }
                 // Build text sections.
-                let editing = editing.get(entity);
-                let password_string = if ui_text.password {
-                    // Build a string composed of black dot characters.
-                    let mut ret = String::with_capacity(ui_text.text.len());
-                    for _grapheme in ui_text.text.graphemes(true) {
-                        ret.push('\u{2022}');
-                    }
-                    Some(ret)
-                } else {
+                let (mut rendered_string, mut runs) = text_runs(ui_text, &fonts, hidpi);
+                let hidden = ui_text.visible_chars.map_or(false, |visible| {
+                    hide_graphemes(&mut rendered_string, &mut runs, visible)
+                });
+                // The cursor and selection aren't drawn while part of the text is hidden.
+                let editing = editing.get(entity).and_then(|editing| if hidden {
                     None
+                } else {
+                    Some(editing)
+                });
+                let letter_spacing = ui_text.letter_spacing * hidpi;
+                for run in &mut runs {
+                    run.color = fade(run.color, opacity);
+                }
+                let brush = {
+                    let cached = self.glyph_brushes
+                        .get_mut(&ui_text.brush_id.unwrap())
+                        .unwrap();
+                    cached.2 = self.frame;
+                    &mut cached.0
                 };
-                let rendered_string = password_string.as_ref().unwrap_or(&ui_text.text);
-                let text = editing
-                    .and_then(|editing| {
-                        if editing.highlight_vector == 0 {
-                            return None;
-                        }
-                        let start = editing
-                            .cursor_position
-                            .min(editing.cursor_position + editing.highlight_vector)
-                            as usize;
-                        let end = editing
-                            .cursor_position
-                            .max(editing.cursor_position + editing.highlight_vector)
-                            as usize;
-                        let start_byte = rendered_string
-                            .grapheme_indices(true)
-                            .nth(start)
-                            .map(|i| i.0);
-                        let end_byte = rendered_string
-                            .grapheme_indices(true)
-                            .nth(end)
-                            .map(|i| i.0)
-                            .unwrap_or(rendered_string.len());
-                        start_byte.map(|start_byte| (editing, (start_byte, end_byte)))
-                    })
-                    .map(|(editing, (start_byte, end_byte))| {
-                        vec![
-                            SectionText {
-                                text: &((rendered_string)[0..start_byte]),
-                                scale: Scale::uniform(ui_text.font_size),
-                                color: ui_text.color,
-                                font_id: FontId(0),
-                            },
-                            SectionText {
-                                text: &((rendered_string)[start_byte..end_byte]),
-                                scale: Scale::uniform(ui_text.font_size),
-                                color: editing.selected_text_color,
-                                font_id: FontId(0),
-                            },
-                            SectionText {
-                                text: &((rendered_string)[end_byte..]),
-                                scale: Scale::uniform(ui_text.font_size),
-                                color: ui_text.color,
-                                font_id: FontId(0),
-                            },
-                        ]
+                let ui_layout = UiLayout::new(ui_text, hidpi);
+                if ui_text.overflow == Overflow::Truncate && ui_text.auto_size.is_none() {
+                    truncate_text(
+                        brush,
+                        &mut rendered_string,
+                        &mut runs,
+                        ui_transform.pixel_width * hidpi,
+                        ui_layout,
+                    );
+                }
+                let selection = editing.and_then(|editing| {
+                    if editing.highlight_vector == 0 {
+                        return None;
+                    }
+                    let start = editing
+                        .cursor_position
+                        .min(editing.cursor_position + editing.highlight_vector)
+                        as usize;
+                    let end = editing
+                        .cursor_position
+                        .max(editing.cursor_position + editing.highlight_vector)
+                        as usize;
+                    let start_byte = rendered_string
+                        .grapheme_indices(true)
+                        .nth(start)
+                        .map(|i| i.0);
+                    let end_byte = rendered_string
+                        .grapheme_indices(true)
+                        .nth(end)
+                        .map(|i| i.0)
+                        .unwrap_or(rendered_string.len());
+                    start_byte.map(|start_byte| {
+                        (start_byte..end_byte, fade(editing.selected_text_color, opacity))
                     })
-                    .unwrap_or(vec![
-                        SectionText {
-                            text: rendered_string,
-                            scale: Scale::uniform(ui_text.font_size),
-                            color: ui_text.color,
-                            font_id: FontId(0),
-                        },
-                    ]);
-                // TODO: If you're adding multi-line support you need to change this to use
-                // Layout::Wrap.
-                let layout = Layout::SingleLine {
-                    line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
-                    h_align: HorizontalAlign::Left,
-                    v_align: VerticalAlign::Top,
+                });
+                let (screen_position, bounds) = match ui_text.auto_size {
+                    Some(padding) => auto_size_area(ui_transform, ui_text, padding),
+                    None => (
+                        text_position(ui_transform, ui_text.align_h, ui_text.align_v),
+                        (ui_transform.pixel_width, ui_transform.pixel_height),
+                    ),
+                };
+                let screen_position = (screen_position.0 * hidpi, screen_position.1 * hidpi);
+                let bounds = (bounds.0 * hidpi, bounds.1 * hidpi);
+                let screen_position = if ui_text.pixel_perfect {
+                    (screen_position.0.round(), screen_position.1.round())
+                } else {
+                    screen_position
                 };
-                let section = VariedSection {
-                    screen_position: (ui_transform.x, ui_transform.y),
-                    bounds: (ui_transform.width, ui_transform.height),
+                let mut section = VariedSection {
+                    screen_position,
+                    bounds,
                     z: ui_transform.z,
-                    layout,
-                    text,
+                    layout: ui_layout.layout,
+                    text: section_texts(&rendered_string, &runs, selection.clone()),
                 };
 
-                // Render background highlight
-                let brush = &mut self.glyph_brushes
-                    .get_mut(&ui_text.brush_id.unwrap())
-                    .unwrap()
-                    .0;
                 // Maintain the glyph cache (used by the input code).
                 ui_text.cached_glyphs.clear();
-                ui_text
-                    .cached_glyphs
-                    .extend(brush.glyphs(&section).cloned());
+                ui_text.cached_glyphs.extend(
+                    ui_layout
+                        .calculate_glyphs(brush.fonts(), &section)
+                        .into_iter()
+                        .map(|glyph| glyph.0),
+                );
+                // The glyph brush can't be scissored, so glyphs that aren't entirely within the
+                // clip are hidden instead.
+                let mut draw_layout = ui_layout;
+                draw_layout.visible = clip.map(|clip| clip.scaled(hidpi));
+                // Single line text that's being edited scrolls horizontally to keep the caret
+                // within its transform, glyphs beyond its left and right edges are hidden too.
+                let scrolling = ui_text.overflow != Overflow::Wrap && ui_text.auto_size.is_none();
+                if let (true, Some(editing)) = (scrolling, editing) {
+                    let edges = (
+                        ui_transform.pixel_x * hidpi,
+                        (ui_transform.pixel_x + ui_transform.pixel_width) * hidpi,
+                    );
+                    let cursor = editing.cursor_position.max(0) as usize;
+                    let cursor_char = grapheme_glyphs(&rendered_string, cursor..cursor).start;
+                    let caret = caret_x(&ui_text.cached_glyphs, cursor_char, ui_text.direction);
+                    let offset = match (caret, glyph_edges(&ui_text.cached_glyphs)) {
+                        (Some(caret), Some(text)) => scroll_to_caret(
+                            editing.scroll_offset * hidpi,
+                            caret,
+                            text,
+                            edges,
+                            SCROLL_MARGIN * hidpi,
+                        ),
+                        _ => 0.,
+                    };
+                    let offset = if ui_text.pixel_perfect {
+                        offset.round()
+                    } else {
+                        offset
+                    };
+                    if offset != 0. {
+                        section.screen_position.0 -= offset;
+                        ui_text.cached_glyphs.clear();
+                        ui_text.cached_glyphs.extend(
+                            ui_layout
+                                .calculate_glyphs(brush.fonts(), &section)
+                                .into_iter()
+                                .map(|glyph| glyph.0),
+                        );
+                    }
+                    if offset / hidpi != editing.scroll_offset {
+                        scrolled.push((entity, offset / hidpi));
+                    }
+                    let own_clip = ClipRect::from(ui_transform);
+                    let text_clip = clip.map_or(own_clip, |clip| clip.intersect(&own_clip));
+                    let text_clip = text_clip.scaled(hidpi);
+                    let (top, bottom) = draw_layout
+                        .visible
+                        .map_or((NEG_INFINITY, INFINITY), |visible| (visible.top, visible.bottom));
+                    draw_layout.visible = Some(ClipRect {
+                        left: text_clip.left,
+                        top,
+                        right: text_clip.right,
+                        bottom,
+                    });
+                    effect.data.scissor = Some(text_clip.scissor(screen_dimensions.height()));
+                }
+                if let Some(padding) = ui_text.auto_size {
+                    let glyphs = ui_text
+                        .cached_glyphs
+                        .iter()
+                        .zip(rendered_string.char_indices())
+                        .map(|(glyph, (byte, _))| (glyph, run_ascent(brush, &runs, byte)));
+                    if let Some(area) = glyph_area(glyphs) {
+                        let width = if ui_text.overflow == Overflow::Wrap {
+                            ui_transform.pixel_width
+                        } else {
+                            (area.right - area.left) / hidpi + padding * 2.
+                        };
+                        let height = (area.bottom - area.top) / hidpi + padding * 2.;
+                        if width != ui_transform.pixel_width
+                            || height != ui_transform.pixel_height
+                        {
+                            resized.push((entity, width, height));
+                        }
+                    }
+                }
+                // Render highlights, beneath the selection.
                 let cache = &mut self.cached_color_textures;
+                for &(start, end, color) in &ui_text.highlights {
+                    let texture = tex_storage.get(&cached_color_texture(
+                        cache,
+                        fade(color, opacity),
+                        &loader,
+                        &tex_storage,
+                    ));
+                    if let Some(texture) = texture {
+                        effect.data.textures.push(texture.view().clone());
+                        effect.data.samplers.push(texture.sampler().clone());
+                        let quads = highlight_quads(
+                            brush,
+                            &ui_text.cached_glyphs,
+                            &rendered_string,
+                            &runs,
+                            grapheme_glyphs(&rendered_string, start..end),
+                            letter_spacing,
+                            ui_text.pixel_perfect,
+                        );
+                        draw_quads(effect, encoder, mesh.slice(), &quads);
+                        effect.data.textures.clear();
+                        effect.data.samplers.clear();
+                    }
+                }
+                // Render background highlight
                 if let Some((texture, (start, end))) = editing.and_then(|ed| {
                     let start = ed.cursor_position
                         .min(ed.cursor_position + ed.highlight_vector)
@@ -408,7 +847,7 @@ impl Pass for DrawUi {
                     tex_storage
                         .get(&cached_color_texture(
                             cache,
-                            color,
+                            fade(color, opacity),
                             &loader,
                             &tex_storage,
                         ))
@@ -416,40 +855,200 @@ impl Pass for DrawUi {
                 }) {
                     effect.data.textures.push(texture.view().clone());
                     effect.data.samplers.push(texture.sampler().clone());
-                    let ascent = brush
-                        .fonts()
-                        .get(&FontId(0))
-                        .unwrap()
-                        .v_metrics(Scale::uniform(ui_text.font_size))
-                        .ascent;
-                    for glyph in brush
-                        .glyphs(&section)
-                        .enumerate()
-                        .filter(|&(i, _g)| start <= i && i < end)
-                        .map(|(_i, g)| g)
-                    {
-                        let height = glyph.scale().y;
-                        let width = glyph.unpositioned().h_metrics().advance_width;
-                        let pos = glyph.position();
-                        let vertex_args = VertexArgs {
-                            proj_vec: proj_vec.into(),
-                            coord: [pos.x, pos.y - ascent],
-                            dimension: [width, height],
-                        };
-                        effect.update_constant_buffer("VertexArgs", &vertex_args, encoder);
-                        effect.draw(mesh.slice(), encoder);
-                    }
+                    let quads = highlight_quads(
+                        brush,
+                        &ui_text.cached_glyphs,
+                        &rendered_string,
+                        &runs,
+                        start..end,
+                        letter_spacing,
+                        ui_text.pixel_perfect,
+                    );
+                    draw_quads(effect, encoder, mesh.slice(), &quads);
                     effect.data.textures.clear();
                     effect.data.samplers.clear();
                 }
                 // Render text
-                brush.queue(section.clone());
-                if let Err(err) = brush.draw_queued(
-                    encoder,
-                    &effect.data.out_blends[0],
-                    &effect.data.out_depth.as_ref().unwrap().0,
-                ) {
-                    eprintln!("Unable to draw text! Error: {:?}", err);
+                // The glyph brush can't be scissored, so clipped text is skipped when it's
+                // entirely outside of its clip.
+                if clip.map_or(true, |clip| clip.overlaps(&ClipRect::from(ui_transform))) {
+                    // The shadow and outline are drawn beneath the text, offset from it.
+                    let layers = text_layers(ui_text.shadow, ui_text.outline, hidpi, opacity);
+                    let sdf = self.sdf_text && ui_text.color_gradient.is_none();
+                    // The glyph brush always blends with straight alpha, so with premultiplied
+                    // alpha or additive blending plain text is rasterized like gradient text and
+                    // drawn with the blending of the effect.
+                    let gradient = match ui_text.color_gradient {
+                        Some((top, bottom)) => Some((fade(top, opacity), fade(bottom, opacity))),
+                        None if premultiplied || is_additive && !sdf => {
+                            let color = fade(ui_text.color, opacity);
+                            Some((color, color))
+                        }
+                        None => None,
+                    };
+                    if sdf {
+                        let mut quads = Vec::new();
+                        {
+                            let glyphs = ui_text
+                                .cached_glyphs
+                                .iter()
+                                .zip(rendered_string.char_indices().map(|i| i.0))
+                                .filter_map(|(glyph, byte)| {
+                                    run_at(&runs, byte).map(|run| {
+                                        let color = flat_glyph_color(&selection, &runs, byte, false)
+                                            .unwrap_or(run.color);
+                                        let font = brush.fonts().get(&run.font_id).unwrap();
+                                        (glyph, &fonts[run.font_id.0], font, color)
+                                    })
+                                })
+                                .collect::<Vec<_>>();
+                            let atlas = &mut self.sdf_atlas;
+                            for &(offset, color) in &layers {
+                                sdf_quads(atlas, &glyphs, offset, Some(color), &mut quads);
+                            }
+                            sdf_quads(atlas, &glyphs, (0., 0.), None, &mut quads);
+                        }
+                        let texture = sdf_texture
+                            .as_ref()
+                            .and_then(|texture| tex_storage.get(texture));
+                        if let Some(texture) = texture {
+                            effect.data.textures.push(texture.view().clone());
+                            effect.data.samplers.push(texture.sampler().clone());
+                            draw_quads(effect, encoder, mesh.slice(), &quads);
+                            effect.data.textures.clear();
+                            effect.data.samplers.clear();
+                        }
+                    } else if gradient.is_none() {
+                        for &(offset, color) in &layers {
+                            let section = offset_section(&section, offset, color);
+                            brush.queue_custom_layout(section, &draw_layout);
+                        }
+                        brush.queue_custom_layout(section.clone(), &draw_layout);
+                    }
+                    if let Err(err) = brush.draw_queued(
+                        encoder,
+                        &effect.data.out_blends[0],
+                        &effect.data.out_depth.as_ref().unwrap().0,
+                    ) {
+                        report_text_error(&mut self.text_error, format!("{:?}", err));
+                    }
+                    // Gradient text is rasterized on top of its shadow and outline and drawn as
+                    // a quad.
+                    if let Some(gradient) = gradient {
+                        let has_gradient = ui_text.color_gradient.is_some();
+                        let ascents = rendered_string
+                            .char_indices()
+                            .map(|(byte, _)| run_ascent(brush, &runs, byte))
+                            .collect::<Vec<_>>();
+                        let layer_glyphs = layers
+                            .iter()
+                            .map(|&(offset, color)| {
+                                (offset_glyphs(&ui_text.cached_glyphs, offset), color)
+                            })
+                            .collect::<Vec<_>>();
+                        let mut glyphs = Vec::new();
+                        for &(ref layer, color) in &layer_glyphs {
+                            glyphs.extend(
+                                layer
+                                    .iter()
+                                    .zip(&ascents)
+                                    .map(|(glyph, &ascent)| (glyph, ascent, Some(color))),
+                            );
+                        }
+                        glyphs.extend(
+                            ui_text
+                                .cached_glyphs
+                                .iter()
+                                .zip(rendered_string.char_indices().map(|i| i.0))
+                                .zip(&ascents)
+                                .map(|((glyph, byte), &ascent)| {
+                                    let flat =
+                                        flat_glyph_color(&selection, &runs, byte, has_gradient);
+                                    (glyph, ascent, flat)
+                                }),
+                        );
+                        let key = gradient_key(&glyphs, gradient);
+                        let texts = self.gradient_texts.entry(entity).or_insert_with(Vec::new);
+                        if texts.last().map_or(true, |text| text.key != key) {
+                            match rasterize_gradient(&glyphs, gradient) {
+                                Some((coord, size, pixels)) => {
+                                    let meta = TextureMetadata {
+                                        sampler: None,
+                                        mip_levels: Some(1),
+                                        size: Some(size),
+                                        dynamic: false,
+                                        format: None,
+                                        channel: None,
+                                    };
+                                    let texture_data = TextureData::U8(pixels, meta);
+                                    texts.push(GradientText {
+                                        key,
+                                        texture: loader.load_from_data(
+                                            texture_data,
+                                            (),
+                                            &*tex_storage,
+                                        ),
+                                        coord,
+                                        dimension: [size.0 as f32, size.1 as f32],
+                                    });
+                                }
+                                None => texts.clear(),
+                            }
+                        }
+                        let loaded = texts
+                            .iter()
+                            .rposition(|text| tex_storage.get(&text.texture).is_some());
+                        if let Some(loaded) = loaded {
+                            texts.drain(..loaded);
+                            let texture = tex_storage.get(&texts[0].texture).unwrap();
+                            effect.data.textures.push(texture.view().clone());
+                            effect.data.samplers.push(texture.sampler().clone());
+                            let quad = QuadArgs::new(texts[0].coord, texts[0].dimension);
+                            draw_quads(effect, encoder, mesh.slice(), &[quad]);
+                            effect.data.textures.clear();
+                            effect.data.samplers.clear();
+                        }
+                    }
+                    // Render decorations
+                    if ui_text.decoration != TextDecoration::None {
+                        let texture = tex_storage.get(&cached_color_texture(
+                            cache,
+                            fade(ui_text.color, opacity),
+                            &loader,
+                            &tex_storage,
+                        ));
+                        if let Some(texture) = texture {
+                            let lines = ui_text
+                                .cached_glyphs
+                                .iter()
+                                .map(|glyph| {
+                                    let pos = glyph.position();
+                                    let advance = glyph.unpositioned().h_metrics().advance_width;
+                                    let line = ClipRect {
+                                        left: pos.x,
+                                        top: pos.y,
+                                        right: pos.x + advance + letter_spacing,
+                                        bottom: pos.y,
+                                    };
+                                    (pos.y, line)
+                                })
+                                .collect();
+                            let scale = glyph_scale(ui_text.font_size, hidpi);
+                            let v_metrics = brush.fonts().get(&FontId(0)).unwrap().v_metrics(scale);
+                            let quads = decoration_quads(
+                                &merge_line_rects(lines),
+                                ui_text.decoration,
+                                v_metrics.ascent,
+                                v_metrics.descent,
+                                scale.y,
+                            );
+                            effect.data.textures.push(texture.view().clone());
+                            effect.data.samplers.push(texture.sampler().clone());
+                            draw_quads(effect, encoder, mesh.slice(), &quads);
+                            effect.data.textures.clear();
+                            effect.data.samplers.clear();
+                        }
+                    }
                 }
                 // Render cursor
                 if focused.entity == Some(entity) {
@@ -457,80 +1056,104 @@ impl Pass for DrawUi {
                         tex_storage
                             .get(&cached_color_texture(
                                 cache,
-                                ui_text.color,
+                                fade(ed.cursor_color.unwrap_or(ui_text.color), opacity),
                                 &loader,
                                 &tex_storage,
                             ))
                             .map(|tex| (tex, ed))
                     }) {
-                        let blink_on = editing.cursor_blink_timer < 0.5 / CURSOR_BLINK_RATE;
-                        if editing.use_block_cursor || blink_on {
+                        let blink_on = editing.blink_rate <= 0.0
+                            || editing.cursor_blink_timer < 0.5 / editing.blink_rate;
+                        if editing.block_cursor() || blink_on {
                             effect.data.textures.push(texture.view().clone());
                             effect.data.samplers.push(texture.sampler().clone());
+                            // The cursor is sized using the font of the run it sits in.
+                            let cursor_byte = rendered_string
+                                .grapheme_indices(true)
+                                .nth(editing.cursor_position as usize)
+                                .map(|i| i.0)
+                                .unwrap_or(rendered_string.len());
+                            let (font_id, scale) = run_at(&runs, cursor_byte)
+                                .map_or(
+                                    (FontId(0), glyph_scale(ui_text.font_size, hidpi)),
+                                    |run| (run.font_id, run.scale),
+                                );
+                            let font = brush.fonts().get(&font_id).unwrap();
                             // Calculate the width of a space for use with the block cursor.
-                            let space_width = if editing.use_block_cursor {
-                                brush
-                                    .fonts()
-                                    .get(&FontId(0))
+                            let space_width = if editing.block_cursor() {
+                                font.glyph(' ')
                                     .unwrap()
-                                    .glyph(' ')
-                                    .unwrap()
-                                    .scaled(Scale::uniform(ui_text.font_size))
+                                    .scaled(scale)
                                     .h_metrics()
                                     .advance_width
                             } else {
                                 // If we aren't using the block cursor, don't bother.
                                 0.0
                             };
-                            let ascent = brush
-                                .fonts()
-                                .get(&FontId(0))
-                                .unwrap()
-                                .v_metrics(Scale::uniform(ui_text.font_size))
-                                .ascent;
-                            let glyph_len = brush.glyphs(&section).count();
-                            let (glyph, at_end) = if editing.cursor_position as usize >= glyph_len {
-                                (brush.glyphs(&section).last(), true)
+                            let ascent = font.v_metrics(scale).ascent;
+                            let glyph_len = ui_text.cached_glyphs.len();
+                            let cursor_char = rendered_string[..cursor_byte].chars().count();
+                            let (glyph, at_end) = if cursor_char >= glyph_len {
+                                (ui_text.cached_glyphs.last(), true)
                             } else {
-                                (
-                                    brush.glyphs(&section).nth(editing.cursor_position as usize),
-                                    false,
-                                )
+                                (ui_text.cached_glyphs.get(cursor_char), false)
                             };
+                            // The layout may not produce glyphs for trailing whitespace, so the
+                            // cursor is moved past those chars by their advance.
+                            let trailing = char_advance(
+                                brush.fonts(),
+                                &rendered_string,
+                                &runs,
+                                glyph_len..cursor_char,
+                                letter_spacing,
+                            );
                             let height;
                             let width;
-                            if editing.use_block_cursor {
-                                height = if blink_on {
-                                    ui_text.font_size
-                                } else {
-                                    ui_text.font_size / 10.0
-                                };
+                            if editing.block_cursor() {
+                                height = if blink_on { scale.y } else { scale.y / 10.0 };
                                 width = space_width;
                             } else {
-                                height = ui_text.font_size;
-                                width = 2.0;
-                            }
-                            let pos = glyph.map(|g| g.position()).unwrap_or(Point {
-                                x: ui_transform.x,
-                                y: ui_transform.y + ascent,
-                            });
-                            let mut x = pos.x;
-                            if let Some(glyph) = glyph {
-                                if at_end {
-                                    x += glyph.unpositioned().h_metrics().advance_width;
-                                }
+                                height = scale.y;
+                                width = 2.0 * hidpi;
                             }
+                            let pos = match glyph {
+                                Some(glyph) => glyph.position(),
+                                None => empty_caret_position(
+                                    &ui_layout,
+                                    brush.fonts(),
+                                    &section,
+                                    font_id,
+                                    scale,
+                                ).unwrap_or(Point {
+                                    x: ui_transform.pixel_x * hidpi,
+                                    y: ui_transform.pixel_y * hidpi + ascent,
+                                }),
+                            };
+                            // The cursor sits before the glyph it's at, or after the last glyph
+                            // at the end of the text.  Right to left text is mirrored.
+                            let x = match (glyph, ui_text.direction) {
+                                (Some(glyph), TextDirection::LeftToRight) => if at_end {
+                                    pos.x + glyph.unpositioned().h_metrics().advance_width
+                                        + letter_spacing + trailing
+                                } else {
+                                    pos.x
+                                },
+                                (Some(glyph), TextDirection::RightToLeft) => if at_end {
+                                    pos.x - letter_spacing - width - trailing
+                                } else {
+                                    pos.x + glyph.unpositioned().h_metrics().advance_width - width
+                                },
+                                (None, TextDirection::LeftToRight) => pos.x + trailing,
+                                (None, TextDirection::RightToLeft) => pos.x - trailing,
+                            };
+                            // As with the highlight this is the top of the glyph's own row.
                             let mut y = pos.y - ascent;
-                            if editing.use_block_cursor && !blink_on {
-                                y += ui_text.font_size * 0.9;
+                            if editing.block_cursor() && !blink_on {
+                                y += scale.y * 0.9;
                             }
-                            let vertex_args = VertexArgs {
-                                proj_vec: proj_vec.into(),
-                                coord: [x, y],
-                                dimension: [width, height],
-                            };
-                            effect.update_constant_buffer("VertexArgs", &vertex_args, encoder);
-                            effect.draw(mesh.slice(), encoder);
+                            let coord = snap([x, y], ui_text.pixel_perfect);
+                            let quad = QuadArgs::new(coord, [width, height]);
+                            draw_quads(effect, encoder, mesh.slice(), &[quad]);
                         }
                         effect.data.textures.clear();
                         effect.data.samplers.clear();
@@ -538,26 +1161,1163 @@ impl Pass for DrawUi {
                 }
             }
         }
+        draw_image_batch(image_batch.take(), &tex_storage, effect, encoder, mesh.slice());
+        effect.data.scissor = Some(full_screen);
+        effect.use_blend_variant(None);
+        // Glyphs added to the atlas this frame are drawn once it's loaded again.
+        self.sdf_atlas.load(&loader, &tex_storage);
+
+        for (entity, width, height) in resized {
+            if let Some(transform) = ui_transform.get_mut(entity) {
+                transform.set_pixel_size((width, height), screen_size);
+            }
+        }
+        for (entity, offset) in scrolled {
+            if let Some(editing) = editing.get_mut(entity) {
+                editing.scroll_offset = offset;
+            }
+        }
+    }
+}
+
+/// A glyph of text drawn from its distance field, along with the handle of its font, the font
+/// itself and the color it's drawn with.
+type SdfTextGlyph<'a> = (
+    &'a PositionedGlyph<'static>,
+    &'a FontHandle,
+    &'a Font<'static>,
+    [f32; 4],
+);
+
+/// Adds the quads drawing `glyphs` from their distance fields in `atlas` to `quads`, moved by
+/// `offset`.  The glyphs are drawn in `color`, or in their own colors if that's `None`.
+fn sdf_quads(
+    atlas: &mut SdfAtlas,
+    glyphs: &[SdfTextGlyph],
+    offset: (f32, f32),
+    color: Option<[f32; 4]>,
+    quads: &mut Vec<QuadArgs>,
+) {
+    for &(glyph, font_handle, font, glyph_color) in glyphs {
+        let field = match atlas.glyph(font_handle, font, glyph.id()) {
+            Some(field) => field,
+            None => continue,
+        };
+        // Fields are computed for `SDF_SIZE` glyphs and scaled to the size they're drawn at.
+        let scale = glyph.scale();
+        let factor = (scale.x / SDF_SIZE, scale.y / SDF_SIZE);
+        let position = glyph.position();
+        quads.push(QuadArgs {
+            tex_rect: field.tex_rect,
+            color: color.unwrap_or(glyph_color),
+            distance_field: 1.,
+            ..QuadArgs::new(
+                [
+                    position.x + offset.0 + field.offset[0] * factor.0,
+                    position.y + offset.1 + field.offset[1] * factor.1,
+                ],
+                [field.size[0] * factor.0, field.size[1] * factor.1],
+            )
+        });
+    }
+}
+
+/// Drops the least recently used glyph brush that wasn't used in `frame`, if there is one.
+fn evict_glyph_brush(brushes: &mut GlyphBrushCache, frame: u64) {
+    let lru = brushes
+        .iter()
+        .filter(|&(_id, value)| value.2 < frame)
+        .min_by_key(|&(_id, value)| value.2)
+        .map(|(id, _value)| *id);
+    if let Some(id) = lru {
+        brushes.remove(&id);
+    }
+}
+
+/// Logs a text drawing error and stores it in `last`, coalescing repeats of the last error.
+fn report_text_error(last: &mut Option<TextDrawError>, message: String) {
+    let now = Instant::now();
+    if let Some(ref mut last) = *last {
+        if last.message == message {
+            last.repeats += 1;
+            if now.duration_since(last.logged_at) >= Duration::from_secs(ERROR_LOG_INTERVAL) {
+                warn!(
+                    "Unable to draw text, occurred {} more times: {}",
+                    last.repeats,
+                    message
+                );
+                last.repeats = 0;
+                last.logged_at = now;
+            }
+            return;
+        }
+    }
+    warn!("Unable to draw text: {}", message);
+    *last = Some(TextDrawError {
+        message,
+        repeats: 0,
+        logged_at: now,
+    });
+}
+
+/// Draws every quad in `quads` using the currently bound texture.
+///
+/// The quads are drawn as instances of `slice`, `MAX_QUADS` at a time.
+fn draw_quads(
+    effect: &mut Effect,
+    encoder: &mut Encoder,
+    slice: &Slice<Resources>,
+    quads: &[QuadArgs],
+) {
+    for chunk in quads.chunks(MAX_QUADS) {
+        effect.update_buffer("Quads", chunk, encoder);
+        let mut slice = slice.clone();
+        slice.instances = Some((chunk.len() as u32, 0));
+        effect.draw(&slice, encoder);
+    }
+}
+
+/// Adds to `batch` if it has the same key, otherwise replaces it with a new batch for `key` and
+/// returns the finished batch to draw.  The quads of the new or continued batch are returned too.
+fn batch_quads<K: PartialEq>(
+    batch: &mut Option<(K, Vec<QuadArgs>)>,
+    key: K,
+) -> (Option<(K, Vec<QuadArgs>)>, &mut Vec<QuadArgs>) {
+    use std::mem;
+
+    let same_batch = batch.as_ref().map_or(false, |batch| batch.0 == key);
+    let finished = if same_batch {
+        None
+    } else {
+        mem::replace(batch, Some((key, Vec::new())))
+    };
+    (finished, &mut batch.as_mut().unwrap().1)
+}
+
+/// Draws a batch of images sharing the same texture and scissor rectangle.  Does nothing if the
+/// texture isn't loaded yet.
+fn draw_image_batch(
+    batch: Option<((TextureHandle, Rect), Vec<QuadArgs>)>,
+    tex_storage: &AssetStorage<Texture>,
+    effect: &mut Effect,
+    encoder: &mut Encoder,
+    slice: &Slice<Resources>,
+) {
+    if let Some(((texture, scissor), quads)) = batch {
+        if let Some(texture) = tex_storage.get(&texture) {
+            effect.data.scissor = Some(scissor);
+            effect.data.textures.push(texture.view().clone());
+            effect.data.samplers.push(texture.sampler().clone());
+            draw_quads(effect, encoder, slice, &quads);
+            effect.data.textures.clear();
+            effect.data.samplers.clear();
+        }
+    }
+}
+
+/// Maps `rect`, given relative to the region `tex_coords` of a texture, to texture coordinates.
+fn sub_tex_rect(tex_coords: [f32; 4], rect: [f32; 4]) -> [f32; 4] {
+    let width = tex_coords[2] - tex_coords[0];
+    let height = tex_coords[3] - tex_coords[1];
+    [
+        tex_coords[0] + rect[0] * width,
+        tex_coords[1] + rect[1] * height,
+        tex_coords[0] + rect[2] * width,
+        tex_coords[1] + rect[3] * height,
+    ]
+}
+
+/// Splits `image_quad`, the quad an image would be drawn with without slicing, into the nine
+/// quads of a nine-slice.
+///
+/// `texture_size` is the size of the whole texture in texels, and `scale` the number of pixels
+/// the borders take up on screen per texel.  Quads with no area are skipped.
+fn nine_slice_quads(
+    image_quad: QuadArgs,
+    nine_slice: NineSlice,
+    texture_size: (u16, u16),
+    scale: f32,
+    quads: &mut Vec<QuadArgs>,
+) {
+    let tex_coords = image_quad.tex_rect;
+    let tex_width = texture_size.0 as f32 * (tex_coords[2] - tex_coords[0]).abs();
+    let tex_height = texture_size.1 as f32 * (tex_coords[3] - tex_coords[1]).abs();
+    let (x, y) = (image_quad.coord[0], image_quad.coord[1]);
+    let (width, height) = (image_quad.dimension[0], image_quad.dimension[1]);
+    // A flipped image also has its slices mirrored on screen.
+    let flip_x = image_quad.flip[0] != 0.;
+    let flip_y = image_quad.flip[1] != 0.;
+    // Borders that don't fit in the transform are scaled down evenly.
+    let fit = |start: f32, end: f32, size: f32| {
+        let factor = if start + end > size {
+            size / (start + end)
+        } else {
+            1.
+        };
+        (start * factor, end * factor)
+    };
+    let (left, right) = if flip_x {
+        fit(nine_slice.right * scale, nine_slice.left * scale, width)
+    } else {
+        fit(nine_slice.left * scale, nine_slice.right * scale, width)
+    };
+    let (top, bottom) = if flip_y {
+        fit(nine_slice.bottom * scale, nine_slice.top * scale, height)
+    } else {
+        fit(nine_slice.top * scale, nine_slice.bottom * scale, height)
+    };
+    let xs = [x, x + left, x + width - right, x + width];
+    let us = [
+        0.,
+        nine_slice.left / tex_width,
+        1. - nine_slice.right / tex_width,
+        1.,
+    ];
+    let ys = [y, y + top, y + height - bottom, y + height];
+    // The top of the screen is at the top of the texture, where v is largest.
+    let vs = [
+        1.,
+        1. - nine_slice.top / tex_height,
+        nine_slice.bottom / tex_height,
+        0.,
+    ];
+    for row in 0..3 {
+        for column in 0..3 {
+            let width = xs[column + 1] - xs[column];
+            let height = ys[row + 1] - ys[row];
+            if width <= 0. || height <= 0. {
+                continue;
+            }
+            let tex_column = if flip_x { 2 - column } else { column };
+            let tex_row = if flip_y { 2 - row } else { row };
+            quads.push(QuadArgs {
+                coord: [xs[column], ys[row]],
+                dimension: [width, height],
+                tex_rect: sub_tex_rect(
+                    tex_coords,
+                    [
+                        us[tex_column],
+                        vs[tex_row + 1],
+                        us[tex_column + 1],
+                        vs[tex_row],
+                    ],
+                ),
+                ..image_quad
+            });
+        }
+    }
+}
+
+/// The ascent of the font of the run containing the byte at `index`.
+fn run_ascent(
+    brush: &GlyphBrush<'static, Resources, Factory>,
+    runs: &[TextRun],
+    index: usize,
+) -> f32 {
+    run_at(runs, index).map_or(0.0, |run| {
+        brush
+            .fonts()
+            .get(&run.font_id)
+            .unwrap()
+            .v_metrics(run.scale)
+            .ascent
+    })
+}
+
+/// Finds the area covered by `glyphs`, given along with the ascent of their fonts.
+///
+/// Returns `None` if there are no glyphs.
+pub(crate) fn glyph_area<'a, I>(glyphs: I) -> Option<ClipRect>
+where
+    I: IntoIterator<Item = (&'a PositionedGlyph<'static>, f32)>,
+{
+    glyphs
+        .into_iter()
+        .map(|(glyph, ascent)| {
+            let pos = glyph.position();
+            ClipRect {
+                left: pos.x,
+                top: pos.y - ascent,
+                right: pos.x + glyph.unpositioned().h_metrics().advance_width,
+                bottom: pos.y - ascent + glyph.scale().y,
+            }
+        })
+        .fold(None, |area: Option<ClipRect>, glyph| {
+            Some(area.map_or(glyph, |area| ClipRect {
+                left: area.left.min(glyph.left),
+                top: area.top.min(glyph.top),
+                right: area.right.max(glyph.right),
+                bottom: area.bottom.max(glyph.bottom),
+            }))
+        })
+}
+
+/// Builds the highlight quads behind the glyphs in the range `glyphs`, with one quad per line.
+///
+/// `glyphs` are the glyphs `string` was laid out into, there is a glyph for every char.
+fn highlight_quads(
+    brush: &GlyphBrush<'static, Resources, Factory>,
+    glyphs: &[PositionedGlyph<'static>],
+    string: &str,
+    runs: &[TextRun],
+    range: Range<usize>,
+    letter_spacing: f32,
+    pixel_perfect: bool,
+) -> Vec<QuadArgs> {
+    let mut glyph_rects = Vec::new();
+    for (glyph, byte) in glyphs
+        .iter()
+        .zip(string.char_indices().map(|i| i.0))
+        .enumerate()
+        .filter(|&(i, _)| range.start <= i && i < range.end)
+        .map(|(_i, g)| g)
+    {
+        let ascent = run_ascent(brush, runs, byte);
+        let height = glyph.scale().y;
+        let width = glyph.unpositioned().h_metrics().advance_width + letter_spacing;
+        // Glyph positions are on the baseline of the row the glyph was laid out on, so this is
+        // correct for wrapped text as well.
+        let pos = glyph.position();
+        let rect = ClipRect {
+            left: pos.x,
+            top: pos.y - ascent,
+            right: pos.x + width,
+            bottom: pos.y - ascent + height,
+        };
+        glyph_rects.push((pos.y, rect));
+    }
+    merge_line_rects(glyph_rects)
+        .into_iter()
+        .map(|rect| {
+            let coord = snap([rect.left, rect.top], pixel_perfect);
+            QuadArgs::new(coord, [rect.right - rect.left, rect.bottom - rect.top])
+        })
+        .collect()
+}
+
+/// Converts a range of graphemes of `string` into the range of its glyphs, of which there is
+/// one per char.
+fn grapheme_glyphs(string: &str, graphemes: Range<usize>) -> Range<usize> {
+    let mut chars = 0;
+    let mut start = None;
+    for (i, grapheme) in string.graphemes(true).enumerate() {
+        if i == graphemes.start {
+            start = Some(chars);
+        }
+        if i == graphemes.end {
+            return start.unwrap_or(chars)..chars;
+        }
+        chars += grapheme.chars().count();
+    }
+    start.unwrap_or(chars)..chars
+}
+
+/// The x coordinate of the caret before the glyph at `index`, or after the last glyph if
+/// `index` is past the end.  Returns `None` if there are no glyphs.
+fn caret_x(glyphs: &[PositionedGlyph], index: usize, direction: TextDirection) -> Option<f32> {
+    let (glyph, at_end) = match glyphs.get(index) {
+        Some(glyph) => (glyph, false),
+        None => match glyphs.last() {
+            Some(glyph) => (glyph, true),
+            None => return None,
+        },
+    };
+    let left = glyph.position().x;
+    let right = left + glyph.unpositioned().h_metrics().advance_width;
+    Some(match (direction, at_end) {
+        (TextDirection::LeftToRight, false) | (TextDirection::RightToLeft, true) => left,
+        (TextDirection::LeftToRight, true) | (TextDirection::RightToLeft, false) => right,
+    })
+}
+
+/// The left and right edges of the area covered by the advances of `glyphs`, or `None` if there
+/// are no glyphs.
+fn glyph_edges(glyphs: &[PositionedGlyph]) -> Option<(f32, f32)> {
+    glyphs.iter().fold(None, |edges, glyph| {
+        let left = glyph.position().x;
+        let right = left + glyph.unpositioned().h_metrics().advance_width;
+        Some(edges.map_or((left, right), |(l, r): (f32, f32)| (l.min(left), r.max(right))))
+    })
+}
+
+/// Adjusts the horizontal scroll `offset` of single line text, so the caret at `caret` stays at
+/// least `margin` within `bounds` without scrolling further than needed to show all of `text`.
+///
+/// Coordinates are of the text before it's scrolled, `text` and `bounds` are given as their
+/// left and right edges.  Text that fits within its bounds isn't scrolled.
+fn scroll_to_caret(
+    offset: f32,
+    caret: f32,
+    text: (f32, f32),
+    bounds: (f32, f32),
+    margin: f32,
+) -> f32 {
+    if text.0 >= bounds.0 && text.1 <= bounds.1 {
+        return 0.;
+    }
+    let mut offset = offset;
+    if caret - offset > bounds.1 - margin {
+        offset = caret - bounds.1 + margin;
+    }
+    if caret - offset < bounds.0 + margin {
+        offset = caret - bounds.0 - margin;
+    }
+    let min = (text.0 - margin - bounds.0).min(0.);
+    let max = (text.1 + margin - bounds.1).max(0.);
+    offset.max(min).min(max)
+}
+
+/// Merges the rectangles of consecutive glyphs on the same line, given along with their
+/// baseline, into a single rectangle covering all of them.
+///
+/// This is used for highlights, which would otherwise show gaps between glyphs.
+fn merge_line_rects(rects: Vec<(f32, ClipRect)>) -> Vec<ClipRect> {
+    let mut merged: Vec<(f32, ClipRect)> = Vec::with_capacity(rects.len());
+    for (baseline, rect) in rects {
+        if let Some(&mut (line, ref mut last)) = merged.last_mut() {
+            if line == baseline {
+                *last = ClipRect {
+                    left: last.left.min(rect.left),
+                    top: last.top.min(rect.top),
+                    right: last.right.max(rect.right),
+                    bottom: last.bottom.max(rect.bottom),
+                };
+                continue;
+            }
+        }
+        merged.push((baseline, rect));
+    }
+    merged.into_iter().map(|(_baseline, rect)| rect).collect()
+}
+
+/// Builds the quads of the decoration lines of text.
+///
+/// `lines` are the laid out lines of the text with their top and bottom on the baseline, and
+/// `ascent`, `descent` and `font_size` are the metrics of the font lines are placed with.
+fn decoration_quads(
+    lines: &[ClipRect],
+    decoration: TextDecoration,
+    ascent: f32,
+    descent: f32,
+    font_size: f32,
+) -> Vec<QuadArgs> {
+    let thickness = (font_size / 15.).max(1.);
+    // The underline sits halfway into the descent, the strikethrough at about half the height
+    // of lowercase letters.
+    let underline = -descent / 2. - thickness / 2.;
+    let strikethrough = -ascent / 3. - thickness / 2.;
+    let offsets: &[f32] = match decoration {
+        TextDecoration::None => &[],
+        TextDecoration::Underline => &[underline],
+        TextDecoration::Strikethrough => &[strikethrough],
+        TextDecoration::UnderlineAndStrikethrough => &[underline, strikethrough],
+    };
+    let mut quads = Vec::with_capacity(lines.len() * offsets.len());
+    for line in lines {
+        for &offset in offsets {
+            quads.push(QuadArgs::new(
+                [line.left, line.top + offset],
+                [line.right - line.left, thickness],
+            ));
+        }
+    }
+    quads
+}
+
+/// Computes the screen position and bounds of auto sized text.
+///
+/// The text is inset by `padding`, and is only bounded horizontally if it wraps so it never
+/// gets clipped.
+fn auto_size_area(
+    ui_transform: &UiTransform,
+    ui_text: &UiText,
+    padding: f32,
+) -> ((f32, f32), (f32, f32)) {
+    use std::f32::INFINITY;
+
+    let (x, y) = text_position(ui_transform, ui_text.align_h, ui_text.align_v);
+    let x = match ui_text.align_h {
+        HorizontalAlignment::Left | HorizontalAlignment::Justify => x + padding,
+        HorizontalAlignment::Center => x,
+        HorizontalAlignment::Right => x - padding,
+    };
+    let y = match ui_text.align_v {
+        VerticalAlignment::Top => y + padding,
+        VerticalAlignment::Center => y,
+        VerticalAlignment::Bottom => y - padding,
+    };
+    let width = if ui_text.overflow == Overflow::Wrap {
+        (ui_transform.pixel_width - padding * 2.).max(0.)
+    } else {
+        INFINITY
+    };
+    ((x, y), (width, INFINITY))
+}
+
+/// A rectangle in screen space stored as its edges, used for clipping and measuring.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ClipRect {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl<'a> From<&'a UiTransform> for ClipRect {
+    fn from(transform: &UiTransform) -> Self {
+        ClipRect {
+            left: transform.pixel_x,
+            top: transform.pixel_y,
+            right: transform.pixel_x + transform.pixel_width,
+            bottom: transform.pixel_y + transform.pixel_height,
+        }
+    }
+}
+
+impl ClipRect {
+    /// The area covered by both rectangles.  This is empty if they don't overlap.
+    fn intersect(&self, other: &ClipRect) -> ClipRect {
+        let left = self.left.max(other.left);
+        let top = self.top.max(other.top);
+        ClipRect {
+            left,
+            top,
+            right: self.right.min(other.right).max(left),
+            bottom: self.bottom.min(other.bottom).max(top),
+        }
+    }
+
+    /// Checks if the rectangles share any area.
+    fn overlaps(&self, other: &ClipRect) -> bool {
+        self.left < other.right && other.left < self.right && self.top < other.bottom
+            && other.top < self.bottom
+    }
+
+    /// Scales every edge of this by `factor`, such as to convert it from logical pixels.
+    fn scaled(&self, factor: f32) -> ClipRect {
+        ClipRect {
+            left: self.left * factor,
+            top: self.top * factor,
+            right: self.right * factor,
+            bottom: self.bottom * factor,
+        }
+    }
+
+    /// Converts this into a scissor rectangle in pixels on a screen `screen_height` pixels tall.
+    /// Scissor rectangles start at the bottom left of the screen, while this starts at the top
+    /// left.
+    fn scissor(&self, screen_height: f32) -> Rect {
+        use std::u16::MAX;
+
+        let to_u16 = |value: f32| value.max(0.).min(MAX as f32) as u16;
+        let x = to_u16(self.left);
+        let y = to_u16(screen_height - self.bottom);
+        Rect {
+            x,
+            y,
+            w: to_u16(self.right) - x,
+            h: to_u16(screen_height - self.top) - y,
+        }
+    }
+}
+
+/// Finds the clip `entity` is drawn with, which is the intersection of the `UiClip` rectangles
+/// of the entity and of every entity above it.
+///
+/// This acts as a clip stack for each chain of parents.  Resolved clips are stored in `cache` so
+/// a shared parent's chain is only walked once per frame.
+fn resolve_clip(
+    entity: Entity,
+    transforms: &WriteStorage<UiTransform>,
+    clips: &ReadStorage<UiClip>,
+    parents: &ReadStorage<Parent>,
+    cache: &mut HashMap<Entity, Option<ClipRect>>,
+) -> Option<ClipRect> {
+    if let Some(clip) = cache.get(&entity) {
+        return *clip;
+    }
+    let parent_clip = match parents.get(entity) {
+        Some(parent) => resolve_clip(parent.entity, transforms, clips, parents, cache),
+        None => None,
+    };
+    let own_clip = match (clips.get(entity), transforms.get(entity)) {
+        (Some(_), Some(transform)) => Some(ClipRect::from(transform)),
+        _ => None,
+    };
+    let clip = match (own_clip, parent_clip) {
+        (Some(own), Some(parent)) => Some(own.intersect(&parent)),
+        (own, parent) => own.or(parent),
+    };
+    cache.insert(entity, clip);
+    clip
+}
+
+/// A glyph of gradient text, along with the ascent of its font and the flat color it's drawn
+/// with instead of the gradient, such as when it's selected or part of a shadow.
+type GradientGlyph<'a> = (&'a PositionedGlyph<'static>, f32, Option<[f32; 4]>);
+
+/// The flat color a glyph of rasterized text is drawn with instead of the gradient.  This is the
+/// selected text color if the glyph at `byte` is selected, and the color of its run if the text
+/// has no gradient.
+fn flat_glyph_color(
+    selection: &Option<(Range<usize>, [f32; 4])>,
+    runs: &[TextRun],
+    byte: usize,
+    has_gradient: bool,
+) -> Option<[f32; 4]> {
+    let selected = selection.as_ref().and_then(|selection| {
+        if selection.0.start <= byte && byte < selection.0.end {
+            Some(selection.1)
+        } else {
+            None
+        }
+    });
+    match selected {
+        Some(color) => Some(color),
+        None if has_gradient => None,
+        None => run_at(runs, byte).map(|run| run.color),
+    }
+}
+
+/// Hashes everything `rasterize_gradient` uses, to detect when gradient text has changed.
+fn gradient_key(glyphs: &[GradientGlyph], gradient: ([f32; 4], [f32; 4])) -> u64 {
+    fn hash_color(color: [f32; 4], hasher: &mut DefaultHasher) {
+        for channel in &color {
+            channel.to_bits().hash(hasher);
+        }
+    }
+    let mut hasher = DefaultHasher::new();
+    hash_color(gradient.0, &mut hasher);
+    hash_color(gradient.1, &mut hasher);
+    for &(glyph, ascent, selected) in glyphs {
+        let pos = glyph.position();
+        glyph.id().0.hash(&mut hasher);
+        pos.x.to_bits().hash(&mut hasher);
+        pos.y.to_bits().hash(&mut hasher);
+        glyph.scale().y.to_bits().hash(&mut hasher);
+        ascent.to_bits().hash(&mut hasher);
+        match selected {
+            Some(color) => hash_color(color, &mut hasher),
+            None => 0u8.hash(&mut hasher),
+        }
+    }
+    hasher.finish()
+}
+
+/// Rasterizes `glyphs` into RGBA pixels, coloring every pixel by where it is between the top and
+/// the bottom of the line of its glyph.  Glyphs are blended over the glyphs before them like the
+/// glyph brush blends them, so shadows and outlines come first.
+///
+/// Returns the screen position and size of the pixels, which are stored bottom row first as
+/// textures expect, or `None` if the glyphs don't cover any pixels.
+fn rasterize_gradient(
+    glyphs: &[GradientGlyph],
+    gradient: ([f32; 4], [f32; 4]),
+) -> Option<([f32; 2], (u16, u16), Vec<u8>)> {
+    let area = glyphs
+        .iter()
+        .filter_map(|glyph| glyph.0.pixel_bounding_box())
+        .fold(None, |area: Option<(i32, i32, i32, i32)>, bb| {
+            Some(area.map_or((bb.min.x, bb.min.y, bb.max.x, bb.max.y), |area| {
+                (
+                    area.0.min(bb.min.x),
+                    area.1.min(bb.min.y),
+                    area.2.max(bb.max.x),
+                    area.3.max(bb.max.y),
+                )
+            }))
+        });
+    let (left, top, right, bottom) = match area {
+        Some(area) => area,
+        None => return None,
+    };
+    let width = (right - left) as usize;
+    let height = (bottom - top) as usize;
+    // Straight alpha, to be converted to bytes once every glyph is blended in.
+    let mut pixels = vec![0f32; width * height * 4];
+    for &(glyph, ascent, selected) in glyphs {
+        let bb = match glyph.pixel_bounding_box() {
+            Some(bb) => bb,
+            None => continue,
+        };
+        let line_top = glyph.position().y - ascent;
+        let line_height = glyph.scale().y;
+        glyph.draw(|x, y, coverage| {
+            let screen_y = bb.min.y + y as i32;
+            let color = selected.unwrap_or_else(|| {
+                let t = ((screen_y as f32 + 0.5 - line_top) / line_height)
+                    .max(0.)
+                    .min(1.);
+                let mix = |i: usize| gradient.0[i] + (gradient.1[i] - gradient.0[i]) * t;
+                [mix(0), mix(1), mix(2), mix(3)]
+            });
+            let column = (bb.min.x + x as i32 - left) as usize;
+            let row = height - 1 - (screen_y - top) as usize;
+            let i = (row * width + column) * 4;
+            let alpha = color[3] * coverage;
+            let below = pixels[i + 3] * (1. - alpha);
+            let blended = alpha + below;
+            if blended > 0. {
+                for channel in 0..3 {
+                    pixels[i + channel] =
+                        (color[channel] * alpha + pixels[i + channel] * below) / blended;
+                }
+            }
+            pixels[i + 3] = blended;
+        });
+    }
+    let to_u8 = |value: f32| (value * 255.).round().max(0.).min(255.) as u8;
+    Some((
+        [left as f32, top as f32],
+        (width as u16, height as u16),
+        pixels.into_iter().map(to_u8).collect(),
+    ))
+}
+
+/// Finds the opacity `entity` is drawn with, which is the product of the `UiOpacity` of the
+/// entity and of every entity above it.
+///
+/// Resolved opacities are stored in `cache` like the clips of `resolve_clip`.
+fn resolve_opacity(
+    entity: Entity,
+    opacities: &ReadStorage<UiOpacity>,
+    parents: &ReadStorage<Parent>,
+    cache: &mut HashMap<Entity, f32>,
+) -> f32 {
+    if let Some(opacity) = cache.get(&entity) {
+        return *opacity;
+    }
+    let parent_opacity = match parents.get(entity) {
+        Some(parent) => resolve_opacity(parent.entity, opacities, parents, cache),
+        None => 1.,
+    };
+    let opacity = opacities.get(entity).map_or(1., |opacity| opacity.0) * parent_opacity;
+    cache.insert(entity, opacity);
+    opacity
+}
+
+/// Rounds `coord` to whole pixels if `pixel_perfect` is set.
+fn snap(coord: [f32; 2], pixel_perfect: bool) -> [f32; 2] {
+    if pixel_perfect {
+        [coord[0].round(), coord[1].round()]
+    } else {
+        coord
+    }
+}
+
+/// Multiplies the alpha of `color` by `opacity`.
+fn fade(color: [f32; 4], opacity: f32) -> [f32; 4] {
+    [color[0], color[1], color[2], color[3] * opacity]
+}
+
+/// A styled byte range of the string rendered for a `UiText`.
+struct TextRun {
+    range: Range<usize>,
+    font_id: FontId,
+    color: [f32; 4],
+    scale: Scale,
+}
+
+/// Builds the string rendered for `ui_text` along with the styling of each part of it.
+///
+/// `fonts` are the fonts the glyph brush of `ui_text` was built with, the index of a font in
+/// it is its `FontId`.  Font sizes are multiplied by `hidpi`.
+fn text_runs(ui_text: &UiText, fonts: &[FontHandle], hidpi: f32) -> (String, Vec<TextRun>) {
+    let mut string = String::new();
+    let mut runs = Vec::new();
+    {
+        let reveal = ui_text.reveal_timer > 0.0;
+        // Only wrapped text is laid out on multiple lines.
+        let newlines = ui_text.overflow == Overflow::Wrap;
+        // Tabs are only kept if the layout moves them to tab stops.
+        let tabs = ui_text.tab_width > 0.0;
+        let mut push = |text: &str, font: &FontHandle, color, font_size, last: bool| {
+            let start = string.len();
+            if ui_text.password {
+                let reveal_last = reveal && last;
+                push_masked(&mut string, text, ui_text.mask_char, reveal_last, newlines, tabs);
+            } else {
+                push_normalized(&mut string, text, newlines, tabs);
+            }
+            runs.push(TextRun {
+                range: start..string.len(),
+                font_id: FontId(fonts.iter().position(|f| f == font).unwrap_or(0)),
+                color,
+                scale: glyph_scale(font_size, hidpi),
+            });
+        };
+        if ui_text.spans.is_empty() {
+            push(&ui_text.text, &ui_text.font, ui_text.color, ui_text.font_size, true);
+        } else {
+            let last_span = ui_text.spans.len() - 1;
+            for (i, span) in ui_text.spans.iter().enumerate() {
+                push(&span.text, &span.font, span.color, span.font_size, i == last_span);
+            }
+        }
+    }
+    (string, runs)
+}
+
+/// The scale glyphs of `font_size` logical pixels are drawn with.
+pub(crate) fn glyph_scale(font_size: f32, hidpi: f32) -> Scale {
+    Scale::uniform(font_size * hidpi)
+}
+
+/// Appends `text` to `string` with every control character replaced by a space, except for line
+/// feeds if `newlines` is set and tabs if `tabs` is set.
+///
+/// Control characters would otherwise be drawn as missing glyphs.  As they're replaced rather
+/// than removed, there is still a glyph for every char of the text.
+pub(crate) fn push_normalized(string: &mut String, text: &str, newlines: bool, tabs: bool) {
+    string.extend(text.chars().map(|c| {
+        if c.is_control() && !(newlines && c == '\n') && !(tabs && c == '\t') {
+            ' '
+        } else {
+            c
+        }
+    }));
+}
+
+/// Appends `text` to `string` with a mask character for every grapheme, except for the last
+/// grapheme if `reveal_last` is set.  Line breaks are kept if `newlines` is set so the masked
+/// text has the same lines as the real text.
+fn push_masked(
+    string: &mut String,
+    text: &str,
+    mask_char: char,
+    reveal_last: bool,
+    newlines: bool,
+    tabs: bool,
+) {
+    let mut graphemes = text.graphemes(true).peekable();
+    while let Some(grapheme) = graphemes.next() {
+        if (reveal_last && graphemes.peek().is_none()) || (newlines && grapheme.ends_with('\n')) {
+            push_normalized(string, grapheme, newlines, tabs);
+        } else {
+            string.push(mask_char);
+        }
+    }
+}
+
+/// Finds the run containing the byte at `index`, or the last run if `index` is past the end.
+fn run_at(runs: &[TextRun], index: usize) -> Option<&TextRun> {
+    runs.iter()
+        .find(|run| index < run.range.end)
+        .or_else(|| runs.last())
+}
+
+/// Splits the runs of `string` into sections for the glyph brush, recoloring the `selection`
+/// byte range if there is one.
+fn section_texts<'a>(
+    string: &'a str,
+    runs: &[TextRun],
+    selection: Option<(Range<usize>, [f32; 4])>,
+) -> Vec<SectionText<'a>> {
+    let mut texts = Vec::with_capacity(runs.len());
+    for run in runs {
+        let mut push = |range: Range<usize>, color: [f32; 4]| if range.start < range.end {
+            texts.push(SectionText {
+                text: &string[range],
+                scale: run.scale,
+                color,
+                font_id: run.font_id,
+            });
+        };
+        match selection {
+            Some((ref selected, selected_color)) => {
+                let start = selected.start.max(run.range.start).min(run.range.end);
+                let end = selected.end.max(start).min(run.range.end);
+                push(run.range.start..start, run.color);
+                push(start..end, selected_color);
+                push(end..run.range.end, run.color);
+            }
+            None => push(run.range.clone(), run.color),
+        }
+    }
+    texts
+}
+
+/// Cuts `string` off after its first `visible` graphemes, returning whether any were cut.
+fn hide_graphemes(string: &mut String, runs: &mut Vec<TextRun>, visible: usize) -> bool {
+    let end_byte = match string.grapheme_indices(true).nth(visible) {
+        Some((byte, _)) => byte,
+        None => return false,
+    };
+    let kept_runs = runs.iter()
+        .rposition(|run| run.range.start < end_byte)
+        .unwrap_or(0) + 1;
+    runs.truncate(kept_runs);
+    string.truncate(end_byte);
+    for run in runs.iter_mut() {
+        run.range.start = run.range.start.min(end_byte);
+        run.range.end = run.range.end.min(end_byte);
+    }
+    true
+}
+
+/// Shortens `string` so it fits within `max_width` once an ellipsis is appended to it.
+///
+/// The text is measured with the spacing options of `ui_layout` on a single line.  Does nothing
+/// if the text already fits.  The text is only cut on grapheme cluster boundaries,
+/// so combining sequences are never split.  The ellipsis uses the style of the last run that
+/// is kept.
+fn truncate_text(
+    brush: &mut GlyphBrush<'static, Resources, Factory>,
+    string: &mut String,
+    runs: &mut Vec<TextRun>,
+    max_width: f32,
+    ui_layout: UiLayout,
+) {
+    use std::f32::INFINITY;
+
+    const ELLIPSIS: char = '\u{2026}';
+
+    // The right edge of every glyph, relative to the start of the text.  There is a glyph per
+    // char.
+    let right_edges = {
+        let layout = UiLayout {
+            layout: Layout::SingleLine {
+                line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+                h_align: HorizontalAlign::Left,
+                v_align: VerticalAlign::Top,
+            },
+            align_h: HorizontalAlignment::Left,
+            align_v: VerticalAlignment::Top,
+            direction: TextDirection::LeftToRight,
+            line_spacing: 1.0,
+            pixel_perfect: false,
+            ..ui_layout
+        };
+        let section = VariedSection {
+            screen_position: (0., 0.),
+            bounds: (INFINITY, INFINITY),
+            z: 0.,
+            layout: layout.layout,
+            text: section_texts(string, runs, None),
+        };
+        layout
+            .calculate_glyphs(brush.fonts(), &section)
+            .iter()
+            .map(|g| g.0.position().x + g.0.unpositioned().h_metrics().advance_width)
+            .collect::<Vec<_>>()
+    };
+    if right_edges.iter().all(|&right| right <= max_width) {
+        return;
+    }
+
+    let ellipsis_width = |run: Option<&TextRun>| {
+        run.map_or(0., |run| {
+            brush
+                .fonts()
+                .get(&run.font_id)
+                .unwrap()
+                .glyph(ELLIPSIS)
+                .unwrap()
+                .scaled(run.scale)
+                .h_metrics()
+                .advance_width
+        })
+    };
+    let mut glyph = 0;
+    let mut end_byte = 0;
+    for (byte, grapheme) in string.grapheme_indices(true) {
+        let chars = grapheme.chars().count();
+        let right = right_edges
+            .iter()
+            .skip(glyph)
+            .take(chars)
+            .fold(0., |right: f32, &r| right.max(r));
+        if right + ui_layout.letter_spacing + ellipsis_width(run_at(runs, byte)) > max_width {
+            break;
+        }
+        glyph += chars;
+        end_byte = byte + grapheme.len();
+    }
+    let kept_runs = runs.iter()
+        .rposition(|run| run.range.start < end_byte)
+        .unwrap_or(0) + 1;
+    runs.truncate(kept_runs);
+    string.truncate(end_byte);
+    string.push(ELLIPSIS);
+    if let Some(run) = runs.last_mut() {
+        run.range.start = run.range.start.min(end_byte);
+        run.range.end = string.len();
     }
 }
 
+/// Creates a copy of `section` moved by `offset` with every part of the text set to `color`.
+///
+/// This is used for effects drawn behind the text such as shadows, so it deliberately discards
+/// the selection colors.
+fn offset_section<'a>(
+    section: &VariedSection<'a>,
+    offset: (f32, f32),
+    color: [f32; 4],
+) -> VariedSection<'a> {
+    let mut section = section.clone();
+    section.screen_position.0 += offset.0;
+    section.screen_position.1 += offset.1;
+    for text in &mut section.text {
+        text.color = color;
+    }
+    section
+}
+
+/// Creates copies of `glyphs` moved by `offset`, for effects rasterized behind the text such as
+/// shadows.
+fn offset_glyphs(
+    glyphs: &[PositionedGlyph<'static>],
+    offset: (f32, f32),
+) -> Vec<PositionedGlyph<'static>> {
+    glyphs
+        .iter()
+        .map(|glyph| {
+            let pos = glyph.position();
+            let pos = point(pos.x + offset.0, pos.y + offset.1);
+            glyph.clone().into_unpositioned().positioned(pos)
+        })
+        .collect()
+}
+
+/// The offset and color of every copy of a text drawn beneath it for its `shadow` and `outline`,
+/// shadow first.  Offsets are multiplied by `hidpi` and colors faded by `opacity`.
+fn text_layers(
+    shadow: Option<(f32, f32, [f32; 4])>,
+    outline: Option<OutlineStyle>,
+    hidpi: f32,
+    opacity: f32,
+) -> Vec<((f32, f32), [f32; 4])> {
+    let mut layers = Vec::new();
+    if let Some((x, y, color)) = shadow {
+        layers.push(((x * hidpi, y * hidpi), fade(color, opacity)));
+    }
+    if let Some(outline) = outline {
+        let width = outline.width * hidpi;
+        for &offset in outline_offsets(outline.quality) {
+            let offset = (offset.0 * width, offset.1 * width);
+            layers.push((offset, fade(outline.color, opacity)));
+        }
+    }
+    layers
+}
+
+/// The unit offsets at which copies of the text are drawn to produce an outline.
+fn outline_offsets(quality: OutlineQuality) -> &'static [(f32, f32)] {
+    use std::f32::consts::FRAC_1_SQRT_2 as D;
+
+    const LOW: [(f32, f32); 4] = [(0., -1.), (1., 0.), (0., 1.), (-1., 0.)];
+    const HIGH: [(f32, f32); 8] = [
+        (0., -1.),
+        (D, -D),
+        (1., 0.),
+        (D, D),
+        (0., 1.),
+        (-D, D),
+        (-1., 0.),
+        (-D, -D),
+    ];
+    match quality {
+        OutlineQuality::Low => &LOW,
+        OutlineQuality::High => &HIGH,
+    }
+}
+
+/// The total advance of the chars of `string` in the range `chars`, including letter spacing.
+///
+/// This measures chars the layout may not have produced glyphs for, such as trailing
+/// whitespace.
+fn char_advance<'font>(
+    fonts: &StdHashMap<FontId, Font<'font>>,
+    string: &str,
+    runs: &[TextRun],
+    chars: Range<usize>,
+    letter_spacing: f32,
+) -> f32 {
+    string
+        .char_indices()
+        .skip(chars.start)
+        .take(chars.end.saturating_sub(chars.start))
+        .map(|(byte, c)| {
+            let advance = run_at(runs, byte)
+                .and_then(|run| {
+                    fonts
+                        .get(&run.font_id)
+                        .and_then(|font| font.glyph(c))
+                        .map(|glyph| glyph.scaled(run.scale).h_metrics().advance_width)
+                })
+                .unwrap_or(0.);
+            advance + letter_spacing
+        })
+        .sum()
+}
+
+/// Finds where the caret of empty text goes, which is where the first glyph typed into it would
+/// be laid out with `ui_layout`.
+///
+/// `section` is the empty section of the text.  Returns a position on the baseline, or `None` if
+/// the font has no glyph for a space.
+fn empty_caret_position<'font>(
+    ui_layout: &UiLayout,
+    fonts: &StdHashMap<FontId, Font<'font>>,
+    section: &VariedSection,
+    font_id: FontId,
+    scale: Scale,
+) -> Option<Point<f32>> {
+    let probe = VariedSection {
+        text: vec![
+            SectionText {
+                text: " ",
+                scale,
+                color: [0.; 4],
+                font_id,
+            },
+        ],
+        ..section.clone()
+    };
+    ui_layout.calculate_glyphs(fonts, &probe).first().map(|glyph| {
+        let pos = glyph.0.position();
+        let advance = glyph.0.unpositioned().h_metrics().advance_width;
+        let x = match ui_layout.align_h {
+            HorizontalAlignment::Left | HorizontalAlignment::Justify => pos.x,
+            HorizontalAlignment::Center => pos.x + advance / 2.,
+            HorizontalAlignment::Right => pos.x + advance,
+        };
+        Point { x, y: pos.y }
+    })
+}
+
+/// Computes the screen position handed to the glyph brush.
+///
+/// The glyph brush aligns text around its screen position rather than within its bounds, so
+/// for centered text this is the middle of the transform, and for right aligned text it's the
+/// right edge.
+fn text_position(
+    ui_transform: &UiTransform,
+    align_h: HorizontalAlignment,
+    align_v: VerticalAlignment,
+) -> (f32, f32) {
+    let x = match align_h {
+        HorizontalAlignment::Left | HorizontalAlignment::Justify => ui_transform.pixel_x,
+        HorizontalAlignment::Center => ui_transform.pixel_x + ui_transform.pixel_width / 2.0,
+        HorizontalAlignment::Right => ui_transform.pixel_x + ui_transform.pixel_width,
+    };
+    let y = match align_v {
+        VerticalAlignment::Top => ui_transform.pixel_y,
+        VerticalAlignment::Center => ui_transform.pixel_y + ui_transform.pixel_height / 2.0,
+        VerticalAlignment::Bottom => ui_transform.pixel_y + ui_transform.pixel_height,
+    };
+    (x, y)
+}
+
 fn cached_color_texture(
     cache: &mut HashMap<KeyColor, TextureHandle>,
     color: [f32; 4],
     loader: &Loader,
     storage: &AssetStorage<Texture>,
 ) -> TextureHandle {
-    fn to_u8(input: f32) -> u8 {
-        (input * 255.0).min(255.0) as u8
-    }
-    let key = KeyColor([
-        to_u8(color[0]),
-        to_u8(color[1]),
-        to_u8(color[2]),
-        to_u8(color[3]),
-    ]);
+    let bytes = color_bytes(color);
     cache
-        .entry(key)
+        .entry(KeyColor(bytes))
         .or_insert_with(|| {
             let meta = TextureMetadata {
                 sampler: None,
@@ -565,10 +2325,593 @@ fn cached_color_texture(
                 size: Some((1, 1)),
                 dynamic: false,
                 format: None,
-                channel: None,
+                channel: Some(target_channel()),
             };
-            let texture_data = TextureData::Rgba(color, meta);
+            // The bytes the texture is keyed by are uploaded as is, so colors that share a
+            // texture are drawn exactly the same.
+            let texture_data = TextureData::U8(bytes.to_vec(), meta);
             loader.load_from_data(texture_data, (), storage)
         })
         .clone()
 }
+
+/// The channel type of the render target.
+///
+/// Solid color textures use it so that the color is not converted between color spaces on
+/// the way to the target, the same as the vertex colors of text.
+fn target_channel() -> ChannelType {
+    ColorFormat::get_format().1
+}
+
+/// Converts a color to bytes, rounding to the nearest value.
+fn color_bytes(color: [f32; 4]) -> [u8; 4] {
+    fn to_u8(input: f32) -> u8 {
+        (input * 255.0).round().max(0.0).min(255.0) as u8
+    }
+    [
+        to_u8(color[0]),
+        to_u8(color[1]),
+        to_u8(color[2]),
+        to_u8(color[3]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_position_follows_alignment() {
+        let transform = UiTransform::new("test".to_string(), 10., 20., 0., 100., 50., 0);
+        assert_eq!(
+            text_position(&transform, HorizontalAlignment::Left, VerticalAlignment::Top),
+            (10., 20.)
+        );
+        assert_eq!(
+            text_position(&transform, HorizontalAlignment::Center, VerticalAlignment::Center),
+            (60., 45.)
+        );
+        assert_eq!(
+            text_position(&transform, HorizontalAlignment::Right, VerticalAlignment::Bottom),
+            (110., 70.)
+        );
+    }
+
+    #[test]
+    fn nine_slice_keeps_corners() {
+        let nine_slice = NineSlice {
+            left: 8.,
+            right: 8.,
+            top: 4.,
+            bottom: 4.,
+        };
+        let mut quads = Vec::new();
+        let quad = QuadArgs::new([0., 0.], [100., 40.]);
+        nine_slice_quads(quad, nine_slice, (32, 16), 1., &mut quads);
+        assert_eq!(quads.len(), 9);
+        // Top left corner.
+        assert_eq!(quads[0].coord, [0., 0.]);
+        assert_eq!(quads[0].dimension, [8., 4.]);
+        assert_eq!(quads[0].tex_rect, [0., 0.75, 0.25, 1.]);
+        // Stretched center.
+        assert_eq!(quads[4].coord, [8., 4.]);
+        assert_eq!(quads[4].dimension, [84., 32.]);
+        assert_eq!(quads[4].tex_rect, [0.25, 0.25, 0.75, 0.75]);
+    }
+
+    #[test]
+    fn half_width_tex_coords_halve_sampled_region() {
+        let half = [0., 0., 0.5, 1.];
+        let rect = sub_tex_rect(half, FULL_TEX_RECT);
+        assert_eq!(rect, half);
+        assert_eq!(rect[2] - rect[0], (FULL_TEX_RECT[2] - FULL_TEX_RECT[0]) / 2.);
+
+        // Nine-slice borders are measured within the region.
+        let nine_slice = NineSlice {
+            left: 8.,
+            right: 8.,
+            top: 4.,
+            bottom: 4.,
+        };
+        let mut quads = Vec::new();
+        let quad = QuadArgs {
+            tex_rect: half,
+            ..QuadArgs::new([0., 0.], [100., 40.])
+        };
+        nine_slice_quads(quad, nine_slice, (64, 16), 1., &mut quads);
+        assert_eq!(quads[0].tex_rect, [0., 0.75, 0.125, 1.]);
+        assert_eq!(quads[8].tex_rect, [0.375, 0., 0.5, 0.25]);
+    }
+
+    #[test]
+    fn flipped_nine_slice_mirrors_slices() {
+        let nine_slice = NineSlice {
+            left: 8.,
+            right: 4.,
+            top: 0.,
+            bottom: 0.,
+        };
+        let mut quads = Vec::new();
+        let quad = QuadArgs {
+            flip: [1., 0.],
+            ..QuadArgs::new([0., 0.], [100., 40.])
+        };
+        nine_slice_quads(quad, nine_slice, (32, 16), 1., &mut quads);
+        assert_eq!(quads.len(), 3);
+        // The right border of the texture is drawn on the left, flipped.
+        assert_eq!(quads[0].dimension, [4., 40.]);
+        assert_eq!(quads[0].tex_rect, [0.875, 0., 1., 1.]);
+        assert_eq!(quads[0].flip, [1., 0.]);
+        assert_eq!(quads[2].coord, [92., 0.]);
+        assert_eq!(quads[2].tex_rect, [0., 0., 0.25, 1.]);
+    }
+
+    #[test]
+    fn consecutive_images_are_drawn_together() {
+        // Counts the batches drawn for a draw order of images by texture, with `None` for text.
+        // Each batch is drawn with one draw call.
+        let draws = |order: &[Option<u32>]| {
+            let mut batch = None;
+            let mut draws = 0;
+            for element in order {
+                let finished = match *element {
+                    Some(texture) => {
+                        let (finished, quads) = batch_quads(&mut batch, texture);
+                        quads.push(QuadArgs::new([0., 0.], [1., 1.]));
+                        finished
+                    }
+                    None => batch.take(),
+                };
+                draws += finished.iter().count();
+            }
+            draws + batch.iter().count()
+        };
+        assert_eq!(draws(&[Some(0), Some(0), Some(0)]), 1);
+        assert_eq!(draws(&[Some(0), Some(1), Some(1), Some(0)]), 3);
+        // Text between images ends the batch, so icons with labels take a draw call each.
+        assert_eq!(draws(&[Some(0), None, Some(0), None, Some(0), None]), 3);
+        // Text drawn after all of the images doesn't.
+        assert_eq!(draws(&[Some(0), Some(0), Some(0), None, None, None]), 1);
+    }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let rect = |x, y, width, height| {
+            ClipRect::from(&UiTransform::new("test".to_string(), x, y, 0., width, height, 0))
+        };
+        let outer = rect(0., 0., 100., 100.);
+        let inner = rect(50., 80., 100., 50.);
+        let clip = inner.intersect(&outer);
+        // Scissor rectangles start from the bottom of the screen, so a clip near the top of the
+        // screen has a large y.
+        assert_eq!(
+            clip.scissor(600.),
+            Rect {
+                x: 50,
+                y: 500,
+                w: 50,
+                h: 20,
+            }
+        );
+
+        let outside = rect(200., 0., 10., 10.);
+        assert!(!outside.overlaps(&outer));
+        assert_eq!(outside.intersect(&outer).scissor(600.).w, 0);
+    }
+
+    #[test]
+    fn gradient_runs_from_top_to_bottom() {
+        use rusttype::{point, FontCollection};
+
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let scale = Scale::uniform(20.);
+        let ascent = font.v_metrics(scale).ascent;
+        let glyph = font.glyph('a')
+            .unwrap()
+            .scaled(scale)
+            .positioned(point(0., ascent));
+        let red = [1., 0., 0., 1.];
+        let blue = [0., 0., 1., 1.];
+        let (_coord, (width, height), pixels) =
+            rasterize_gradient(&[(&glyph, ascent, None)], (red, blue)).unwrap();
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+        // The most opaque pixel of a row, as red and blue.
+        let row_color = |row: usize| {
+            let start = row * width as usize * 4;
+            let row = &pixels[start..start + width as usize * 4];
+            let pixel = row.chunks(4).max_by_key(|pixel| pixel[3]).unwrap();
+            (pixel[0], pixel[2])
+        };
+        // Pixels are stored bottom row first.
+        let (top_red, top_blue) = row_color(height as usize - 1);
+        let (bottom_red, bottom_blue) = row_color(0);
+        assert!(top_red > bottom_red);
+        assert!(top_blue < bottom_blue);
+    }
+
+    #[test]
+    fn premultiplied_text_keeps_its_color_at_the_edges() {
+        use rusttype::{point, FontCollection};
+
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let scale = Scale::uniform(20.);
+        let ascent = font.v_metrics(scale).ascent;
+        // Positioned between pixels so the edges are only partly covered.
+        let glyph = font.glyph('o')
+            .unwrap()
+            .scaled(scale)
+            .positioned(point(0.3, ascent + 0.3));
+        let red = [1., 0., 0., 1.];
+        let runs = vec![
+            TextRun {
+                range: 0..1,
+                font_id: FontId(0),
+                color: red,
+                scale,
+            },
+        ];
+        let white = [1.; 4];
+        let selection = Some((1..2, white));
+        assert_eq!(flat_glyph_color(&selection, &runs, 0, false), Some(red));
+        assert_eq!(flat_glyph_color(&selection, &runs, 0, true), None);
+        assert_eq!(flat_glyph_color(&selection, &runs, 1, true), Some(white));
+        let flat = flat_glyph_color(&None, &runs, 0, false);
+        let (_coord, _size, pixels) =
+            rasterize_gradient(&[(&glyph, ascent, flat)], (white, white)).unwrap();
+        // The pixels keep straight alpha for the shader to premultiply, so partly covered edge
+        // pixels aren't darkened.
+        let edges = pixels
+            .chunks(4)
+            .filter(|pixel| pixel[3] > 0 && pixel[3] < 255)
+            .collect::<Vec<_>>();
+        assert!(!edges.is_empty());
+        for pixel in edges {
+            assert_eq!(&pixel[..3], &[255, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn shadows_are_rasterized_beneath_the_text() {
+        use rusttype::FontCollection;
+
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let scale = Scale::uniform(20.);
+        let ascent = font.v_metrics(scale).ascent;
+        let glyph = font.glyph('o')
+            .unwrap()
+            .scaled(scale)
+            .positioned(point(0., ascent));
+        let shadow = offset_glyphs(&[glyph.clone()], (4., 4.));
+        let (black, red) = ([0., 0., 0., 1.], [1., 0., 0., 1.]);
+        let text_only = rasterize_gradient(&[(&glyph, ascent, Some(red))], (red, red)).unwrap();
+        let glyphs = [(&shadow[0], ascent, Some(black)), (&glyph, ascent, Some(red))];
+        let (coord, size, pixels) = rasterize_gradient(&glyphs, (red, red)).unwrap();
+        // The texture grows to fit the shadow, which shows where the text doesn't cover it.
+        assert_eq!(coord, text_only.0);
+        assert_eq!(size, ((text_only.1).0 + 4, (text_only.1).1 + 4));
+        assert!(pixels.chunks(4).any(|pixel| pixel == [0, 0, 0, 255]));
+        assert!(pixels.chunks(4).any(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn glyph_scale_follows_hidpi_factor() {
+        assert_eq!(glyph_scale(12., 1.), Scale::uniform(12.));
+        assert_eq!(glyph_scale(12., 2.), Scale::uniform(24.));
+        let clip = ClipRect {
+            left: 1.,
+            top: 2.,
+            right: 3.,
+            bottom: 4.,
+        };
+        assert_eq!(
+            clip.scaled(2.).scissor(100.),
+            Rect {
+                x: 2,
+                y: 92,
+                w: 4,
+                h: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn repeated_text_errors_are_coalesced() {
+        let mut last = None;
+        report_text_error(&mut last, "first".to_string());
+        report_text_error(&mut last, "first".to_string());
+        report_text_error(&mut last, "first".to_string());
+        assert_eq!(last.as_ref().map(|error| error.repeats), Some(2));
+        report_text_error(&mut last, "second".to_string());
+        let last = last.unwrap();
+        assert_eq!(last.message, "second");
+        assert_eq!(last.repeats, 0);
+    }
+
+    #[test]
+    fn empty_centered_caret_is_centered() {
+        use rusttype::FontCollection;
+        use std::f32::INFINITY;
+
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let mut fonts = StdHashMap::new();
+        fonts.insert(FontId(0), font);
+        let ui_layout = UiLayout {
+            layout: Layout::SingleLine {
+                line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+                h_align: HorizontalAlign::Center,
+                v_align: VerticalAlign::Center,
+            },
+            align_h: HorizontalAlignment::Center,
+            align_v: VerticalAlignment::Center,
+            direction: TextDirection::LeftToRight,
+            line_spacing: 1.0,
+            kerning: true,
+            letter_spacing: 0.0,
+            tab_width: 0.0,
+            pixel_perfect: false,
+            visible: None,
+        };
+        let section = VariedSection {
+            screen_position: (50., 20.),
+            bounds: (INFINITY, INFINITY),
+            z: 0.,
+            layout: ui_layout.layout,
+            text: Vec::new(),
+        };
+        let caret =
+            empty_caret_position(&ui_layout, &fonts, &section, FontId(0), Scale::uniform(20.))
+                .unwrap();
+        assert!((caret.x - 50.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn control_characters_are_replaced() {
+        let mut string = String::new();
+        push_normalized(&mut string, "a\r\nb\u{7}\t", false, false);
+        assert_eq!(string, "a  b  ");
+        string.clear();
+        push_normalized(&mut string, "a\r\nb", true, false);
+        assert_eq!(string, "a \nb");
+        string.clear();
+        push_normalized(&mut string, "a\tb\n", false, true);
+        assert_eq!(string, "a\tb ");
+    }
+
+    #[test]
+    fn shadows_are_offset_copies_of_the_masked_text() {
+        fn texts<'a>(section: &VariedSection<'a>) -> Vec<(&'a str, [f32; 4])> {
+            section.text.iter().map(|text| (text.text, text.color)).collect()
+        }
+
+        let mut string = String::new();
+        push_masked(&mut string, "ab\ncd", '*', false, true, false);
+        assert_eq!(string, "**\n**");
+        let mut revealed = String::new();
+        push_masked(&mut revealed, "ab", '*', true, false, false);
+        assert_eq!(revealed, "*b");
+
+        let (red, white, black) = ([1., 0., 0., 1.], [1.; 4], [0., 0., 0., 0.5]);
+        let runs = vec![
+            TextRun {
+                range: 0..string.len(),
+                font_id: FontId(0),
+                color: red,
+                scale: Scale::uniform(20.),
+            },
+        ];
+        let section = VariedSection {
+            screen_position: (10., 20.),
+            bounds: (100., 100.),
+            z: 0.,
+            layout: Layout::Wrap {
+                line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+                h_align: HorizontalAlign::Left,
+                v_align: VerticalAlign::Top,
+            },
+            text: section_texts(&string, &runs, Some((1..4, white))),
+        };
+        let shadow = offset_section(&section, (2., 3.), black);
+        // The shadow is a second section of the same masked text moved by the offset, without
+        // the selection color.
+        assert_eq!(shadow.screen_position, (12., 23.));
+        assert_eq!(shadow.bounds, section.bounds);
+        assert_eq!(texts(&section), vec![("*", red), ("*\n*", white), ("*", red)]);
+        assert_eq!(texts(&shadow), vec![("*", black), ("*\n*", black), ("*", black)]);
+        assert_eq!(section.screen_position, (10., 20.));
+    }
+
+    #[test]
+    fn outlines_surround_the_text_in_a_single_color() {
+        assert_eq!(outline_offsets(OutlineQuality::Low).len(), 4);
+        assert_eq!(outline_offsets(OutlineQuality::High).len(), 8);
+        for &(x, y) in outline_offsets(OutlineQuality::High) {
+            assert!(((x * x + y * y).sqrt() - 1.).abs() < 1e-6);
+        }
+
+        let (red, white, black) = ([1., 0., 0., 1.], [1.; 4], [0., 0., 0., 1.]);
+        let outline = OutlineStyle {
+            width: 2.,
+            color: black,
+            quality: OutlineQuality::Low,
+        };
+        let layers = text_layers(Some((1., 1., red)), Some(outline), 2., 0.5);
+        // The shadow comes first, then a copy in every direction at the scaled width.
+        assert_eq!(layers.len(), 5);
+        assert_eq!(layers[0], ((2., 2.), [1., 0., 0., 0.5]));
+        assert_eq!(layers[1], ((0., -4.), [0., 0., 0., 0.5]));
+        assert_eq!(layers[2].0, (4., 0.));
+
+        let string = "abc";
+        let runs = vec![
+            TextRun {
+                range: 0..string.len(),
+                font_id: FontId(0),
+                color: red,
+                scale: Scale::uniform(20.),
+            },
+        ];
+        let section = VariedSection {
+            screen_position: (0., 0.),
+            bounds: (100., 100.),
+            z: 0.,
+            layout: Layout::SingleLine {
+                line_breaker: BuiltInLineBreaker::UnicodeLineBreaker,
+                h_align: HorizontalAlign::Left,
+                v_align: VerticalAlign::Top,
+            },
+            text: section_texts(string, &runs, Some((1..2, white))),
+        };
+        // Every copy of the outline drops the selection color along with the text color.
+        for &(offset, color) in &layers[1..] {
+            let copy = offset_section(&section, offset, color);
+            assert_eq!(copy.text.len(), 3);
+            assert!(copy.text.iter().all(|text| text.color == [0., 0., 0., 0.5]));
+        }
+    }
+
+    #[test]
+    fn highlights_are_merged_per_line() {
+        let rect = |left, right, top| ClipRect {
+            left,
+            top,
+            right,
+            bottom: top + 10.,
+        };
+        let merged = merge_line_rects(vec![
+            (10., rect(0., 4.5, 0.)),
+            (10., rect(5., 9.5, 0.)),
+            (20., rect(0., 3., 10.)),
+        ]);
+        assert_eq!(merged, vec![rect(0., 9.5, 0.), rect(0., 3., 10.)]);
+    }
+
+    #[test]
+    fn underline_spans_the_line_below_the_baseline() {
+        let line = ClipRect {
+            left: 10.,
+            top: 50.,
+            right: 90.,
+            bottom: 50.,
+        };
+        let quads = decoration_quads(&[line], TextDecoration::Underline, 16., -4., 20.);
+        assert_eq!(quads.len(), 1);
+        assert_eq!(quads[0].coord[0], 10.);
+        assert_eq!(quads[0].dimension[0], 80.);
+        assert!(quads[0].coord[1] > 50.);
+        assert!(quads[0].coord[1] + quads[0].dimension[1] < 54.);
+
+        let quads = decoration_quads(
+            &[line],
+            TextDecoration::UnderlineAndStrikethrough,
+            16.,
+            -4.,
+            20.,
+        );
+        assert_eq!(quads.len(), 2);
+        assert!(quads[1].coord[1] < 50.);
+        assert!(decoration_quads(&[line], TextDecoration::None, 16., -4., 20.).is_empty());
+    }
+
+    #[test]
+    fn grapheme_ranges_map_to_glyphs() {
+        let string = "ae\u{301}io";
+        // The accented e is two chars, and so two glyphs.
+        assert_eq!(grapheme_glyphs(string, 1..3), 1..4);
+        assert_eq!(grapheme_glyphs(string, 0..1), 0..1);
+        assert_eq!(grapheme_glyphs(string, 3..10), 4..5);
+        assert_eq!(grapheme_glyphs(string, 7..9), 5..5);
+    }
+
+    #[test]
+    fn trailing_spaces_advance_the_caret() {
+        use rusttype::FontCollection;
+
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let scale = Scale::uniform(20.);
+        let space = font.glyph(' ').unwrap().scaled(scale).h_metrics().advance_width;
+        let mut fonts = StdHashMap::new();
+        fonts.insert(FontId(0), font);
+        let string = "hi   ";
+        let runs = vec![
+            TextRun {
+                range: 0..string.len(),
+                font_id: FontId(0),
+                color: [0.; 4],
+                scale,
+            },
+        ];
+        // With only the glyphs of "hi" laid out, the caret at the end is past all three spaces.
+        let trailing = char_advance(&fonts, string, &runs, 2..5, 1.);
+        assert!(space > 0.);
+        assert!((trailing - (space + 1.) * 3.).abs() < 1e-3);
+        assert_eq!(char_advance(&fonts, string, &runs, 5..2, 1.), 0.);
+    }
+
+    #[test]
+    fn color_bytes_are_rounded_and_clamped() {
+        assert_eq!(color_bytes([0.5, 1.5, -0.5, 1.]), [128, 255, 0, 255]);
+        assert_eq!(color_bytes([0.2, 0.4, 0.8, 0.]), [51, 102, 204, 0]);
+    }
+
+    #[test]
+    fn hidden_graphemes_are_cut_off() {
+        let run = |range| TextRun {
+            range,
+            font_id: FontId(0),
+            color: [0.; 4],
+            scale: Scale::uniform(10.),
+        };
+        let mut string = "ae\u{301}bc".to_string();
+        let mut runs = vec![run(0..4), run(4..6)];
+        assert!(hide_graphemes(&mut string, &mut runs, 2));
+        assert_eq!(string, "ae\u{301}");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].range, 0..4);
+        assert!(hide_graphemes(&mut string, &mut runs, 0));
+        assert_eq!(string, "");
+        assert_eq!(runs[0].range, 0..0);
+        assert!(!hide_graphemes(&mut string, &mut runs, 5));
+    }
+
+    #[test]
+    fn long_text_scrolls_to_reveal_the_caret() {
+        // 300 pixels of text in a 100 pixel wide box.
+        let text = (0., 300.);
+        let bounds = (0., 100.);
+        // Typing at the end scrolls so the caret is just inside the right edge.
+        let offset = scroll_to_caret(0., 300., text, bounds, 4.);
+        assert_eq!(offset, 204.);
+        assert!(300. - offset <= bounds.1);
+        // Moving the caret within the visible part doesn't scroll.
+        assert_eq!(scroll_to_caret(offset, 250., text, bounds, 4.), offset);
+        // Moving it back to the start scrolls all the way back.
+        assert_eq!(scroll_to_caret(offset, 0., text, bounds, 4.), 0.);
+        // Text that fits is never scrolled.
+        assert_eq!(scroll_to_caret(50., 80., (0., 80.), bounds, 4.), 0.);
+    }
+}
@@ -0,0 +1,284 @@
+//! Signed distance field glyphs for `DrawUi::with_sdf_text`.
+
+use amethyst_assets::{AssetStorage, Loader, WeakHandle};
+use amethyst_renderer::{Texture, TextureData, TextureHandle, TextureMetadata};
+use fnv::FnvHashMap as HashMap;
+use rusttype::{point, Font, GlyphId, Scale};
+
+use super::*;
+
+/// The size in pixels glyphs are rasterized at to compute their distance fields.
+pub(crate) const SDF_SIZE: f32 = 48.;
+
+/// How many pixels of an `SDF_SIZE` glyph the distance field reaches beyond its outline, on both
+/// sides of it.
+const SPREAD: usize = 6;
+
+/// The width and height of the atlas texture in pixels.
+const ATLAS_SIZE: usize = 1024;
+
+/// The distance field of a glyph in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SdfGlyph {
+    /// The texture coordinates of the field in the atlas, as `[u_min, v_min, u_max, v_max]`.
+    pub tex_rect: [f32; 4],
+    /// The top left corner of the field relative to the origin of the glyph, in pixels of an
+    /// `SDF_SIZE` glyph.
+    pub offset: [f32; 2],
+    /// The width and height of the field, in pixels of an `SDF_SIZE` glyph.
+    pub size: [f32; 2],
+}
+
+/// A single texture holding the distance fields of every glyph drawn so far, at every size.
+///
+/// Glyphs are packed onto shelves as they're first drawn.  When the atlas fills up it's cleared
+/// and the glyphs are added again as they're drawn.
+pub(crate) struct SdfAtlas {
+    /// The atlas as straight alpha RGBA, top row first.
+    pixels: Vec<u8>,
+    /// The glyphs by the id of their font handle and their glyph id, `None` for glyphs without
+    /// an outline, like spaces.
+    glyphs: HashMap<(u32, u32), Option<SdfGlyph>>,
+    /// The fonts of the glyphs by the id of their handle, used to forget glyphs of fonts that
+    /// have been dropped as their ids may be reused.
+    fonts: HashMap<u32, WeakHandle<FontAsset>>,
+    /// The left and top edge of the free space of the current shelf and its height.
+    shelf: (usize, usize, usize),
+    /// Whether glyphs were added since the atlas was last loaded into a texture.
+    dirty: bool,
+    /// The textures of the atlas, the newest last.  Older textures are drawn until the newest
+    /// one is loaded.
+    textures: Vec<TextureHandle>,
+}
+
+impl SdfAtlas {
+    /// Creates an empty atlas.
+    pub fn new() -> Self {
+        SdfAtlas {
+            pixels: vec![0; ATLAS_SIZE * ATLAS_SIZE * 4],
+            glyphs: HashMap::default(),
+            fonts: HashMap::default(),
+            shelf: (0, 0, 0),
+            dirty: false,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Finds the distance field of a glyph of `font`, adding it to the atlas if it isn't in it
+    /// yet.  Returns `None` if the glyph has no outline.
+    pub fn glyph(
+        &mut self,
+        font_handle: &FontHandle,
+        font: &Font,
+        glyph: GlyphId,
+    ) -> Option<SdfGlyph> {
+        let key = (font_handle.id(), glyph.0);
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+        self.fonts
+            .entry(font_handle.id())
+            .or_insert_with(|| font_handle.downgrade());
+        let sdf_glyph = font.glyph(glyph).and_then(|glyph| {
+            let glyph = glyph
+                .scaled(Scale::uniform(SDF_SIZE))
+                .positioned(point(0., 0.));
+            glyph.pixel_bounding_box().map(|bb| {
+                let width = (bb.max.x - bb.min.x) as usize;
+                let height = (bb.max.y - bb.min.y) as usize;
+                let mut coverage = vec![0.; width * height];
+                glyph.draw(|x, y, value| coverage[y as usize * width + x as usize] = value);
+                let (field, size) = distance_field(&coverage, (width, height));
+                let offset = [
+                    (bb.min.x - SPREAD as i32) as f32,
+                    (bb.min.y - SPREAD as i32) as f32,
+                ];
+                (field, size, offset)
+            })
+        });
+        let sdf_glyph = match sdf_glyph {
+            Some((field, size, offset)) => {
+                let corner = match self.allocate(size) {
+                    Some(corner) => corner,
+                    None => {
+                        self.clear();
+                        match self.allocate(size) {
+                            Some(corner) => corner,
+                            None => return None,
+                        }
+                    }
+                };
+                for row in 0..size.1 {
+                    for column in 0..size.0 {
+                        let i = ((corner.1 + row) * ATLAS_SIZE + corner.0 + column) * 4;
+                        self.pixels[i] = 255;
+                        self.pixels[i + 1] = 255;
+                        self.pixels[i + 2] = 255;
+                        self.pixels[i + 3] = field[row * size.0 + column];
+                    }
+                }
+                self.dirty = true;
+                // The atlas is loaded bottom row first, so v runs up from the bottom row.
+                let atlas = ATLAS_SIZE as f32;
+                Some(SdfGlyph {
+                    tex_rect: [
+                        corner.0 as f32 / atlas,
+                        1. - (corner.1 + size.1) as f32 / atlas,
+                        (corner.0 + size.0) as f32 / atlas,
+                        1. - corner.1 as f32 / atlas,
+                    ],
+                    offset,
+                    size: [size.0 as f32, size.1 as f32],
+                })
+            }
+            None => None,
+        };
+        self.glyphs.insert(key, sdf_glyph);
+        sdf_glyph
+    }
+
+    /// Forgets the glyphs of fonts that have been dropped.  Their space isn't reused until the
+    /// atlas is cleared.
+    pub fn remove_dead_fonts(&mut self) {
+        let fonts = &mut self.fonts;
+        fonts.retain(|_id, font| !font.is_dead());
+        self.glyphs.retain(|&(font, _glyph), _| fonts.contains_key(&font));
+    }
+
+    /// Loads the atlas into a new texture if glyphs were added to it.
+    pub fn load(&mut self, loader: &Loader, tex_storage: &AssetStorage<Texture>) {
+        if !self.dirty {
+            return;
+        }
+        // Textures are stored bottom row first.
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for row in self.pixels.chunks(ATLAS_SIZE * 4).rev() {
+            pixels.extend_from_slice(row);
+        }
+        let meta = TextureMetadata {
+            sampler: None,
+            mip_levels: Some(1),
+            size: Some((ATLAS_SIZE as u16, ATLAS_SIZE as u16)),
+            dynamic: false,
+            format: None,
+            channel: None,
+        };
+        let texture = loader.load_from_data(TextureData::U8(pixels, meta), (), tex_storage);
+        self.textures.push(texture);
+        self.dirty = false;
+    }
+
+    /// The newest texture of the atlas that has been loaded, if there is one.
+    pub fn texture(&mut self, tex_storage: &AssetStorage<Texture>) -> Option<TextureHandle> {
+        let loaded = self.textures
+            .iter()
+            .rposition(|texture| tex_storage.get(texture).is_some());
+        loaded.map(|loaded| {
+            self.textures.drain(..loaded);
+            self.textures[0].clone()
+        })
+    }
+
+    /// Finds space for a field of the given size, starting a new shelf if it doesn't fit on
+    /// the current one.  A pixel is left free around every field so they don't bleed into each
+    /// other when sampled.
+    fn allocate(&mut self, size: (usize, usize)) -> Option<(usize, usize)> {
+        let (width, height) = (size.0 + 1, size.1 + 1);
+        if self.shelf.0 + width > ATLAS_SIZE {
+            self.shelf = (0, self.shelf.1 + self.shelf.2, 0);
+        }
+        if self.shelf.0 + width > ATLAS_SIZE || self.shelf.1 + height > ATLAS_SIZE {
+            return None;
+        }
+        let corner = (self.shelf.0, self.shelf.1);
+        self.shelf.0 += width;
+        self.shelf.2 = self.shelf.2.max(height);
+        Some(corner)
+    }
+
+    /// Removes every glyph from the atlas.  The old textures are dropped too, as the glyphs are
+    /// placed differently from now on.
+    fn clear(&mut self) {
+        for value in &mut self.pixels {
+            *value = 0;
+        }
+        self.glyphs.clear();
+        self.shelf = (0, 0, 0);
+        self.textures.clear();
+    }
+}
+
+/// Computes the signed distance field of a glyph from its coverage, reaching `SPREAD` pixels
+/// beyond the glyph on every side.  Returns the field, top row first, and its size.
+///
+/// Distances are mapped from `-SPREAD..SPREAD` to `0..255`, so the outline is at 128 and the
+/// inside of the glyph is above it.
+pub(crate) fn distance_field(
+    coverage: &[f32],
+    (width, height): (usize, usize),
+) -> (Vec<u8>, (usize, usize)) {
+    let spread = SPREAD as isize;
+    let inside = |x: isize, y: isize| {
+        x >= 0 && y >= 0 && x < width as isize && y < height as isize
+            && coverage[y as usize * width + x as usize] >= 0.5
+    };
+    let size = (width + SPREAD * 2, height + SPREAD * 2);
+    let mut field = Vec::with_capacity(size.0 * size.1);
+    for y in 0..size.1 as isize {
+        for x in 0..size.0 as isize {
+            let (x, y) = (x - spread, y - spread);
+            let own = inside(x, y);
+            // The distance to the center of the nearest pixel on the other side of the outline.
+            let mut nearest = spread as f32;
+            for dy in -spread..spread + 1 {
+                for dx in -spread..spread + 1 {
+                    if inside(x + dx, y + dy) != own {
+                        let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                        nearest = nearest.min(distance);
+                    }
+                }
+            }
+            // The outline lies halfway between the pixels.
+            let distance = (nearest - 0.5).max(0.);
+            let signed = if own { distance } else { -distance };
+            let value = 0.5 + signed / (SPREAD as f32 * 2.);
+            field.push((value * 255.).round().max(0.).min(255.) as u8);
+        }
+    }
+    (field, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_fields_are_signed_around_the_outline() {
+        // A filled 8x8 square.
+        let coverage = vec![1.; 64];
+        let (field, size) = distance_field(&coverage, (8, 8));
+        assert_eq!(size, (8 + SPREAD * 2, 8 + SPREAD * 2));
+        let at = |x: usize, y: usize| field[y * size.0 + x];
+        // The center is inside, the corners of the field are as far out as it reaches.
+        let center = SPREAD + 4;
+        assert!(at(center, center) > 128);
+        assert!(at(0, 0) < 16);
+        // The pixels next to the outline are on either side of 128.
+        assert!(at(SPREAD, center) > 128);
+        assert!(at(SPREAD - 1, center) < 128);
+        // Further into the glyph is further from the outline.
+        assert!(at(SPREAD + 1, center) > at(SPREAD, center));
+    }
+
+    #[test]
+    fn fields_are_packed_onto_shelves() {
+        let mut atlas = SdfAtlas::new();
+        assert_eq!(atlas.allocate((10, 20)), Some((0, 0)));
+        assert_eq!(atlas.allocate((10, 5)), Some((11, 0)));
+        // Too wide for the rest of the shelf, so it starts a new one below the tallest field.
+        assert_eq!(atlas.allocate((ATLAS_SIZE - 20, 5)), Some((0, 21)));
+        assert_eq!(atlas.allocate((ATLAS_SIZE, 5)), None);
+        atlas.clear();
+        assert_eq!(atlas.allocate((10, 20)), Some((0, 0)));
+    }
+}
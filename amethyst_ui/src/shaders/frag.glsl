@@ -7,10 +7,12 @@ uniform sampler2D albedo;
 in VertexData {
   vec4 position;
   vec2 tex_coord;
+  vec4 color;
+  float distance_field;
 } vertex;
 
 out vec4 color;
 
 void main() {
-    color = texture(albedo, vertex.tex_coord);
+    color = texture(albedo, vertex.tex_coord) * vertex.color;
 }
@@ -0,0 +1,20 @@
+// TODO: Needs documentation.
+
+#version 150 core
+
+uniform sampler2D albedo;
+
+in VertexData {
+  vec4 position;
+  vec2 tex_coord;
+  vec4 color;
+  float distance_field;
+} vertex;
+
+out vec4 color;
+
+// The same as `frag.glsl`, but outputs colors with premultiplied alpha.
+void main() {
+    vec4 straight = texture(albedo, vertex.tex_coord) * vertex.color;
+    color = vec4(straight.rgb * straight.a, straight.a);
+}
@@ -0,0 +1,28 @@
+// TODO: Needs documentation.
+
+#version 150 core
+
+uniform sampler2D albedo;
+
+in VertexData {
+  vec4 position;
+  vec2 tex_coord;
+  vec4 color;
+  float distance_field;
+} vertex;
+
+out vec4 color;
+
+// The same as `frag.glsl`, but draws quads flagged as distance fields by smoothing their alpha
+// around the outline at 0.5.
+void main() {
+    vec4 texel = texture(albedo, vertex.tex_coord);
+    if (vertex.distance_field > 0.5) {
+        // Smooth over about a pixel on screen, whatever size the glyph is drawn at.
+        float width = max(fwidth(texel.a), 0.0001);
+        float coverage = smoothstep(0.5 - width, 0.5 + width, texel.a);
+        color = vec4(vertex.color.rgb, vertex.color.a * coverage);
+    } else {
+        color = texel * vertex.color;
+    }
+}
@@ -0,0 +1,28 @@
+// TODO: Needs documentation.
+
+#version 150 core
+
+uniform sampler2D albedo;
+
+in VertexData {
+  vec4 position;
+  vec2 tex_coord;
+  vec4 color;
+  float distance_field;
+} vertex;
+
+out vec4 color;
+
+// The same as `frag_sdf.glsl`, but outputs colors with premultiplied alpha.
+void main() {
+    vec4 texel = texture(albedo, vertex.tex_coord);
+    vec4 straight;
+    if (vertex.distance_field > 0.5) {
+        float width = max(fwidth(texel.a), 0.0001);
+        float coverage = smoothstep(0.5 - width, 0.5 + width, texel.a);
+        straight = vec4(vertex.color.rgb, vertex.color.a * coverage);
+    } else {
+        straight = texel * vertex.color;
+    }
+    color = vec4(straight.rgb * straight.a, straight.a);
+}
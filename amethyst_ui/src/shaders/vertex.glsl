@@ -2,11 +2,34 @@
 
 #version 150 core
 
+// The maximum number of quads in a single draw call, this has to match `MAX_QUADS` in the pass.
+#define MAX_QUADS 128
+
 // std140 is a cross platform layout.
 layout (std140) uniform VertexArgs {
     uniform vec4 proj_vec;
-    uniform vec2 coord;
-    uniform vec2 dimension;
+};
+
+struct Quad {
+    vec2 coord;
+    vec2 dimension;
+    // The texture coordinates mapped onto the quad, as (u_min, v_min, u_max, v_max).
+    vec4 tex_rect;
+    // The color the texture is multiplied with.
+    vec4 color;
+    // Whether the texture is mirrored on each axis, 1.0 if it is.
+    vec2 flip;
+    // The point the quad is rotated around, in pixels.
+    vec2 pivot;
+    // The clockwise rotation of the quad in radians.
+    float rotation;
+    // Whether the alpha of the texture is a signed distance field, 1.0 if it is.
+    float distance_field;
+};
+
+// Every instance drawn is a quad from this array.
+layout (std140) uniform Quads {
+    Quad quads[MAX_QUADS];
 };
 
 in vec3 position;
@@ -15,14 +38,28 @@ in vec2 tex_coord;
 out VertexData {
   vec4 position;
   vec2 tex_coord;
+  vec4 color;
+  float distance_field;
 } vertex;
 
 void main() {
+    Quad quad = quads[gl_InstanceID];
     vertex.position = vec4(position, 1);
-    vertex.position *= vec4(dimension, 1, 1);
-    vertex.position += vec4(coord, 0, 0);
+    vertex.position *= vec4(quad.dimension, 1, 1);
+    vertex.position += vec4(quad.coord, 0, 0);
+    if (quad.rotation != 0.0) {
+        // The y axis points down, so this turns the quad clockwise on screen.
+        float c = cos(quad.rotation);
+        float s = sin(quad.rotation);
+        vec2 offset = vertex.position.xy - quad.pivot;
+        vertex.position.xy = quad.pivot + mat2(c, s, -s, c) * offset;
+    }
     vertex.position *= proj_vec;
     vertex.position += vec4(-1, 1, 0, 0);
-    vertex.tex_coord = tex_coord;
+    // Flipping happens within the quad's texture rectangle.
+    vec2 flipped = mix(tex_coord, 1.0 - tex_coord, quad.flip);
+    vertex.tex_coord = mix(quad.tex_rect.xy, quad.tex_rect.zw, flipped);
+    vertex.color = quad.color;
+    vertex.distance_field = quad.distance_field;
     gl_Position = vertex.position;
 }
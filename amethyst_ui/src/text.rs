@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 use std::ops::Range;
 
 use amethyst_core::timing::Time;
+use amethyst_renderer::ScreenDimensions;
 use clipboard::{ClipboardContext, ClipboardProvider};
 use hibitset::BitSet;
 use rusttype::PositionedGlyph;
@@ -16,6 +17,127 @@ use winit::{ElementState, Event, KeyboardInput, ModifiersState, MouseButton, Vir
 
 use super::*;
 
+/// How text behaves when it is wider than its `UiTransform`.
+///
+/// Control characters are drawn as spaces, except for line feeds in wrapped text and tabs in
+/// text with a `tab_width`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Overflow {
+    /// The text is rendered on a single line, anything past the right edge is clipped.
+    Clip,
+    /// The text wraps onto a new line whenever it would exceed the width of the `UiTransform`,
+    /// and at every line feed.
+    Wrap,
+    /// The text is rendered on a single line, and if it doesn't fit the end is replaced with an
+    /// ellipsis.  The text is only ever cut between grapheme clusters.
+    Truncate,
+}
+
+/// The horizontal alignment of text within its `UiTransform`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HorizontalAlignment {
+    /// Text starts at the left edge of the transform.
+    Left,
+    /// Text is centered between the left and right edges of the transform.
+    Center,
+    /// Text ends at the right edge of the transform.
+    Right,
+    /// Wrapped lines are stretched to reach both edges of the transform by widening the spaces
+    /// between words.  The last line of every paragraph starts at the left edge, as does text
+    /// that isn't wrapped.
+    Justify,
+}
+
+/// The vertical alignment of text within its `UiTransform`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum VerticalAlignment {
+    /// Text starts at the top edge of the transform.
+    Top,
+    /// Text is centered between the top and bottom edges of the transform.
+    Center,
+    /// Text ends at the bottom edge of the transform.
+    Bottom,
+}
+
+/// The direction text runs in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TextDirection {
+    /// The first character is on the left and text advances to the right.
+    LeftToRight,
+    /// The first character is on the right and text advances to the left, as used by scripts
+    /// such as Arabic and Hebrew.
+    ///
+    /// Every line is mirrored as a whole, there is no bidirectional reordering.  Left to right
+    /// text embedded in it, such as numbers, is mirrored along with everything else, so it has
+    /// to be stored reversed.
+    RightToLeft,
+}
+
+/// Lines drawn along a `UiText`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextDecoration {
+    /// No lines are drawn.
+    None,
+    /// A line is drawn under the text, such as for links.
+    Underline,
+    /// A line is drawn through the middle of the text.
+    Strikethrough,
+    /// Both an underline and a strikethrough are drawn.
+    UnderlineAndStrikethrough,
+}
+
+/// How many copies of the text are drawn to produce an outline.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutlineQuality {
+    /// Four copies, offset up, down, left and right.  Corners may look slightly thin.
+    Low,
+    /// Eight copies, offset in the four cardinal and the four diagonal directions.
+    High,
+}
+
+/// An outline drawn around every glyph of a `UiText`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutlineStyle {
+    /// The thickness of the outline in pixels.
+    pub width: f32,
+    /// The color of the outline, using a range of 0.0 to 1.0 per channel.
+    pub color: [f32; 4],
+    /// How many copies of the text are used to draw the outline.
+    pub quality: OutlineQuality,
+}
+
+/// A run of text with its own font, color and size, used to build rich text in a `UiText`.
+#[derive(Clone, Debug)]
+pub struct TextSpan {
+    /// The string rendered by this span.
+    pub text: String,
+    /// The height of a line of text in pixels.
+    pub font_size: f32,
+    /// The color of the rendered text, using a range of 0.0 to 1.0 per channel.
+    pub color: [f32; 4],
+    /// The font used for rendering.
+    pub font: FontHandle,
+}
+
+impl TextSpan {
+    /// Initializes a new TextSpan
+    ///
+    /// # Parameters
+    ///
+    /// * `font`: A handle to a `Font` asset
+    /// * `text`: the glyphs to render
+    /// * `color`: RGBA color with a maximum of 1.0 and a minimum of 0.0 for each channel
+    /// * `font_size`: a uniform scale applied to the glyphs
+    pub fn new(font: FontHandle, text: String, color: [f32; 4], font_size: f32) -> TextSpan {
+        TextSpan {
+            text,
+            font_size,
+            color,
+            font,
+        }
+    }
+}
+
 /// A component used to display text in this entity's UiTransform
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
@@ -26,17 +148,85 @@ pub struct UiText {
     pub font_size: f32,
     /// The color of the rendered text, using a range of 0.0 to 1.0 per channel.
     pub color: [f32; 4],
+    /// An optional vertical gradient drawn in place of `color` and the colors of the spans,
+    /// given as the color at the top and the color at the bottom of every line.
+    ///
+    /// Gradient text is rasterized into a texture of its own whenever it changes, so it's slower
+    /// to update than flat text.  Selected text still uses the selection color.
+    pub color_gradient: Option<([f32; 4], [f32; 4])>,
     /// The font used for rendering.
     pub font: FontHandle,
-    /// If true this will be rendered as dots instead of the text.
+    /// Rich text spans.  If this isn't empty the spans are rendered one after another in place
+    /// of `text`, each with its own font, color and size.
+    ///
+    /// Editing is still done on `text`, so spans are best used for text that isn't edited. The
+    /// cursor and selection are drawn using the font of the span they're in.
+    pub spans: Vec<TextSpan>,
+    /// If true every grapheme of the text will be rendered as `mask_char` instead.
     pub password: bool,
-    /// Cached FontHandle, used to detect changes to the font.
-    pub(crate) cached_font: FontHandle,
+    /// The character shown in place of each grapheme of a password.  Defaults to a bullet.
+    pub mask_char: char,
+    /// How long in seconds the final grapheme of a password stays unmasked after a character
+    /// is typed.  If this is 0.0 it is never shown.
+    pub reveal_last: f32,
+    /// How the text is laid out when it doesn't fit on a single line.
+    pub overflow: Overflow,
+    /// An optional drop shadow drawn underneath the text, given as an x offset, a y offset and
+    /// the color of the shadow.
+    pub shadow: Option<(f32, f32, [f32; 4])>,
+    /// An optional outline drawn around the glyphs.
+    pub outline: Option<OutlineStyle>,
+    /// Ranges of graphemes of the rendered text highlighted with a background color, given as
+    /// the start of the range, its end, and the color.
+    ///
+    /// These are drawn beneath the selection of a `TextEditing`, regardless of focus.
+    pub highlights: Vec<(usize, usize, [f32; 4])>,
+    /// Lines drawn along the text in its color.  They span the whole width of every line and
+    /// are placed using the metrics of `font`.
+    pub decoration: TextDecoration,
+    /// The horizontal alignment of the text within the `UiTransform`.
+    pub align_h: HorizontalAlignment,
+    /// The vertical alignment of the text within the `UiTransform`.
+    pub align_v: VerticalAlignment,
+    /// The direction the text runs in.  The cursor moves with the arrow keys in the direction
+    /// they point to regardless.
+    pub direction: TextDirection,
+    /// A multiplier for the distance between wrapped lines, 1.0 uses the spacing of the font.
+    pub line_spacing: f32,
+    /// Whether the kerning of the font is applied between glyphs, true by default.  Turning it
+    /// off keeps the glyphs of pixel fonts on a fixed grid.
+    pub kerning: bool,
+    /// Extra space in pixels added after every glyph.
+    pub letter_spacing: f32,
+    /// The distance in pixels between tab stops.  A tab moves the text after it to the next
+    /// multiple of this from the start of its line.  If this is 0.0 tabs are drawn as spaces.
+    pub tab_width: f32,
+    /// If true the glyphs, selection highlight and cursor are moved to whole pixels, which
+    /// stops text from shimmering while it moves by fractions of a pixel.
+    pub pixel_perfect: bool,
+    /// If this is set the `UiTransform` is resized to fit the text with this much padding on
+    /// every side.
+    ///
+    /// Wrapped text keeps the width of the transform and only grows vertically, text that isn't
+    /// wrapped is never clipped or truncated.  The transform is resized after the UI is drawn,
+    /// so the new size is only used from the next frame on.
+    pub auto_size: Option<f32>,
+    /// If this is set only this many graphemes of the text are drawn, such as to reveal it a
+    /// grapheme at a time.  The cursor and selection aren't drawn while some of the text is
+    /// hidden.
+    ///
+    /// Only the visible text is laid out, so a word being revealed at the end of a wrapped line
+    /// moves onto the next line once it no longer fits.
+    pub visible_chars: Option<usize>,
+    /// Cached FontHandles of this and all spans, used to detect changes to the fonts.
+    pub(crate) cached_fonts: Vec<FontHandle>,
     /// Cached glyph positions, used to process mouse highlighting
     #[derivative(Debug = "ignore")]
     pub(crate) cached_glyphs: Vec<PositionedGlyph<'static>>,
     /// Cached id used to retrieve the `GlyphBrush` in the `UiPass`.
     pub(crate) brush_id: Option<u32>,
+    /// The time left before the final grapheme of a password is masked again.
+    pub(crate) reveal_timer: f32,
 }
 
 impl UiText {
@@ -52,16 +242,93 @@ impl UiText {
         UiText {
             text,
             color,
+            color_gradient: None,
             font_size,
-            font: font.clone(),
+            font,
+            spans: Vec::new(),
             password: false,
-            cached_font: font,
+            mask_char: '\u{2022}',
+            reveal_last: 0.0,
+            overflow: Overflow::Clip,
+            shadow: None,
+            outline: None,
+            highlights: Vec::new(),
+            decoration: TextDecoration::None,
+            align_h: HorizontalAlignment::Left,
+            align_v: VerticalAlignment::Top,
+            direction: TextDirection::LeftToRight,
+            line_spacing: 1.0,
+            kerning: true,
+            letter_spacing: 0.0,
+            tab_width: 0.0,
+            pixel_perfect: false,
+            auto_size: None,
+            visible_chars: None,
+            cached_fonts: Vec::new(),
             cached_glyphs: Vec::new(),
             brush_id: None,
+            reveal_timer: 0.0,
         }
     }
 }
 
+impl UiText {
+    /// Finds the caret position closest to the given screen coordinates, this is the number of
+    /// glyphs before the caret.  The coordinates are in physical pixels, like the cursor
+    /// positions of window events.
+    ///
+    /// Points before the first glyph of a line map to the start of that line, and points past
+    /// the last glyph map to the end of it.  This uses the glyph positions from the last time
+    /// this was drawn, so returns `None` if nothing has been laid out yet.
+    pub fn glyph_index_at(&self, x: f32, y: f32) -> Option<usize> {
+        caret_index_at(&self.cached_glyphs, self.direction, x, y)
+    }
+}
+
+/// Finds the caret position closest to a point, in the same pixels as the glyphs.
+fn caret_index_at(
+    glyphs: &[PositionedGlyph],
+    direction: TextDirection,
+    x: f32,
+    y: f32,
+) -> Option<usize> {
+    // Find the line closest to the point, glyph positions are on the baseline so compare
+    // against the vertical center of the glyphs instead.
+    let line = glyphs
+        .iter()
+        .map(|g| g.position().y)
+        .zip(glyphs.iter().map(|g| g.scale().y))
+        .fold(None, |closest: Option<(f32, f32)>, (baseline, height)| {
+            let distance = (baseline - height / 2.0 - y).abs();
+            match closest {
+                Some((_, closest_distance)) if closest_distance <= distance => closest,
+                _ => Some((baseline, distance)),
+            }
+        })
+        .map(|(baseline, _)| baseline);
+    let line = match line {
+        Some(line) => line,
+        None => return None,
+    };
+    let mut end = None;
+    for (i, glyph) in glyphs.iter().enumerate() {
+        let pos = glyph.position();
+        if pos.y != line {
+            continue;
+        }
+        let advance = glyph.unpositioned().h_metrics().advance_width;
+        let before = match direction {
+            TextDirection::LeftToRight => x < pos.x + advance / 2.0,
+            TextDirection::RightToLeft => x > pos.x + advance / 2.0,
+        };
+        if before {
+            return Some(i);
+        }
+        end = Some(i + 1);
+    }
+    end
+}
+
 impl Component for UiText {
     type Storage = DenseVecStorage<Self>;
 }
@@ -71,30 +338,47 @@ impl Component for UiText {
 pub struct TextEditing {
     /// The current editing cursor position, specified in terms of glyphs, not characters.
     pub cursor_position: isize,
-    /// The maximum graphemes permitted in this string.
-    pub max_length: usize,
+    /// The maximum graphemes permitted in this string, or `None` if there is no limit.
+    ///
+    /// Input systems should refuse to insert anything that would make the text longer than
+    /// this.
+    pub max_length: Option<usize>,
     /// The amount and direction of glyphs highlighted relative to the cursor.
     pub highlight_vector: isize,
     /// The color of the text itself when highlighted.
     pub selected_text_color: [f32; 4],
     /// The text background color when highlighted.
+    ///
+    /// While the text isn't focused every channel of this is halved, which dims the highlight
+    /// and makes it more transparent.
     pub selected_background_color: [f32; 4],
     /// If this is true the text will use a block cursor for editing.  Otherwise this uses a
-    /// standard line cursor.  This is not recommended if your font is not monospace.
+    /// standard line cursor, unless `overwrite` is set.  This is not recommended if your font is
+    /// not monospace.
     pub use_block_cursor: bool,
+    /// If this is true typed characters replace the grapheme after the cursor instead of being
+    /// inserted before it.  This always uses a block cursor.  The insert key toggles this.
+    pub overwrite: bool,
+    /// How many times the cursor blinks per second.  If this is 0.0 the cursor doesn't blink.
+    pub blink_rate: f32,
+    /// The color of the cursor.  If this is `None` the cursor uses the color of the text.
+    pub cursor_color: Option<[f32; 4]>,
+    /// How far in pixels single line text is scrolled to the left, such as when it's too long
+    /// for its `UiTransform`.  The UI pass updates this to keep the cursor visible.
+    pub scroll_offset: f32,
 
     /// This value is used to control cursor blinking.
     ///
-    /// When it is greater than 0.5 / CURSOR_BLINK_RATE the cursor should not display, when it
-    /// is greater than or equal to 1.0 / CURSOR_BLINK_RATE it should be reset to 0.  When the
-    /// player types it should be reset to 0.
+    /// When it is greater than 0.5 / blink_rate the cursor should not display, when it is
+    /// greater than or equal to 1.0 / blink_rate it should be reset to 0.  When the player types
+    /// it should be reset to 0.
     pub(crate) cursor_blink_timer: f32,
 }
 
 impl TextEditing {
     /// Create a new TextEditing Component
     pub fn new(
-        max_length: usize,
+        max_length: Option<usize>,
         selected_text_color: [f32; 4],
         selected_background_color: [f32; 4],
         use_block_cursor: bool,
@@ -106,9 +390,137 @@ impl TextEditing {
             selected_text_color,
             selected_background_color,
             use_block_cursor,
+            overwrite: false,
+            blink_rate: CURSOR_BLINK_RATE,
+            cursor_color: None,
+            scroll_offset: 0.0,
             cursor_blink_timer: 0.0,
         }
     }
+
+    /// Checks if the cursor is drawn as a block, which it is in overwrite mode or if
+    /// `use_block_cursor` is set.
+    pub fn block_cursor(&self) -> bool {
+        self.use_block_cursor || self.overwrite
+    }
+
+    /// Finds the cursor position at the end of the word the cursor is in or before, given the
+    /// text being edited.  Consecutive whitespace is skipped as if it were a single word.
+    ///
+    /// Returns the current position at the end of the text.
+    pub fn next_word_boundary(&self, text: &str) -> isize {
+        word_boundaries(text)
+            .into_iter()
+            .find(|&boundary| boundary > self.cursor_position)
+            .unwrap_or(self.cursor_position)
+    }
+
+    /// Finds the cursor position at the start of the word the cursor is in or after, given the
+    /// text being edited.  Consecutive whitespace is skipped as if it were a single word.
+    ///
+    /// Returns the current position at the start of the text.
+    pub fn prev_word_boundary(&self, text: &str) -> isize {
+        word_boundaries(text)
+            .into_iter()
+            .rev()
+            .find(|&boundary| boundary < self.cursor_position)
+            .unwrap_or(self.cursor_position)
+    }
+
+    /// Selects the word containing the grapheme at `index` of the text being edited, leaving
+    /// the cursor at the end of the word.  Consecutive whitespace is selected as a single word.
+    ///
+    /// An `index` at or past the end of the text selects the last word.
+    pub fn select_word_at(&mut self, text: &str, index: usize) {
+        let boundaries = word_boundaries(text);
+        let index = index as isize;
+        let start = boundaries
+            .iter()
+            .rev()
+            .find(|&&boundary| boundary <= index && boundary < *boundaries.last().unwrap())
+            .cloned()
+            .unwrap_or(0);
+        let end = boundaries
+            .iter()
+            .find(|&&boundary| boundary > start)
+            .cloned()
+            .unwrap_or(start);
+        self.cursor_position = end;
+        self.highlight_vector = start - end;
+        self.cursor_blink_timer = 0.0;
+    }
+
+    /// Selects the graphemes between the indices `from` and `to` of the text being edited,
+    /// leaving the cursor at `to`.  Indices past the end of the text are moved to the end.
+    ///
+    /// If `from` and `to` are the same this only moves the cursor.
+    pub fn select_range(&mut self, text: &str, from: usize, to: usize) {
+        let len = text.graphemes(true).count();
+        let from = from.min(len) as isize;
+        let to = to.min(len) as isize;
+        self.cursor_position = to;
+        self.highlight_vector = from - to;
+        self.cursor_blink_timer = 0.0;
+    }
+
+    /// Selects the text of `ui_text` between the screen coordinates `from` and `to`, such as
+    /// the start and end of a mouse drag, leaving the cursor at `to`.
+    ///
+    /// Points are mapped to caret positions with `UiText::glyph_index_at`, so points outside of
+    /// a line select up to its start or end.  Nothing is selected if `ui_text` hasn't been laid
+    /// out yet.
+    pub fn select_between(&mut self, ui_text: &UiText, from: (f32, f32), to: (f32, f32)) {
+        let index = |(x, y): (f32, f32)| ui_text.glyph_index_at(x, y).unwrap_or(0);
+        self.select_range(&ui_text.text, index(from), index(to));
+    }
+}
+
+/// The default color of selected text.
+const SELECTED_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// The default color of the highlight behind selected text.
+const SELECTED_BACKGROUND_COLOR: [f32; 4] = [0.2, 0.4, 0.9, 1.0];
+
+/// The longest time in seconds between two clicks for them to count as a double click.
+const DOUBLE_CLICK_TIME: f64 = 0.5;
+
+/// Whether the cursor is over the transform.  Cursor positions are in physical pixels, while
+/// transforms are laid out in logical pixels.
+fn contains_cursor(transform: &UiTransform, cursor: (f32, f32), hidpi: f32) -> bool {
+    let (x, y) = (cursor.0 / hidpi, cursor.1 / hidpi);
+    transform.pixel_x <= x && transform.pixel_x + transform.pixel_width >= x
+        && transform.pixel_y <= y && transform.pixel_y + transform.pixel_height >= y
+}
+
+/// The grapheme indices of the word boundaries in `text`, including its start and end.  There
+/// are no boundaries between consecutive whitespace.
+fn word_boundaries(text: &str) -> Vec<isize> {
+    let mut boundaries = vec![0];
+    let mut graphemes = 0;
+    let mut after_whitespace = false;
+    for word in text.split_word_bounds() {
+        let whitespace = word.chars().all(char::is_whitespace);
+        if whitespace && after_whitespace {
+            boundaries.pop();
+        }
+        graphemes += word.graphemes(true).count() as isize;
+        boundaries.push(graphemes);
+        after_whitespace = whitespace;
+    }
+    boundaries
+}
+
+impl Default for TextEditing {
+    /// An editable text without a length limit, with a line cursor and white text on a blue
+    /// highlight when selected.
+    fn default() -> Self {
+        TextEditing::new(
+            None,
+            SELECTED_TEXT_COLOR,
+            SELECTED_BACKGROUND_COLOR,
+            false,
+        )
+    }
 }
 
 impl Component for TextEditing {
@@ -128,8 +540,14 @@ pub struct UiSystem {
     tab_order_cache: CachedTabOrder,
     /// This is set to true while the left mouse button is pressed.
     left_mouse_button_pressed: bool,
-    /// The screen coordinates of the mouse
+    /// The screen coordinates of the mouse, in physical pixels.
     mouse_position: (f32, f32),
+    /// The editable entity that was last clicked and the time it was clicked at, used to
+    /// detect double clicks.
+    last_click: Option<(Entity, f64)>,
+    /// The screen coordinates the left mouse button was last pressed at, where selections made
+    /// by dragging start.
+    drag_start: (f32, f32),
 }
 
 impl UiSystem {
@@ -143,6 +561,8 @@ impl UiSystem {
             },
             left_mouse_button_pressed: false,
             mouse_position: (0., 0.),
+            last_click: None,
+            drag_start: (0., 0.),
         }
     }
 }
@@ -156,11 +576,21 @@ impl<'a> System<'a> for UiSystem {
         FetchMut<'a, UiFocused>,
         Fetch<'a, EventChannel<Event>>,
         Fetch<'a, Time>,
+        Fetch<'a, ScreenDimensions>,
     );
 
     fn run(
         &mut self,
-        (entities, mut text, mut editable, transform, mut focused, events, time): Self::SystemData,
+        (
+            entities,
+            mut text,
+            mut editable,
+            transform,
+            mut focused,
+            events,
+            time,
+            screen_dimensions,
+        ): Self::SystemData,
     ) {
         // Populate and update the tab order cache.
         {
@@ -221,6 +651,7 @@ impl<'a> System<'a> for UiSystem {
                 let normalized = text.text.nfd().collect::<String>();
                 text.text = normalized;
             }
+            text.reveal_timer = (text.reveal_timer - time.delta_real_seconds()).max(0.0);
         }
 
         {
@@ -231,8 +662,12 @@ impl<'a> System<'a> for UiSystem {
                     .next()
             });
             if let Some((ref mut _focused_text, ref mut focused_edit)) = focused_text_edit {
-                focused_edit.cursor_blink_timer += time.delta_real_seconds();
-                if focused_edit.cursor_blink_timer >= 1.0 / CURSOR_BLINK_RATE {
+                if focused_edit.blink_rate > 0.0 {
+                    focused_edit.cursor_blink_timer += time.delta_real_seconds();
+                    if focused_edit.cursor_blink_timer >= 1.0 / focused_edit.blink_rate {
+                        focused_edit.cursor_blink_timer = 0.0;
+                    }
+                } else {
                     focused_edit.cursor_blink_timer = 0.0;
                 }
             }
@@ -291,55 +726,13 @@ impl<'a> System<'a> for UiSystem {
                                 .next()
                         });
                         if let Some((ref mut focused_text, ref mut focused_edit)) =
-                            focused_text_edit 
+                            focused_text_edit
                         {
-                            use std::f32::NAN;
-
-                            let mouse_x = self.mouse_position.0;
-                            let mouse_y = self.mouse_position.1;
-                            // Find the glyph closest to the mouse position.
-                            focused_edit.highlight_vector = focused_text
-                                .cached_glyphs
-                                .iter()
-                                .enumerate()
-                                .fold((0, (NAN, NAN)), |(index, (x, y)), (i, g)| {
-                                    let pos = g.position();
-                                    // Use Pythagorean theorem to compute distance
-                                    if ((x - mouse_x).powi(2) + (y - mouse_y).powi(2)).sqrt()
-                                        < ((pos.x - mouse_x).powi(2)
-                                            + (pos.y - mouse_y).powi(2))
-                                            .sqrt()
-                                    {
-                                        (index, (x, y))
-                                    } else {
-                                        (i, (pos.x, pos.y))
-                                    }
-                                })
-                                .0
-                                as isize - focused_edit.cursor_position;
-                            // The end of the text, while not a glyph, is still something
-                            // you'll likely want to click your cursor to, so if the cursor is
-                            // near the end of the text, check if we should put it at the end
-                            // of the text.
-                            if focused_edit.cursor_position + focused_edit.highlight_vector + 1
-                                == focused_text.cached_glyphs.len() as isize
-                            {
-                                if let Some(last_glyph) =
-                                    focused_text.cached_glyphs.iter().last()
-                                {
-                                    if (last_glyph.position().x - mouse_x).abs()
-                                        > ((last_glyph.position().x
-                                            + last_glyph
-                                                .unpositioned()
-                                                .h_metrics()
-                                                .advance_width)
-                                            - mouse_x)
-                                            .abs()
-                                    {
-                                        focused_edit.highlight_vector += 1;
-                                    }
-                                }
-                            }
+                            focused_edit.select_between(
+                                focused_text,
+                                self.drag_start,
+                                self.mouse_position,
+                            );
                         }
                     }
                 }
@@ -359,14 +752,10 @@ impl<'a> System<'a> for UiSystem {
                             self.left_mouse_button_pressed = true;
                             // Start searching for an element to focus.
                             // Find all eligible elements
+                            let hidpi = screen_dimensions.hidpi_factor();
                             let mut eligible = (&*entities, &transform)
                                 .join()
-                                .filter(|&(_, t)| {
-                                    t.x <= self.mouse_position.0
-                                        && t.x + t.width >= self.mouse_position.0
-                                        && t.y <= self.mouse_position.1
-                                        && t.y + t.height >= self.mouse_position.1
-                                })
+                                .filter(|&(_, t)| contains_cursor(t, self.mouse_position, hidpi))
                                 .collect::<Vec<_>>();
                             // In instances of ambiguity we want to select the element with the
                             // lowest Z order, so we need to find the lowest Z order value among
@@ -400,53 +789,28 @@ impl<'a> System<'a> for UiSystem {
                             if let Some((ref mut focused_text, ref mut focused_edit)) =
                                 focused_text_edit
                             {
-                                use std::f32::NAN;
-
-                                let mouse_x = self.mouse_position.0;
-                                let mouse_y = self.mouse_position.1;
-                                // Find the glyph closest to the click position.
-                                focused_edit.highlight_vector = 0;
-                                focused_edit.cursor_position = focused_text
-                                    .cached_glyphs
-                                    .iter()
-                                    .enumerate()
-                                    .fold((0, (NAN, NAN)), |(index, (x, y)), (i, g)| {
-                                        let pos = g.position();
-                                        // Use Pythagorean theorem to compute distance
-                                        if ((x - mouse_x).powi(2) + (y - mouse_y).powi(2)).sqrt()
-                                            < ((pos.x - mouse_x).powi(2)
-                                                + (pos.y - mouse_y).powi(2))
-                                                .sqrt()
-                                        {
-                                            (index, (x, y))
-                                        } else {
-                                            (i, (pos.x, pos.y))
-                                        }
-                                    })
-                                    .0
-                                    as isize;
-                                // The end of the text, while not a glyph, is still something
-                                // you'll likely want to click your cursor to, so if the cursor is
-                                // near the end of the text, check if we should put it at the end
-                                // of the text.
-                                if focused_edit.cursor_position + 1
-                                    == focused_text.cached_glyphs.len() as isize
-                                {
-                                    if let Some(last_glyph) =
-                                        focused_text.cached_glyphs.iter().last()
-                                    {
-                                        if (last_glyph.position().x - mouse_x).abs()
-                                            > ((last_glyph.position().x
-                                                + last_glyph
-                                                    .unpositioned()
-                                                    .h_metrics()
-                                                    .advance_width)
-                                                - mouse_x)
-                                                .abs()
-                                        {
-                                            focused_edit.cursor_position += 1;
-                                        }
+                                let (mouse_x, mouse_y) = self.mouse_position;
+                                let index = focused_text.glyph_index_at(mouse_x, mouse_y);
+                                let now = time.absolute_real_time_seconds();
+                                let double_click = match (self.last_click, focused.entity) {
+                                    (Some((clicked, at)), Some(entity)) => {
+                                        clicked == entity && now - at <= DOUBLE_CLICK_TIME
                                     }
+                                    _ => false,
+                                };
+                                if double_click {
+                                    focused_edit.select_word_at(
+                                        &focused_text.text,
+                                        index.unwrap_or(0),
+                                    );
+                                    // Dragging would replace the selected word.
+                                    self.left_mouse_button_pressed = false;
+                                    self.last_click = None;
+                                } else {
+                                    focused_edit.highlight_vector = 0;
+                                    focused_edit.cursor_position = index.unwrap_or(0) as isize;
+                                    self.drag_start = self.mouse_position;
+                                    self.last_click = focused.entity.map(|entity| (entity, now));
                                 }
                             }
                         }
@@ -495,6 +859,7 @@ impl<'a> System<'a> for UiSystem {
                             });
                         match input {
                             '\u{8}' /*Backspace*/ => if !deleted {
+                                focused_text.reveal_timer = 0.0;
                                 if focused_edit.cursor_position > 0 {
                                     if let Some((byte, len)) = focused_text
                                         .text
@@ -509,10 +874,21 @@ impl<'a> System<'a> for UiSystem {
                                 }
                             },
                             _ => {
-                                if focused_text.text.graphemes(true).count() < focused_edit.max_length {
-                                    focused_text.text.insert(start_byte, input);
-                                    focused_edit.cursor_position += 1;
+                                if focused_edit.overwrite && !deleted {
+                                    let len = focused_text.text[start_byte..]
+                                        .graphemes(true)
+                                        .next()
+                                        .map_or(0, |grapheme| grapheme.len());
+                                    focused_text.text.drain(start_byte..start_byte + len);
                                 }
+                                let mut buffer = [0; 4];
+                                insert_capped(
+                                    focused_edit,
+                                    &mut focused_text.text,
+                                    start_byte,
+                                    input.encode_utf8(&mut buffer),
+                                );
+                                focused_text.reveal_timer = focused_text.reveal_last;
                             }
                         }
                     }
@@ -529,7 +905,7 @@ impl<'a> System<'a> for UiSystem {
                                 ..
                             },
                         ..
-                    } => match v_keycode {
+                    } => match mirror_arrows(v_keycode, focused_text.direction) {
                         VirtualKeyCode::Home | VirtualKeyCode::Up => {
                             focused_edit.highlight_vector = if modifiers.shift {
                                 focused_edit.cursor_position
@@ -549,6 +925,10 @@ impl<'a> System<'a> for UiSystem {
                             focused_edit.cursor_position = glyph_len;
                             focused_edit.cursor_blink_timer = 0.0;
                         }
+                        VirtualKeyCode::Insert => {
+                            focused_edit.overwrite = !focused_edit.overwrite;
+                            focused_edit.cursor_blink_timer = 0.0;
+                        }
                         VirtualKeyCode::Delete => {
                             if !delete_highlighted(focused_edit, focused_text) {
                                 if let Some((start_byte, start_glyph_len)) = focused_text
@@ -569,17 +949,8 @@ impl<'a> System<'a> for UiSystem {
                         {
                             if focused_edit.cursor_position > 0 {
                                 let delta = if ctrl_or_cmd(&modifiers) {
-                                    let mut graphemes = 0;
-                                    for word in focused_text.text.split_word_bounds() {
-                                        let word_graphemes = word.graphemes(true).count() as isize;
-                                        if graphemes + word_graphemes
-                                            >= focused_edit.cursor_position
-                                        {
-                                            break;
-                                        }
-                                        graphemes += word_graphemes;
-                                    }
-                                    focused_edit.cursor_position - graphemes
+                                    focused_edit.cursor_position
+                                        - focused_edit.prev_word_boundary(&focused_text.text)
                                 } else {
                                     1
                                 };
@@ -600,14 +971,8 @@ impl<'a> System<'a> for UiSystem {
                                 let glyph_len = focused_text.text.graphemes(true).count();
                                 if (focused_edit.cursor_position as usize) < glyph_len {
                                     let delta = if ctrl_or_cmd(&modifiers) {
-                                        let mut graphemes = 0;
-                                        for word in focused_text.text.split_word_bounds() {
-                                            graphemes += word.graphemes(true).count() as isize;
-                                            if graphemes > focused_edit.cursor_position {
-                                                break;
-                                            }
-                                        }
-                                        graphemes - focused_edit.cursor_position
+                                        focused_edit.next_word_boundary(&focused_text.text)
+                                            - focused_edit.cursor_position
                                     } else {
                                         1
                                     };
@@ -648,18 +1013,12 @@ impl<'a> System<'a> for UiSystem {
                             let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
                             if let Ok(contents) = ctx.get_contents() {
                                 let index = cursor_byte_index(focused_edit, focused_text);
-                                let empty_space = focused_edit.max_length
-                                    - focused_text.text.graphemes(true).count();
-                                let contents = contents.graphemes(true).take(empty_space).fold(
-                                    String::new(),
-                                    |mut init, new| {
-                                        init.push_str(new);
-                                        init
-                                    },
+                                insert_capped(
+                                    focused_edit,
+                                    &mut focused_text.text,
+                                    index,
+                                    &contents,
                                 );
-                                focused_text.text.insert_str(index, &contents);
-                                focused_edit.cursor_position +=
-                                    contents.graphemes(true).count() as isize;
                             }
                         },
                         _ => {}
@@ -677,6 +1036,16 @@ fn ctrl_or_cmd(modifiers: &ModifiersState) -> bool {
         || (cfg!(not(target_os = "macos")) && modifiers.ctrl)
 }
 
+/// Swaps the left and right arrow keys for right to left text, so the cursor follows the arrow
+/// on screen.
+fn mirror_arrows(key: VirtualKeyCode, direction: TextDirection) -> VirtualKeyCode {
+    match (key, direction) {
+        (VirtualKeyCode::Left, TextDirection::RightToLeft) => VirtualKeyCode::Right,
+        (VirtualKeyCode::Right, TextDirection::RightToLeft) => VirtualKeyCode::Left,
+        (key, _) => key,
+    }
+}
+
 fn read_highlighted<'a>(edit: &TextEditing, text: &'a UiText) -> &'a str {
     let range = highlighted_bytes(edit, text);
     &text.text[range]
@@ -711,6 +1080,25 @@ fn cursor_byte_index(edit: &TextEditing, text: &UiText) -> usize {
         .unwrap_or(text.text.len())
 }
 
+/// Inserts as much of `input` into `text` at the byte `index` as `max_length` allows, moving the
+/// cursor past the inserted graphemes.
+///
+/// Only whole graphemes are inserted.
+fn insert_capped(edit: &mut TextEditing, text: &mut String, index: usize, input: &str) {
+    let allowed = match edit.max_length {
+        Some(max_length) => max_length.saturating_sub(text.graphemes(true).count()),
+        None => usize::max_value(),
+    };
+    let end = input
+        .grapheme_indices(true)
+        .nth(allowed)
+        .map(|i| i.0)
+        .unwrap_or(input.len());
+    let inserted = &input[..end];
+    text.insert_str(index, inserted);
+    edit.cursor_position += inserted.graphemes(true).count() as isize;
+}
+
 /// Returns the byte indices that are highlighted in the string.
 fn highlighted_bytes(edit: &TextEditing, text: &UiText) -> Range<usize> {
     let start = edit.cursor_position
@@ -729,3 +1117,110 @@ fn highlighted_bytes(edit: &TextEditing, text: &UiText) -> Range<usize> {
         .unwrap_or(text.text.len());
     start_byte..end_byte
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insertion_stops_at_max_length() {
+        let mut edit = TextEditing::new(Some(4), [0.; 4], [0.; 4], false);
+        let mut text = String::new();
+        insert_capped(&mut edit, &mut text, 0, "abc");
+        insert_capped(&mut edit, &mut text, 3, "de\u{301}f");
+        assert_eq!(text, "abcd");
+        assert_eq!(edit.cursor_position, 4);
+        insert_capped(&mut edit, &mut text, 4, "g");
+        assert_eq!(text, "abcd");
+
+        // A combining sequence counts as a single grapheme.
+        let mut edit = TextEditing::new(Some(2), [0.; 4], [0.; 4], false);
+        let mut text = String::new();
+        insert_capped(&mut edit, &mut text, 0, "e\u{301}xy");
+        assert_eq!(text, "e\u{301}x");
+        assert_eq!(edit.cursor_position, 2);
+    }
+
+    #[test]
+    fn word_boundaries_skip_whitespace_runs() {
+        let text = "foo   bar";
+        let mut edit = TextEditing::new(None, [0.; 4], [0.; 4], false);
+        assert_eq!(edit.prev_word_boundary(text), 0);
+        assert_eq!(edit.next_word_boundary(text), 3);
+        edit.cursor_position = 3;
+        assert_eq!(edit.next_word_boundary(text), 6);
+        edit.cursor_position = 4;
+        assert_eq!(edit.prev_word_boundary(text), 3);
+        assert_eq!(edit.next_word_boundary(text), 6);
+        edit.cursor_position = 9;
+        assert_eq!(edit.next_word_boundary(text), 9);
+        assert_eq!(edit.prev_word_boundary(text), 6);
+    }
+
+    #[test]
+    fn select_word_at_spans_the_word() {
+        let text = "foo  bar";
+        let mut edit = TextEditing::new(None, [0.; 4], [0.; 4], false);
+        edit.select_word_at(text, 1);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (3, -3));
+        edit.select_word_at(text, 4);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (5, -2));
+        edit.select_word_at(text, 8);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (8, -3));
+        edit.select_word_at("", 0);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (0, 0));
+    }
+
+    #[test]
+    fn select_range_is_clamped_to_the_text() {
+        let text = "ab\u{e9}c";
+        let mut edit = TextEditing::new(None, [0.; 4], [0.; 4], false);
+        edit.select_range(text, 1, 3);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (3, -2));
+        edit.select_range(text, 10, 0);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (0, 4));
+        edit.select_range(text, 2, 2);
+        assert_eq!((edit.cursor_position, edit.highlight_vector), (2, 0));
+    }
+
+    #[test]
+    fn cursor_hits_elements_and_glyphs_at_2x() {
+        use rusttype::{point, FontCollection, Scale};
+
+        let hidpi = 2.;
+        // In logical pixels this covers 30 to 50 on both axes.
+        let transform = UiTransform::new("test".to_string(), 30., 30., 0., 20., 20., 0);
+        assert!(contains_cursor(&transform, (80., 80.), hidpi));
+        assert!(!contains_cursor(&transform, (40., 40.), hidpi));
+        assert!(contains_cursor(&transform, (40., 40.), 1.));
+
+        // Glyphs are laid out in physical pixels, like the cursor.
+        let font = FontCollection::from_bytes(&include_bytes!(
+            "../../examples/assets/font/square.ttf"
+        )[..])
+            .into_fonts()
+            .nth(0)
+            .unwrap();
+        let scale = Scale::uniform(20. * hidpi);
+        let ascent = font.v_metrics(scale).ascent;
+        let mut x = 60.;
+        let glyphs = "ab"
+            .chars()
+            .map(|c| {
+                let glyph = font.glyph(c).unwrap().scaled(scale);
+                let advance = glyph.h_metrics().advance_width;
+                let glyph = glyph.positioned(point(x, 60. + ascent));
+                x += advance;
+                (glyph, advance)
+            })
+            .collect::<Vec<_>>();
+        let second = glyphs[1].0.position().x;
+        let advance = glyphs[1].1;
+        let glyphs = glyphs.into_iter().map(|glyph| glyph.0).collect::<Vec<_>>();
+        let y = 60. + ascent / 2.;
+        let ltr = TextDirection::LeftToRight;
+        assert_eq!(caret_index_at(&glyphs, ltr, second + advance * 0.25, y), Some(1));
+        assert_eq!(caret_index_at(&glyphs, ltr, second + advance * 0.75, y), Some(2));
+        assert_eq!(caret_index_at(&glyphs, ltr, 0., y), Some(0));
+    }
+}
@@ -1,6 +1,70 @@
 use specs::{Component, DenseVecStorage, FlaggedStorage};
 use std::marker::PhantomData;
 
+/// The point of the screen the position of a `UiTransform` is relative to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Anchor {
+    /// The top left corner of the screen.
+    TopLeft,
+    /// The middle of the top edge of the screen.
+    TopMiddle,
+    /// The top right corner of the screen.
+    TopRight,
+    /// The middle of the left edge of the screen.
+    MiddleLeft,
+    /// The center of the screen.
+    Middle,
+    /// The middle of the right edge of the screen.
+    MiddleRight,
+    /// The bottom left corner of the screen.
+    BottomLeft,
+    /// The middle of the bottom edge of the screen.
+    BottomMiddle,
+    /// The bottom right corner of the screen.
+    BottomRight,
+}
+
+impl Anchor {
+    /// The position of this point on a screen of the given width and height.
+    pub fn position(&self, width: f32, height: f32) -> (f32, f32) {
+        use self::Anchor::*;
+
+        let x = match *self {
+            TopLeft | MiddleLeft | BottomLeft => 0.0,
+            TopMiddle | Middle | BottomMiddle => width / 2.0,
+            TopRight | MiddleRight | BottomRight => width,
+        };
+        let y = match *self {
+            TopLeft | TopMiddle | TopRight => 0.0,
+            MiddleLeft | Middle | MiddleRight => height / 2.0,
+            BottomLeft | BottomMiddle | BottomRight => height,
+        };
+        (x, y)
+    }
+}
+
+impl Default for Anchor {
+    fn default() -> Self {
+        Anchor::TopLeft
+    }
+}
+
+/// How the width and height of a `UiTransform` are measured.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScaleMode {
+    /// The size is given in pixels.
+    Pixel,
+    /// The size is given as a fraction of the size of the screen, so a width of 0.2 is a fifth
+    /// of the width of the screen.  The element is always at least a pixel in size.
+    Percent,
+}
+
+impl Default for ScaleMode {
+    fn default() -> Self {
+        ScaleMode::Pixel
+    }
+}
+
 
 /// The raw pixels on screen that are populated.
 ///
@@ -10,22 +74,42 @@ use std::marker::PhantomData;
 pub struct UiTransform {
     /// An identifier. Serves no purpose other than to help you distinguish between UI elements.
     pub id: String,
-    /// X coordinate, 0 is the left edge, while the width of the screen is the right edge.
+    /// X coordinate of the left edge relative to the anchor.  With the default anchor 0 is the
+    /// left edge, while the width of the screen is the right edge.
     pub x: f32,
-    /// Y coordinate, 0 is the top edge, while the height of the screen is the bottom edge.
+    /// Y coordinate of the top edge relative to the anchor.  With the default anchor 0 is the
+    /// top edge, while the height of the screen is the bottom edge.
     pub y: f32,
     /// Z order, entities with a lower Z order will be rendered on top of entities with a higher
     /// Z order.
     pub z: f32,
-    /// The width of this UI element
+    /// The width of this UI element, measured according to `size_mode`.
     pub width: f32,
-    /// The height of this UI element
+    /// The height of this UI element, measured according to `size_mode`.
     pub height: f32,
+    /// How `width` and `height` are measured.  Defaults to pixels.
+    pub size_mode: ScaleMode,
     /// The UI element tab order.  When the player presses tab the UI focus will shift to the
     /// UI element with the next highest tab order, or if another element with the same tab_order
     /// as this one exists they are ordered according to Entity creation order.  Shift-tab walks
     /// this ordering backwards.
     pub tab_order: i32,
+    /// The point of the screen `x` and `y` are relative to, so the element stays in place
+    /// relative to it when the window is resized.  Defaults to the top left corner.
+    pub anchor: Anchor,
+    /// The clockwise rotation of this UI element around its center in radians.  Only images are
+    /// rotated, text is drawn unrotated.
+    pub rotation: f32,
+    /// The x coordinate of the left edge on the screen, resolved from `x` and the anchor by the
+    /// UI pass.
+    pub(crate) pixel_x: f32,
+    /// The y coordinate of the top edge on the screen, resolved from `y` and the anchor by the
+    /// UI pass.
+    pub(crate) pixel_y: f32,
+    /// The width on the screen in pixels, resolved from `width` by the UI pass.
+    pub(crate) pixel_width: f32,
+    /// The height on the screen in pixels, resolved from `height` by the UI pass.
+    pub(crate) pixel_height: f32,
     /// A private field to keep this from being initialized without new.
     pd: PhantomData<u8>,
 }
@@ -48,12 +132,92 @@ impl UiTransform {
             z,
             width,
             height,
+            size_mode: ScaleMode::Pixel,
             tab_order,
+            anchor: Anchor::TopLeft,
+            rotation: 0.,
+            pixel_x: x,
+            pixel_y: y,
+            pixel_width: width,
+            pixel_height: height,
             pd: PhantomData,
         }
     }
+
+    /// Resolves the position and size of this on a screen of the given width and height.
+    pub(crate) fn resolve(&mut self, width: f32, height: f32) {
+        let (x, y) = self.anchor.position(width, height);
+        self.pixel_x = x + self.x;
+        self.pixel_y = y + self.y;
+        match self.size_mode {
+            ScaleMode::Pixel => {
+                self.pixel_width = self.width;
+                self.pixel_height = self.height;
+            }
+            ScaleMode::Percent => {
+                self.pixel_width = (self.width * width).max(1.0);
+                self.pixel_height = (self.height * height).max(1.0);
+            }
+        }
+    }
+
+    /// Sets the size of this in pixels, converting it to the `size_mode` of this on a screen of
+    /// the given width and height.
+    pub(crate) fn set_pixel_size(&mut self, size: (f32, f32), screen: (f32, f32)) {
+        self.pixel_width = size.0;
+        self.pixel_height = size.1;
+        match self.size_mode {
+            ScaleMode::Pixel => {
+                self.width = size.0;
+                self.height = size.1;
+            }
+            ScaleMode::Percent => {
+                self.width = size.0 / screen.0;
+                self.height = size.1 / screen.1;
+            }
+        }
+    }
 }
 
 impl Component for UiTransform {
     type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchored_positions_follow_the_screen() {
+        let mut transform = UiTransform::new("test".to_string(), -110., -60., 0., 100., 50., 0);
+        transform.anchor = Anchor::BottomRight;
+        transform.resolve(800., 600.);
+        assert_eq!((transform.pixel_x, transform.pixel_y), (690., 540.));
+        transform.resolve(1024., 768.);
+        assert_eq!((transform.pixel_x, transform.pixel_y), (914., 708.));
+
+        transform.anchor = Anchor::TopLeft;
+        transform.x = 10.;
+        transform.y = 20.;
+        transform.resolve(1024., 768.);
+        assert_eq!((transform.pixel_x, transform.pixel_y), (10., 20.));
+        assert_eq!(Anchor::Middle.position(800., 600.), (400., 300.));
+    }
+
+    #[test]
+    fn percent_sizes_follow_the_screen() {
+        let mut transform = UiTransform::new("test".to_string(), 0., 0., 0., 0.2, 0.5, 0);
+        transform.size_mode = ScaleMode::Percent;
+        transform.resolve(800., 600.);
+        assert_eq!((transform.pixel_width, transform.pixel_height), (160., 300.));
+        transform.resolve(1000., 400.);
+        assert_eq!((transform.pixel_width, transform.pixel_height), (200., 200.));
+        // Tiny percentages are still a pixel in size.
+        transform.width = 0.;
+        transform.resolve(1000., 400.);
+        assert_eq!(transform.pixel_width, 1.);
+        // Pixel sizes are converted back to percentages.
+        transform.set_pixel_size((500., 100.), (1000., 400.));
+        assert_eq!((transform.width, transform.height), (0.5, 0.25));
+    }
+}
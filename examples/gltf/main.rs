@@ -29,6 +29,7 @@ impl State for Example {
             GltfSceneOptions {
                 generate_tex_coords: Some((0.1, 0.1)),
                 load_animations: true,
+                ..GltfSceneOptions::default()
             },
         );
 
@@ -70,9 +70,7 @@ impl State for Example {
                 266.,
                 0,
             ))
-            .with(UiImage {
-                texture: logo.clone(),
-            })
+            .with(UiImage::new(logo.clone()))
             .build();
 
         let text = world
@@ -93,7 +91,7 @@ impl State for Example {
                 75.,
             ))
             .with(TextEditing::new(
-                12,
+                Some(12),
                 [0.0, 0.0, 0.0, 1.0],
                 [1.0, 1.0, 1.0, 1.0],
                 false,